@@ -0,0 +1,149 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Per-test artifact directories shared between parent and child.
+//!
+//! If enabled, the parent creates an empty directory for the test
+//! before spawning the child and conveys its path via the
+//! `TEST_FORK_ARTIFACTS` environment variable. The child can store
+//! screenshots, profiles, logs, and the like there, which the parent
+//! keeps (and reports) after the child exited. Artifact directories are
+//! located in `artifacts/` inside the directory output is persisted to
+//! (see [`Builder::persist_output`][crate::Builder::persist_output]),
+//! or inside the artifact directory of the current process, if any.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::junit::file_name;
+use crate::persist::output_dir;
+
+
+/// The environment variable conveying the artifact directory to the
+/// child.
+const ARTIFACTS_ENV: &str = "TEST_FORK_ARTIFACTS";
+
+
+/// The treatment of the artifact directory of a child process.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Artifacts {
+    /// Don't provide an artifact directory.
+    #[default]
+    Disabled,
+    /// Provide an artifact directory and keep it once the child
+    /// exited.
+    Keep,
+    /// Provide an artifact directory and keep it only if the child
+    /// failed.
+    KeepOnFailure,
+}
+
+
+/// Retrieve the artifact directory of the current test, if any.
+///
+/// The directory is only available inside a child process spawned with
+/// artifacts enabled (see [`Builder::artifacts`][crate::Builder::artifacts]).
+pub fn artifact_dir() -> Option<PathBuf> {
+    env::var_os(ARTIFACTS_ENV).map(PathBuf::from)
+}
+
+
+/// Determine the artifact directory for the child running the given
+/// test.
+fn dir(test_name: &str) -> io::Result<PathBuf> {
+    // A child forking on its own nests the artifact directories of its
+    // children inside its own.
+    let root = match artifact_dir() {
+        Some(dir) => dir,
+        None => output_dir()?.join("artifacts"),
+    };
+    Ok(root.join(file_name(test_name)))
+}
+
+/// Create an empty artifact directory for the child running the given
+/// test and convey it to the child process spawned by `command`.
+pub(crate) fn configure(command: &mut Command, test_name: &str) -> io::Result<()> {
+    let dir = dir(test_name)?;
+    // Remove artifacts of a previous run.
+    match fs::remove_dir_all(&dir) {
+        Ok(()) => (),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+        Err(err) => return Err(err),
+    }
+    let () = fs::create_dir_all(&dir)?;
+    command.env(ARTIFACTS_ENV, dir);
+    Ok(())
+}
+
+/// Keep and report the artifact directory of the child running the
+/// given test or remove it, as configured by `artifacts`.
+pub(crate) fn finish(test_name: &str, artifacts: Artifacts, failed: bool) {
+    let keep = match artifacts {
+        Artifacts::Disabled => return,
+        Artifacts::Keep => true,
+        Artifacts::KeepOnFailure => failed,
+    };
+
+    if let Ok(dir) = dir(test_name) {
+        if keep {
+            eprintln!(
+                "test-fork: artifacts of `{test_name}` kept in {}",
+                dir.display()
+            );
+        } else {
+            let _result = fs::remove_dir_all(&dir);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::process::ExitCode;
+
+    use crate::Builder;
+
+
+    /// Check that the child is provided with an artifact directory that
+    /// is kept if the child failed.
+    #[test]
+    fn artifacts_kept_on_failure() {
+        let test_name = fork_test_name!(artifacts_kept_on_failure);
+        let _failure = Builder::new()
+            .artifacts(Artifacts::KeepOnFailure)
+            .try_fork(fork_id!(), test_name, || {
+                let dir = artifact_dir().unwrap();
+                let () = fs::write(dir.join("log.txt"), "details").unwrap();
+                ExitCode::FAILURE
+            })
+            .unwrap_err();
+
+        let dir = dir(test_name).unwrap();
+        let log = fs::read_to_string(dir.join("log.txt")).unwrap();
+        assert_eq!(log, "details");
+        let () = fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Check that the artifact directory of a successful child is
+    /// removed, unless it is to be kept.
+    #[test]
+    fn artifacts_removed_on_success() {
+        let test_name = fork_test_name!(artifacts_removed_on_success);
+        let () = Builder::new()
+            .artifacts(Artifacts::KeepOnFailure)
+            .fork(fork_id!(), test_name, || {
+                let dir = artifact_dir().unwrap();
+                assert!(dir.is_dir());
+                let () = fs::write(dir.join("log.txt"), "details").unwrap();
+            })
+            .unwrap();
+
+        assert!(!dir(test_name).unwrap().exists());
+        assert_eq!(artifact_dir(), None);
+    }
+}
@@ -0,0 +1,229 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Pluggable backends for launching and supervising child processes.
+//!
+//! The protocol for identifying which test and which occurrence of a
+//! fork point a child is responsible for is conveyed entirely through
+//! the [`Command`] describing the child: its arguments and environment.
+//! A [`ProcessBackend`] merely decides how a process running this
+//! command gets launched, and the [`ChildProcess`] it hands out how it
+//! is waited for and killed. That allows for running children in
+//! alternative environments, such as a container or a remote machine.
+
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::process::Child;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::child::kill_tree;
+
+
+/// The default number of times to retry launching a child after a
+/// transient failure.
+pub(crate) const DEFAULT_SPAWN_RETRIES: u32 = 3;
+/// The default delay before the first retry, which doubles with every
+/// retry.
+pub(crate) const DEFAULT_SPAWN_BACKOFF: Duration = Duration::from_millis(10);
+
+
+/// A child process launched by a [`ProcessBackend`].
+pub trait ChildProcess: Debug + Send {
+    /// Retrieve the OS-assigned process identifier of the child.
+    fn id(&self) -> u32;
+
+    /// Check whether the child has exited, without blocking.
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>>;
+
+    /// Wait for the child to exit.
+    fn wait(&mut self) -> io::Result<ExitStatus>;
+
+    /// Kill the child process.
+    fn kill(&mut self) -> io::Result<()>;
+
+    /// Kill the child process along with all processes it spawned.
+    ///
+    /// The default implementation kills the process group (on Unix
+    /// systems) or the process tree (on Windows) rooted at the child
+    /// and then the child itself.
+    fn kill_group(&mut self) -> io::Result<()> {
+        let () = kill_tree(self.id())?;
+        // Make sure to kill the child itself, even if killing the
+        // group did not work out for some reason.
+        self.kill()
+    }
+
+    /// Retrieve the underlying [`Child`], if any.
+    fn as_child(&self) -> Option<&Child> {
+        None
+    }
+}
+
+impl ChildProcess for Child {
+    fn id(&self) -> u32 {
+        Self::id(self)
+    }
+
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        Self::try_wait(self)
+    }
+
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        Self::wait(self)
+    }
+
+    fn kill(&mut self) -> io::Result<()> {
+        Self::kill(self)
+    }
+
+    fn as_child(&self) -> Option<&Child> {
+        Some(self)
+    }
+}
+
+
+/// A child process as launched by a [`ProcessBackend`], along with
+/// the parent's ends of its standard I/O pipes.
+pub struct Launched {
+    /// The child process.
+    pub process: Box<dyn ChildProcess>,
+    /// The writing end of the child's standard input, if piped.
+    pub stdin: Option<Box<dyn Write + Send>>,
+    /// The reading end of the child's standard output, if piped.
+    pub stdout: Option<Box<dyn Read + Send>>,
+    /// The reading end of the child's standard error, if piped.
+    pub stderr: Option<Box<dyn Read + Send>>,
+}
+
+impl Debug for Launched {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Launched")
+            .field("process", &self.process)
+            .finish_non_exhaustive()
+    }
+}
+
+impl From<Child> for Launched {
+    fn from(mut child: Child) -> Self {
+        Self {
+            stdin: child
+                .stdin
+                .take()
+                .map(|stdin| Box::new(stdin) as Box<dyn Write + Send>),
+            stdout: child
+                .stdout
+                .take()
+                .map(|stdout| Box::new(stdout) as Box<dyn Read + Send>),
+            stderr: child
+                .stderr
+                .take()
+                .map(|stderr| Box::new(stderr) as Box<dyn Read + Send>),
+            process: Box::new(child),
+        }
+    }
+}
+
+
+/// A backend for launching child processes.
+///
+/// A backend can be configured via
+/// [`Builder::backend`][crate::Builder::backend].
+pub trait ProcessBackend: Debug + Send + Sync {
+    /// Launch a child process as described by `command`.
+    ///
+    /// `command` is fully configured: the child has to run its program
+    /// with its arguments, environment, and standard I/O
+    /// configuration. Output of the child is expected to be made
+    /// available through the pipes of the returned [`Launched`]
+    /// object.
+    fn launch(&self, command: &mut Command) -> io::Result<Launched>;
+}
+
+
+/// The default [`ProcessBackend`], spawning children via
+/// [`Command::spawn`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CommandBackend;
+
+impl ProcessBackend for CommandBackend {
+    fn launch(&self, command: &mut Command) -> io::Result<Launched> {
+        let child = command.spawn()?;
+        Ok(Launched::from(child))
+    }
+}
+
+
+/// Check whether a failure to launch a child is transient, i.e.,
+/// whether it may go away when trying again.
+fn is_transient(err: &io::Error) -> bool {
+    // `EAGAIN` (e.g., because of hitting the process limit) maps to
+    // `WouldBlock`.
+    if matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+    ) {
+        return true
+    }
+
+    // The executable may still be open for writing by a concurrent
+    // `fork` in some other thread that has yet to `exec`.
+    #[cfg(unix)]
+    if err.raw_os_error() == Some(libc::ETXTBSY) {
+        return true
+    }
+    false
+}
+
+
+/// A [`ProcessBackend`] retrying to launch a child with exponential
+/// backoff if launching failed transiently.
+#[derive(Debug)]
+pub(crate) struct Retrying<'backend> {
+    /// The backend to launch children with.
+    backend: &'backend dyn ProcessBackend,
+    /// The number of times to retry.
+    retries: u32,
+    /// The delay before the first retry.
+    backoff: Duration,
+}
+
+impl<'backend> Retrying<'backend> {
+    pub(crate) fn new(
+        backend: &'backend dyn ProcessBackend,
+        retries: u32,
+        backoff: Duration,
+    ) -> Self {
+        Self {
+            backend,
+            retries,
+            backoff,
+        }
+    }
+}
+
+impl ProcessBackend for Retrying<'_> {
+    fn launch(&self, command: &mut Command) -> io::Result<Launched> {
+        let mut backoff = self.backoff;
+        for retry in 1..=self.retries {
+            match self.backend.launch(command) {
+                Err(err) if is_transient(&err) => {
+                    log!(
+                        "launching child failed transiently ({err}); retry {retry}/{} in {backoff:?}",
+                        self.retries
+                    );
+                    let () = sleep(backoff);
+                    backoff = backoff.saturating_mul(2);
+                }
+                result => return result,
+            }
+        }
+        self.backend.launch(command)
+    }
+}
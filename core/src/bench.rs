@@ -0,0 +1,515 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! A configurable measurement loop for benchmarks run in a child
+//! process.
+//!
+//! The child reports its measurements to the parent in the form of a
+//! single line record on stderr:
+//! `test-fork-bench:\t<iterations>\t<elapsed>\t<sample>,<sample>,...`,
+//! with `<elapsed>` being the time the benchmark function ran for and
+//! each sample being the time per iteration, both in nanoseconds. The
+//! parent attributes the remainder of the time it took to run the
+//! child to the overhead of spawning it and exchanging data with it.
+//! If the
+//! `TEST_FORK_BENCH_OUTPUT` environment variable is set, the parent
+//! appends the results of each benchmark to the file it names, as CSV
+//! if it has a `.csv` extension and as JSON Lines otherwise.
+
+use std::env;
+use std::fs::OpenOptions;
+use std::hint::black_box;
+use std::io;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::record;
+
+
+/// The number of samples to take of each benchmark.
+const SAMPLES: usize = 50;
+/// The approximate time that taking a single sample should take.
+const SAMPLE_TIME: Duration = Duration::from_millis(1);
+/// The prefix identifying a benchmark record line.
+const RECORD_PREFIX: &str = "test-fork-bench:";
+/// The environment variable specifying the file to append benchmark
+/// results to.
+const BENCH_OUTPUT_ENV: &str = "TEST_FORK_BENCH_OUTPUT";
+/// The header of CSV formatted benchmark results.
+const CSV_HEADER: &str =
+    "name,measure,iters,median_ns,mean_ns,variance,min_ns,max_ns,overhead_ns,samples\n";
+
+
+/// The clock used for measuring benchmark iterations.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Measure {
+    /// Measure the elapsed wall clock time.
+    #[default]
+    Wall,
+    /// Measure the CPU time consumed by the benchmarking thread.
+    ///
+    /// CPU time is only supported on Unix systems. Elsewhere, wall
+    /// clock time is measured instead.
+    Cpu,
+}
+
+impl Measure {
+    /// Retrieve the name of the clock.
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Self::Wall => "wall",
+            Self::Cpu => "cpu",
+        }
+    }
+
+    /// Measure the time it takes to run `f`.
+    fn time(self, f: impl FnOnce()) -> Duration {
+        match self {
+            #[cfg(unix)]
+            Self::Cpu => {
+                let start = thread_cpu_time();
+                let () = f();
+                thread_cpu_time().saturating_sub(start)
+            }
+            _ => {
+                let start = Instant::now();
+                let () = f();
+                start.elapsed()
+            }
+        }
+    }
+}
+
+
+/// Retrieve the CPU time consumed by the current thread.
+#[cfg(unix)]
+fn thread_cpu_time() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `ts` is valid for writes.
+    let _rc = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) };
+    Duration::new(
+        u64::try_from(ts.tv_sec).unwrap_or_default(),
+        u32::try_from(ts.tv_nsec).unwrap_or_default(),
+    )
+}
+
+
+/// Options controlling the measurement loop of a benchmark.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct BenchOptions {
+    /// See [`Builder::warmup_iters`][crate::Builder::warmup_iters].
+    pub warmup_iters: u64,
+    /// See [`Builder::min_iters`][crate::Builder::min_iters].
+    pub min_iters: u64,
+    /// See [`Builder::measure`][crate::Builder::measure].
+    pub measure: Measure,
+}
+
+
+/// A summary of the measurements of a benchmark.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct BenchSummary {
+    /// The number of measured iterations.
+    pub iters: u64,
+    /// The median time per iteration across all samples, in
+    /// nanoseconds.
+    pub median_ns: f64,
+    /// The mean time per iteration across all samples, in nanoseconds.
+    pub mean_ns: f64,
+    /// The variance of the time per iteration across all samples, in
+    /// square nanoseconds.
+    pub variance: f64,
+    /// The shortest time per iteration of any sample, in nanoseconds.
+    pub min_ns: f64,
+    /// The longest time per iteration of any sample, in nanoseconds.
+    pub max_ns: f64,
+    /// The time per iteration of each sample, in nanoseconds, in the
+    /// order in which the samples were taken.
+    pub samples: Vec<f64>,
+    /// The fixed cost of running the benchmark in a child process,
+    /// i.e., of spawning the child and exchanging data with it.
+    ///
+    /// All per-iteration figures are measured inside the child and are
+    /// not affected by this overhead. As such, they are comparable to
+    /// those of benchmarks run in-process.
+    pub overhead: Duration,
+}
+
+impl BenchSummary {
+    /// Summarize the given samples, each representing the time per
+    /// iteration in nanoseconds.
+    fn from_samples(iters: u64, samples: Vec<f64>) -> Self {
+        if samples.is_empty() {
+            return Self::default()
+        }
+
+        let mut sorted = samples.clone();
+        let () = sorted.sort_by(f64::total_cmp);
+        #[expect(clippy::cast_precision_loss)]
+        let count = sorted.len() as f64;
+        let mid = sorted.len() / 2;
+        let median = if sorted.len() % 2 == 0 {
+            (sorted.get(mid - 1).unwrap_or(&0.0) + sorted.get(mid).unwrap_or(&0.0)) / 2.0
+        } else {
+            sorted.get(mid).copied().unwrap_or_default()
+        };
+        let mean = sorted.iter().sum::<f64>() / count;
+        let variance = if sorted.len() > 1 {
+            sorted.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (count - 1.0)
+        } else {
+            0.0
+        };
+
+        Self {
+            iters,
+            median_ns: median,
+            mean_ns: mean,
+            variance,
+            min_ns: sorted.first().copied().unwrap_or_default(),
+            max_ns: sorted.last().copied().unwrap_or_default(),
+            samples,
+            overhead: Duration::ZERO,
+        }
+    }
+
+    /// Serialize the measurements into their record line
+    /// representation, including the trailing newline.
+    ///
+    /// Only the raw measurements are included, along with the time
+    /// that the benchmark function ran for (`elapsed`). The parent
+    /// derives everything else from them.
+    fn to_line(&self, elapsed: Duration) -> String {
+        let samples = self
+            .samples
+            .iter()
+            .map(f64::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "{RECORD_PREFIX}\t{}\t{}\t{samples}\n",
+            self.iters,
+            elapsed.as_nanos()
+        )
+    }
+
+    /// Parse a record line as produced by [`BenchSummary::to_line`],
+    /// sans trailing newline.
+    fn from_line(line: &str) -> Option<(Self, Duration)> {
+        let rest = line.strip_prefix(RECORD_PREFIX)?.strip_prefix('\t')?;
+        let (iters, rest) = rest.split_once('\t')?;
+        let (elapsed, samples) = rest.split_once('\t')?;
+        let iters = iters.parse().ok()?;
+        let elapsed = Duration::from_nanos(elapsed.parse().ok()?);
+        let samples = samples
+            .split(',')
+            .filter(|sample| !sample.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .ok()?;
+        Some((Self::from_samples(iters, samples), elapsed))
+    }
+
+    /// Write the measurements, along with the time the benchmark
+    /// function ran for, to stderr in the form of a record, for the
+    /// parent to pick up.
+    pub(crate) fn emit(&self, elapsed: Duration) {
+        // Write the record in one go, to prevent interleaving with the
+        // output of other threads.
+        let _result = io::stderr().write_all(self.to_line(elapsed).as_bytes());
+    }
+
+    /// Extract the measurements reported by a child from its stderr
+    /// output, returning them, along with the time the benchmark
+    /// function ran for, and the remaining output.
+    pub(crate) fn extract(stderr: &[u8]) -> (Option<(Self, Duration)>, Vec<u8>) {
+        let (summaries, remainder) = record::extract(stderr, Self::from_line);
+        (summaries.into_iter().last(), remainder)
+    }
+
+    /// Format the summary as a JSON object, on a single line.
+    fn to_json(&self, test_name: &str, measure: Measure) -> String {
+        let samples = self
+            .samples
+            .iter()
+            .map(f64::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut name = String::new();
+        let () = record::write_json_str(&mut name, test_name);
+        format!(
+            "{{\"name\":{name},\"measure\":\"{}\",\"iters\":{},\"median_ns\":{},\"mean_ns\":{},\"variance\":{},\"min_ns\":{},\"max_ns\":{},\"overhead_ns\":{},\"samples\":[{samples}]}}\n",
+            measure.as_str(),
+            self.iters,
+            self.median_ns,
+            self.mean_ns,
+            self.variance,
+            self.min_ns,
+            self.max_ns,
+            self.overhead.as_nanos(),
+        )
+    }
+
+    /// Format the summary as a CSV row, with samples separated by
+    /// spaces.
+    fn to_csv(&self, test_name: &str, measure: Measure) -> String {
+        let samples = self
+            .samples
+            .iter()
+            .map(f64::to_string)
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!(
+            "{},{},{},{},{},{},{},{},{},{samples}\n",
+            csv_escape(test_name),
+            measure.as_str(),
+            self.iters,
+            self.median_ns,
+            self.mean_ns,
+            self.variance,
+            self.min_ns,
+            self.max_ns,
+            self.overhead.as_nanos(),
+        )
+    }
+
+    /// Report the summary of the benchmark `test_name` on stderr and,
+    /// if requested, append it to the file named by
+    /// `TEST_FORK_BENCH_OUTPUT`.
+    pub(crate) fn report(&self, test_name: &str, measure: Measure) {
+        // Bypass any output capturing by the test harness, so that the
+        // result is visible just like libtest's own.
+        let _result = writeln!(
+            io::stderr(),
+            "test-fork: {test_name}: {:.0} ns/iter (+/- {:.0}), {} iterations ({} time), {:.3?} process overhead",
+            self.median_ns,
+            self.variance.sqrt(),
+            self.iters,
+            measure.as_str(),
+            self.overhead,
+        );
+
+        let path = match env::var_os(BENCH_OUTPUT_ENV) {
+            Some(path) => PathBuf::from(path),
+            None => return,
+        };
+
+        // Results are purely informational. Failure to write them
+        // should not fail the benchmark.
+        if let Err(err) = self.append(&path, test_name, measure) {
+            eprintln!(
+                "test-fork: failed to write benchmark results to {}: {err}",
+                path.display()
+            );
+        }
+    }
+
+    /// Append the summary to the file at `path`, as CSV if its
+    /// extension is `csv` and as JSON Lines otherwise.
+    fn append(&self, path: &Path, test_name: &str, measure: Measure) -> io::Result<()> {
+        /// Serialize appending from concurrently running benchmarks.
+        static LOCK: Mutex<()> = Mutex::new(());
+
+        let _guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let is_csv = path.extension().is_some_and(|ext| ext == "csv");
+        let data = if is_csv {
+            let mut data = String::new();
+            if file.metadata()?.len() == 0 {
+                data += CSV_HEADER;
+            }
+            data + &self.to_csv(test_name, measure)
+        } else {
+            self.to_json(test_name, measure)
+        };
+        file.write_all(data.as_bytes())
+    }
+}
+
+
+/// Escape a field for inclusion in a CSV row, quoting it if necessary.
+fn csv_escape(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+
+/// A benchmark runner with a configurable measurement loop.
+///
+/// An object of this type is provided to benchmarks run via
+/// [`Builder::fork_bench`][crate::Builder::fork_bench]. Its interface
+/// mirrors that of the standard library's `test::Bencher`.
+#[derive(Debug)]
+pub struct Bencher {
+    /// The options controlling the measurement loop.
+    options: BenchOptions,
+    /// The summary of the measurements, once taken.
+    summary: Option<BenchSummary>,
+}
+
+impl Bencher {
+    pub(crate) fn new(options: BenchOptions) -> Self {
+        Self {
+            options,
+            summary: None,
+        }
+    }
+
+    /// Run `f` in a loop, measuring the time each iteration takes.
+    ///
+    /// After running the configured number of warmup iterations, `f` is
+    /// run in batches sized such that each takes roughly a millisecond.
+    /// A number of such batches are measured, but at least enough to
+    /// cover the configured minimum number of iterations.
+    pub fn iter<T, F>(&mut self, mut f: F)
+    where
+        F: FnMut() -> T,
+    {
+        let BenchOptions {
+            warmup_iters,
+            min_iters,
+            measure,
+        } = self.options;
+
+        for _ in 0..warmup_iters {
+            let _value = black_box(f());
+        }
+
+        let mut run = |batch: u64| {
+            measure.time(|| {
+                for _ in 0..batch {
+                    let _value = black_box(f());
+                }
+            })
+        };
+
+        let mut batch = 1u64;
+        while run(batch) < SAMPLE_TIME && batch < u64::from(u32::MAX) {
+            batch *= 2;
+        }
+
+        let mut samples = Vec::with_capacity(SAMPLES);
+        let mut iters = 0;
+        while samples.len() < SAMPLES || iters < min_iters {
+            let time = run(batch);
+            #[expect(clippy::cast_precision_loss)]
+            let () = samples.push(time.as_nanos() as f64 / batch as f64);
+            iters += batch;
+        }
+
+        self.summary = Some(BenchSummary::from_samples(iters, samples));
+    }
+
+    /// Retrieve the summary of the measurements taken by
+    /// [`Bencher::iter`], if it was called.
+    pub(crate) fn into_summary(self) -> Option<BenchSummary> {
+        self.summary
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that samples are summarized correctly.
+    #[test]
+    fn sample_summary() {
+        let summary = BenchSummary::from_samples(40, vec![4.0, 1.0, 3.0, 2.0]);
+        assert_eq!(summary.iters, 40);
+        assert_eq!(summary.median_ns, 2.5);
+        assert_eq!(summary.mean_ns, 2.5);
+        assert!((summary.variance - 5.0 / 3.0).abs() < f64::EPSILON);
+        assert_eq!(summary.min_ns, 1.0);
+        assert_eq!(summary.max_ns, 4.0);
+        assert_eq!(summary.samples, [4.0, 1.0, 3.0, 2.0]);
+
+        assert_eq!(
+            BenchSummary::from_samples(0, Vec::new()),
+            BenchSummary::default()
+        );
+    }
+
+    /// Check that a summary survives a round trip through its record
+    /// representation and gets picked out of the surrounding output.
+    #[test]
+    fn record_round_trip() {
+        let summary = BenchSummary::from_samples(7, vec![1.5, 9.25, 1.0 / 3.0]);
+        let elapsed = Duration::from_micros(1234);
+        let stderr = format!("some output\n{}more output\n", summary.to_line(elapsed));
+
+        let (extracted, remainder) = BenchSummary::extract(stderr.as_bytes());
+        assert_eq!(extracted, Some((summary, elapsed)));
+        assert_eq!(remainder, b"some output\nmore output\n");
+    }
+
+    /// Check that results are formatted as expected for the supported
+    /// output formats.
+    #[test]
+    fn result_formatting() {
+        let mut summary = BenchSummary::from_samples(20, vec![3.0, 1.5]);
+        summary.overhead = Duration::from_millis(2);
+
+        let json = summary.to_json("foo::\"bar\"", Measure::Cpu);
+        let expected = r#"{"name":"foo::\"bar\"","measure":"cpu","iters":20,"median_ns":2.25,"mean_ns":2.25,"variance":1.125,"min_ns":1.5,"max_ns":3,"overhead_ns":2000000,"samples":[3,1.5]}
+"#;
+        assert_eq!(json, expected);
+
+        let csv = summary.to_csv("foo,bar", Measure::Wall);
+        assert_eq!(
+            csv,
+            "\"foo,bar\",wall,20,2.25,2.25,1.125,1.5,3,2000000,3 1.5\n"
+        );
+    }
+
+    /// Check that results get appended to the output file, with a
+    /// single CSV header.
+    #[test]
+    fn result_appending() {
+        use std::env::temp_dir;
+        use std::fs;
+        use std::process;
+
+        let summary = BenchSummary::from_samples(1, vec![1.0]);
+        let path = temp_dir().join(format!("test-fork-bench-{}.csv", process::id()));
+        let _result = fs::remove_file(&path);
+        let () = summary.append(&path, "a", Measure::Wall).unwrap();
+        let () = summary.append(&path, "b", Measure::Wall).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let () = fs::remove_file(&path).unwrap();
+        let expected = format!("{CSV_HEADER}a,wall,1,1,1,0,1,1,0,1\nb,wall,1,1,1,0,1,1,0,1\n");
+        assert_eq!(content, expected);
+    }
+
+    /// Check that the measurement loop honors its options.
+    #[test]
+    fn measurement_loop() {
+        let options = BenchOptions {
+            warmup_iters: 5,
+            min_iters: 100_000,
+            measure: Measure::Cpu,
+        };
+        let mut count = 0u64;
+        let mut bencher = Bencher::new(options);
+        let () = bencher.iter(|| count += 1);
+
+        let summary = bencher.into_summary().unwrap();
+        assert!(summary.iters >= 100_000);
+        assert!(count >= summary.iters + 5);
+        assert!(summary.min_ns <= summary.median_ns);
+        assert!(summary.median_ns <= summary.max_ns);
+    }
+}
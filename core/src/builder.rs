@@ -0,0 +1,3212 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+use std::borrow::Cow;
+#[cfg(any(unix, feature = "proptest", feature = "quickcheck"))]
+use std::cell::OnceCell;
+#[cfg(any(feature = "proptest", feature = "quickcheck"))]
+use std::cell::RefCell;
+use std::env;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+#[cfg(any(feature = "proptest", feature = "quickcheck"))]
+use std::fmt::Debug;
+use std::fs;
+use std::io;
+use std::io::Read as _;
+use std::io::Write as _;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::fd::BorrowedFd;
+#[cfg(unix)]
+use std::os::fd::OwnedFd;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::panic;
+#[cfg(unix)]
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+#[cfg(any(feature = "proptest", feature = "quickcheck"))]
+use std::process::ExitCode;
+use std::process::Stdio;
+use std::process::Termination;
+#[cfg(feature = "quickcheck")]
+use std::rc::Rc;
+use std::result;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+#[cfg(feature = "proptest")]
+use proptest::strategy::Strategy;
+#[cfg(feature = "proptest")]
+use proptest::test_runner::Config as ProptestConfig;
+#[cfg(feature = "proptest")]
+use proptest::test_runner::TestCaseError;
+#[cfg(feature = "proptest")]
+use proptest::test_runner::TestCaseResult;
+#[cfg(feature = "proptest")]
+use proptest::test_runner::TestError;
+#[cfg(feature = "proptest")]
+use proptest::test_runner::TestRunner;
+#[cfg(feature = "quickcheck")]
+use quickcheck::Arbitrary;
+#[cfg(feature = "quickcheck")]
+use quickcheck::Gen;
+#[cfg(feature = "quickcheck")]
+use quickcheck::QuickCheck;
+#[cfg(feature = "quickcheck")]
+use quickcheck::TestResult;
+#[cfg(feature = "quickcheck")]
+use quickcheck::Testable;
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::artifacts;
+use crate::artifacts::Artifacts;
+use crate::backend::CommandBackend;
+use crate::backend::ProcessBackend;
+use crate::backend::Retrying;
+use crate::backend::DEFAULT_SPAWN_BACKOFF;
+use crate::backend::DEFAULT_SPAWN_RETRIES;
+use crate::bench::BenchOptions;
+use crate::bench::BenchSummary;
+use crate::bench::Bencher;
+use crate::bench::Measure;
+#[cfg(target_os = "linux")]
+use crate::caps;
+use crate::child::ChildWrapper;
+use crate::cmdline::UnknownFlags;
+use crate::color::Color;
+use crate::debug;
+use crate::dry_run;
+#[cfg(feature = "quickcheck")]
+use crate::error::Error;
+use crate::error::Result;
+use crate::failure::ChildFailure;
+#[cfg(target_os = "linux")]
+use crate::faketime;
+#[cfg(unix)]
+use crate::fds;
+use crate::fork::fork_int_with;
+use crate::fork::forward_output;
+use crate::fork::is_child;
+use crate::fork::supervise_child;
+use crate::fork::LaunchOptions;
+use crate::fork::Supervision;
+use crate::fork::LLVM_PROFILE_FILE_ENV;
+use crate::fork::PROCESS_SUPPORT;
+use crate::golden;
+use crate::handle::ForkHandle;
+use crate::heartbeat::Monitor;
+#[cfg(target_os = "linux")]
+use crate::namespace;
+use crate::outcome::ForkOutcome;
+#[cfg(all(target_os = "linux", feature = "perf"))]
+use crate::perf;
+use crate::persist;
+#[cfg(all(unix, feature = "pty"))]
+use crate::pty;
+use crate::resource;
+use crate::sanitizer;
+use crate::sched;
+use crate::serial;
+#[cfg(unix)]
+use crate::shared::SharedBuffer;
+
+
+/// The environment variable conveying the index of an instance run via
+/// [`Builder::fork_instances`] to it.
+const INSTANCE_ENV: &str = "TEST_FORK_INSTANCE";
+/// The exit code of a child whose property test case got rejected or
+/// discarded.
+#[cfg(any(feature = "proptest", feature = "quickcheck"))]
+const EX_REJECTED: u8 = 75;
+
+
+/// The source of the data fed to the child's standard input.
+#[derive(Clone, Debug)]
+enum Stdin {
+    /// The data to feed.
+    Data(Vec<u8>),
+    /// The path to a file containing the data to feed.
+    File(PathBuf),
+}
+
+
+/// A builder for configuring how a function is run in a child process.
+///
+/// The [`fork`][crate::fork()], [`fork_in_out`][crate::fork_in_out],
+/// and [`fork_in_out_vec`][crate::fork_in_out_vec] functions are
+/// shorthands for using a default constructed builder.
+#[derive(Clone, Debug, Default)]
+pub struct Builder {
+    /// See [`Builder::heartbeat`].
+    heartbeat: Option<Duration>,
+    /// Options controlling the supervision of the child.
+    supervision: Supervision,
+    /// See [`Builder::env_clear_except`].
+    env_allow: Option<Vec<String>>,
+    /// See [`Builder::expected_output`].
+    expected_output: Option<PathBuf>,
+    /// See [`Builder::stdin`] and [`Builder::stdin_file`].
+    stdin: Option<Stdin>,
+    /// See [`Builder::env`].
+    envs: Vec<(OsString, OsString)>,
+    /// See [`Builder::before`].
+    before: Option<fn(&mut Self)>,
+    /// See [`Builder::after`].
+    after: Option<fn(&result::Result<ForkOutcome, ChildFailure>)>,
+    /// See [`Builder::raw_fork`].
+    raw_fork: bool,
+    /// See [`Builder::in_process`].
+    in_process: bool,
+    /// See [`Builder::should_panic`].
+    should_panic: bool,
+    /// See [`Builder::kill_on_drop`].
+    leave_running: bool,
+    /// See [`Builder::opt_in_env`].
+    opt_in_env: Option<OsString>,
+    /// See [`Builder::debug`].
+    debug: bool,
+    /// See [`Builder::backend`].
+    backend: Option<Arc<dyn ProcessBackend>>,
+    /// See [`Builder::spawn_retries`].
+    spawn_retries: Option<(u32, Duration)>,
+    /// See [`Builder::overlay`].
+    #[cfg(target_os = "linux")]
+    overlays: Vec<PathBuf>,
+    /// See [`Builder::pid_namespace`].
+    #[cfg(target_os = "linux")]
+    pid_namespace: bool,
+    /// See [`Builder::drop_capability`].
+    #[cfg(target_os = "linux")]
+    drop_caps: Vec<String>,
+    /// See [`Builder::fake_time`].
+    #[cfg(target_os = "linux")]
+    fake_time: Option<String>,
+    /// See [`Builder::nice`].
+    nice: Option<i32>,
+    /// See [`Builder::cpu_affinity`].
+    cpu_affinity: Option<Vec<usize>>,
+    /// See [`Builder::serial`].
+    serial: Option<String>,
+    /// See [`Builder::reserve_ports`].
+    reserve_ports: usize,
+    /// See [`Builder::perf_counters`].
+    #[cfg(all(target_os = "linux", feature = "perf"))]
+    perf_counters: bool,
+    /// See [`Builder::pty`].
+    #[cfg(all(unix, feature = "pty"))]
+    pty: bool,
+    /// See [`Builder::warmup_iters`], [`Builder::min_iters`], and
+    /// [`Builder::measure`].
+    bench: BenchOptions,
+    /// See [`Builder::unknown_flags`].
+    unknown_flags: Option<UnknownFlags>,
+    /// See [`Builder::executable`].
+    exe: Option<PathBuf>,
+}
+
+impl Builder {
+    /// Create a new builder using the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable heartbeat based hang detection.
+    ///
+    /// With this option set, the child sends a heartbeat to the parent
+    /// whenever the test reported progress via
+    /// [`heartbeat`][crate::heartbeat()]. If no heartbeat was received
+    /// for `timeout` while the child is still running, the child is
+    /// considered hung: its output and state are reported, it is
+    /// killed, and the parent panics.
+    ///
+    /// Starting the test counts as progress. Hence, a test not
+    /// reporting progress on its own is considered hung once it ran
+    /// for `timeout`, no matter whether it is deadlocked, spinning, or
+    /// merely slow. Until the child sent its first heartbeat, it is
+    /// granted at least 30 seconds, so that a slow start of the child
+    /// isn't reported as a hang.
+    pub fn heartbeat(&mut self, timeout: Duration) -> &mut Self {
+        self.heartbeat = Some(timeout);
+        self
+    }
+
+    /// Fail if the child emits a sanitizer report.
+    ///
+    /// Depending on their configuration, sanitizers (e.g., the address
+    /// or thread sanitizer) may report an error but still let the
+    /// process exit successfully. With this option set,
+    /// the child's stderr is scanned for sanitizer reports and the
+    /// child considered failed if one is found.
+    ///
+    /// To make sure that reports can be found, sanitizers of the child
+    /// are configured to log to stderr, overriding any `log_path`
+    /// setting in `ASAN_OPTIONS` and similar variables. All other
+    /// options are preserved.
+    pub fn sanitizer_check(&mut self, enabled: bool) -> &mut Self {
+        self.supervision.sanitizers = enabled;
+        self
+    }
+
+    /// Fail if the child leaves behind any processes it spawned.
+    ///
+    /// With this option set, the parent checks whether any processes
+    /// spawned (directly or indirectly) by the child are still running
+    /// after the child exited. If so, they are killed and the child is
+    /// considered failed.
+    ///
+    /// This check relies on the child running in a process group of
+    /// its own and is only supported on Unix systems. Processes that
+    /// moved into a different process group or session escape
+    /// detection. On Linux, leaked processes are described in detail.
+    pub fn leak_check(&mut self, enabled: bool) -> &mut Self {
+        self.supervision.leaks = enabled;
+        self
+    }
+
+    /// Collect performance counters for the child.
+    ///
+    /// With this option set, the parent attaches counters for CPU
+    /// cycles, retired instructions, cache misses, and consumed CPU
+    /// time to the child, using `perf_event_open(2)`. Their values are
+    /// reported in [`ForkOutcome::perf_counters`] once the child
+    /// exited. Because the child is a process of its own, the counters
+    /// cover only the test (and whatever processes it spawns), free of
+    /// noise caused by other tests.
+    ///
+    /// Counting starts once the child `exec`s the test executable and
+    /// only events in user space are counted. Counters for which the
+    /// system lacks support are not reported. As they depend on the
+    /// child being `exec`ed, this option is not supported in
+    /// [raw fork mode][Builder::raw_fork]. When using a custom
+    /// [backend][Builder::backend], the counters cover the process it
+    /// spawns (e.g., a container engine's client), which may not
+    /// represent the test itself.
+    #[cfg(all(target_os = "linux", feature = "perf"))]
+    pub fn perf_counters(&mut self, enabled: bool) -> &mut Self {
+        self.perf_counters = enabled;
+        self
+    }
+
+    /// Connect the child's standard output to a pseudo-terminal.
+    ///
+    /// By default, the child's output is piped to the parent, causing
+    /// code checking `isatty` to conclude it is not writing to a
+    /// terminal. With this option set, the child writes to an 80x24
+    /// pseudo-terminal instead, the output of which is relayed to the
+    /// parent just like piped output. Line feeds are not translated,
+    /// unlike with a regular terminal. The child's standard error
+    /// remains piped, so that panics and other reports keep being
+    /// detected.
+    ///
+    /// This option is not supported in [raw fork mode][Builder::raw_fork].
+    #[cfg(all(unix, feature = "pty"))]
+    pub fn pty(&mut self, enabled: bool) -> &mut Self {
+        self.pty = enabled;
+        self
+    }
+
+    /// Start the child from a minimal environment, containing only the
+    /// variables named in `allow`.
+    ///
+    /// By default, the child inherits the entire environment of the
+    /// parent. With this option set, only the listed variables are
+    /// inherited, making the child's behavior more reproducible across
+    /// machines.
+    ///
+    /// Variables used by `test-fork` itself (`TEST_FORK_*`), code
+    /// coverage instrumentation (`LLVM_PROFILE_FILE`), and sanitizers
+    /// (`ASAN_OPTIONS` and similar) are always inherited.
+    pub fn env_clear_except<I, S>(&mut self, allow: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let allow = allow
+            .into_iter()
+            .map(|name| name.as_ref().to_string())
+            .collect();
+        self.env_allow = Some(allow);
+        self
+    }
+
+    /// Capture the child's output instead of forwarding it.
+    ///
+    /// By default, the output of a successful child is forwarded to
+    /// the parent's output. With this option set, it is made available
+    /// as part of the [`ForkOutcome`] returned by [`Builder::try_fork`]
+    /// and [`Builder::try_fork_in_out`] instead, so that the test can
+    /// make assertions on it.
+    pub fn capture_output(&mut self, enabled: bool) -> &mut Self {
+        self.supervision.capture_output = enabled;
+        self
+    }
+
+    /// Limit the amount of the child's output retained to `limit`
+    /// bytes, for each of its standard output and standard error.
+    ///
+    /// By default, all output of the child is buffered by the parent,
+    /// so that a runaway child may exhaust the parent's memory. With
+    /// this option set, output beyond the limit is dropped and a
+    /// `... output truncated (N bytes omitted) ...` marker is emitted
+    /// in its place, both when forwarding and when capturing output.
+    /// Note that a panic report written after the limit has been
+    /// reached is dropped as well, in which case the child's failure
+    /// is reported solely based on its exit status.
+    pub fn output_limit(&mut self, limit: usize) -> &mut Self {
+        self.supervision.output_limit = Some(limit);
+        self
+    }
+
+    /// Persist the child's output to files, for post-mortem analysis.
+    ///
+    /// Everything the child writes to its standard output and standard
+    /// error is written to `<test-name>.out` and `<test-name>.err`
+    /// files as it arrives, regardless of [`Builder::output_limit`].
+    /// The files are kept only if the child failed, in which case their
+    /// paths are reported. They are written into the directory named
+    /// by the `TEST_FORK_OUTPUT_DIR` environment variable, if set, and
+    /// into `test-fork/` inside Cargo's target directory otherwise.
+    /// Setting `TEST_FORK_OUTPUT_DIR` enables this option for all
+    /// tests.
+    pub fn persist_output(&mut self, enabled: bool) -> &mut Self {
+        self.supervision.persist_output = enabled;
+        self
+    }
+
+    /// Provide the child with a directory for storing artifacts.
+    ///
+    /// With this option set, an empty directory is created for the test
+    /// before the child is spawned and its path is conveyed to the child
+    /// via the `TEST_FORK_ARTIFACTS` environment variable (see
+    /// [`artifact_dir`][crate::artifact_dir]). Children can store
+    /// screenshots, heap profiles, logs, and the like there. Once the
+    /// child exited, the directory is either kept, in which case its
+    /// path is reported, or removed, as configured. Artifact
+    /// directories reside in `artifacts/` inside the directory output
+    /// is persisted to (see [`Builder::persist_output`]), and any
+    /// artifacts of a previous run of the test are removed.
+    pub fn artifacts(&mut self, artifacts: Artifacts) -> &mut Self {
+        self.supervision.artifacts = artifacts;
+        self
+    }
+
+    /// Prefix each line of the child's output forwarded to the parent's
+    /// output with the name of the test, as `[<test>] `.
+    ///
+    /// When running tests concurrently with `--nocapture`, that makes
+    /// it possible to attribute interleaved output to the tests that
+    /// produced it. Captured output (see [`Builder::capture_output`])
+    /// is not affected.
+    pub fn prefix_output(&mut self, enabled: bool) -> &mut Self {
+        self.supervision.prefix_output = enabled;
+        self
+    }
+
+    /// Configure the treatment of colors in the child's output.
+    ///
+    /// As the child's output is piped to the parent, the child commonly
+    /// disables colors, e.g., in assertion diffs. With [`Color::Always`],
+    /// the child is asked to use colors nevertheless (via the
+    /// `CLICOLOR_FORCE` and `FORCE_COLOR` environment variables) and
+    /// its output is forwarded untouched. With [`Color::Never`], the
+    /// child is asked not to use colors (via `NO_COLOR`) and any ANSI
+    /// escape sequences are stripped from the output being forwarded,
+    /// which is useful for keeping CI logs readable. Captured output
+    /// (see [`Builder::capture_output`]) is never modified.
+    pub fn color(&mut self, color: Color) -> &mut Self {
+        self.supervision.color = color;
+        self
+    }
+
+    /// Compare the output of the test against the golden file at
+    /// `path`.
+    ///
+    /// With this option set, everything the test writes to its standard
+    /// output is compared against the contents of the given file and
+    /// the child is considered failed if the two differ. Output of the
+    /// test harness is not part of the comparison.
+    ///
+    /// If the `TEST_FORK_UPDATE_GOLDEN` environment variable is set (to
+    /// a value other than `0`), the golden file is instead rewritten
+    /// with the test's actual output.
+    pub fn expected_output<P>(&mut self, path: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.expected_output = Some(path.into());
+        self
+    }
+
+    /// Feed `data` to the child's standard input.
+    ///
+    /// By default, the child's standard input is empty. With this
+    /// option set, the provided data are available to be read instead,
+    /// followed by end-of-file.
+    pub fn stdin<D>(&mut self, data: D) -> &mut Self
+    where
+        D: Into<Vec<u8>>,
+    {
+        self.stdin = Some(Stdin::Data(data.into()));
+        self
+    }
+
+    /// Feed the contents of the file at `path` to the child's standard
+    /// input.
+    ///
+    /// This option is the file based equivalent of [`Builder::stdin`].
+    /// The file is read before the child is spawned.
+    pub fn stdin_file<P>(&mut self, path: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.stdin = Some(Stdin::File(path.into()));
+        self
+    }
+
+    /// Set the environment variable `key` to `value` in the child.
+    ///
+    /// Variables set this way take precedence over inherited ones and
+    /// are retained even if the environment is cleared (see
+    /// [`Builder::env_clear_except`]).
+    pub fn env<K, V>(&mut self, key: K, value: V) -> &mut Self
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        let () = self
+            .envs
+            .push((key.as_ref().to_owned(), value.as_ref().to_owned()));
+        self
+    }
+
+    /// Run `hook` in the parent before the child is spawned.
+    ///
+    /// The hook is provided with a copy of the builder, which it may
+    /// adjust to configure the child (e.g., by using [`Builder::env`]
+    /// to tell it about a resource that it set up). It runs only in the
+    /// parent process, never in the child.
+    pub fn before(&mut self, hook: fn(&mut Self)) -> &mut Self {
+        self.before = Some(hook);
+        self
+    }
+
+    /// Run `hook` in the parent after the child exited.
+    ///
+    /// The hook is provided with the result of running the child and
+    /// is invoked regardless of whether the child succeeded or not,
+    /// making it suitable for tearing down resources set up by the
+    /// [`Builder::before`] hook. It runs only in the parent process,
+    /// never in the child.
+    pub fn after(&mut self, hook: fn(&result::Result<ForkOutcome, ChildFailure>)) -> &mut Self {
+        self.after = Some(hook);
+        self
+    }
+
+    /// Create the child via a plain `fork`, without `exec`.
+    ///
+    /// By default, the child is a new instance of the current
+    /// executable, which runs just the test in question. With this
+    /// option set, the current process is forked instead and the child
+    /// continues to run the test from the fork point on, avoiding the
+    /// overhead of process startup and test harness initialization.
+    ///
+    /// This mode comes with severe caveats, though:
+    /// - the child contains only the forking thread; locks held by any
+    ///   other thread at the time of the fork (e.g., by other tests run
+    ///   concurrently by the test harness) remain locked forever and
+    ///   may cause the child to deadlock; using it is only advisable
+    ///   when running tests with `--test-threads=1`
+    /// - output written using `print!` and similar macros may get
+    ///   captured by the test harness in the child and lost; write to
+    ///   [`io::stdout`][std::io::stdout] directly or run tests with
+    ///   `--nocapture` to be safe
+    /// - the child's environment is not cleared and its standard input
+    ///   cannot be provided, i.e., [`Builder::env_clear_except`],
+    ///   [`Builder::stdin`], and [`Builder::expected_output`] are not
+    ///   supported and cause a panic (as do [`Builder::backend`],
+    ///   [`Builder::executable`], [`Builder::spawn_retries`],
+    ///   [`Builder::nice`],
+    ///   [`Builder::cpu_affinity`], `Builder::overlay`, and
+    ///   `Builder::pid_namespace`)
+    ///
+    /// This option is only available on Unix systems.
+    #[cfg(all(unix, feature = "raw-fork"))]
+    pub fn raw_fork(&mut self, enabled: bool) -> &mut Self {
+        self.raw_fork = enabled;
+        self
+    }
+
+    /// Run the test in the current process instead of a child process.
+    ///
+    /// With this option set, no child is created and the test is run
+    /// directly by the caller, as it is on targets that do not support
+    /// spawning processes. Most other options have no effect in this
+    /// mode. It is meant for running tests in a separate process only
+    /// conditionally (e.g., only on some targets) without having to
+    /// maintain a second copy of each test.
+    pub fn in_process(&mut self, enabled: bool) -> &mut Self {
+        self.in_process = enabled;
+        self
+    }
+
+    /// Indicate that the test is expected to panic, as is the case for
+    /// tests annotated with `#[should_panic]`.
+    ///
+    /// A child failing for reasons other than a panic, e.g., because it
+    /// exited with a non-zero exit code, got killed by a signal,
+    /// crashed, or hung, does not satisfy the
+    /// expectation. With this option set, such a failure is reported
+    /// on standard error and the fork function returns normally instead
+    /// of panicking, causing the test harness to fail the test for not
+    /// panicking. Without it, any failure of the child results in a
+    /// panic. This option is set automatically by the
+    /// `#[test_fork::test]` attribute for `#[should_panic]` tests.
+    ///
+    /// Note that code following the fork point in the parent has to
+    /// refrain from panicking for this scheme to work.
+    pub fn should_panic(&mut self, enabled: bool) -> &mut Self {
+        self.should_panic = enabled;
+        self
+    }
+
+    /// Make the child wait for a debugger to attach before running the
+    /// test.
+    ///
+    /// The parent prints the child's process identifier along with a
+    /// hint on how to attach to it. On Unix systems, the child stops
+    /// itself and resumes once continued by the debugger (or by
+    /// `SIGCONT`). On Windows, it waits until a debugger is present.
+    /// Heartbeat based hang detection is disabled in this mode.
+    ///
+    /// The same behavior can be requested without recompiling by
+    /// setting the `TEST_FORK_DEBUG` environment variable to a
+    /// non-empty value other than `0`, e.g., `TEST_FORK_DEBUG=1 cargo
+    /// test my_test`.
+    pub fn debug(&mut self, enabled: bool) -> &mut Self {
+        self.debug = enabled;
+        self
+    }
+
+    /// Control whether a child is killed when the [`ForkHandle`]
+    /// representing it is dropped without being joined.
+    ///
+    /// By default, the child's process group gets killed. When disabled,
+    /// the child gets detached instead (see [`ChildWrapper::detach`])
+    /// and may outlive the parent.
+    ///
+    /// Disabling this option is dangerous and not meant for normal use.
+    /// It exists solely for tests verifying daemonization or the
+    /// handling of orphaned processes, which are responsible for
+    /// reaping the child via [`reap_detached`][crate::reap_detached].
+    pub fn kill_on_drop(&mut self, enabled: bool) -> &mut Self {
+        self.leave_running = !enabled;
+        self
+    }
+
+    /// Run the test in a child process only if the environment variable
+    /// `name` is set.
+    ///
+    /// With this option set, the test is run in-process (see
+    /// [`Builder::in_process`]) unless the variable is set to a
+    /// non-empty value other than `0`. That way, heavy test suites can
+    /// be run in-process during local development, while paying for
+    /// running each test in a separate process only where it matters
+    /// (e.g., in CI, via `TEST_FORK=1 cargo test`). The variable is
+    /// evaluated at run time, so the test is compiled identically
+    /// either way.
+    pub fn opt_in_env<K>(&mut self, name: K) -> &mut Self
+    where
+        K: AsRef<OsStr>,
+    {
+        self.opt_in_env = Some(name.as_ref().to_os_string());
+        self
+    }
+
+    /// Isolate the child's modifications to the directory at `path`.
+    ///
+    /// With this option set, the child runs in a mount namespace of its
+    /// own, in which a throwaway overlay is mounted on top of the
+    /// directory. The child sees the directory's original contents and
+    /// can modify them at will, but all modifications are discarded
+    /// when it exits, without being visible to the parent or any other
+    /// process. That allows for tests writing to fixed locations (e.g.,
+    /// configuration directories or `/tmp`) to run concurrently.
+    ///
+    /// This option may be provided multiple times to isolate multiple
+    /// directories. It requires support for unprivileged user
+    /// namespaces and overlay mounts in them (Linux 5.11 or newer) and
+    /// is not supported in conjunction with [`Builder::raw_fork`] or a
+    /// custom [`Builder::backend`].
+    ///
+    /// This option is only available on Linux.
+    ///
+    /// ## Panics
+    ///
+    /// Forking panics if `path` does not refer to an existing directory.
+    #[cfg(target_os = "linux")]
+    pub fn overlay<P>(&mut self, path: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.overlays.push(path.into());
+        self
+    }
+
+    /// Run the child as PID 1 of a PID namespace of its own.
+    ///
+    /// With this option set, all processes spawned by the child
+    /// (directly or indirectly) live in the same PID namespace and are
+    /// killed by the kernel once the child exits or gets killed. That
+    /// is a stronger guarantee than the one provided by process groups,
+    /// which processes may leave, e.g., when daemonizing. Note that
+    /// `/proc` keeps reflecting the parent's PID namespace.
+    ///
+    /// This option requires support for unprivileged user namespaces
+    /// and is not supported in conjunction with [`Builder::raw_fork`]
+    /// or a custom [`Builder::backend`].
+    ///
+    /// This option is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn pid_namespace(&mut self, enabled: bool) -> &mut Self {
+        self.pid_namespace = enabled;
+        self
+    }
+
+    /// Drop the Linux capability with the given name in the child,
+    /// before the test runs.
+    ///
+    /// The name is matched case insensitively and may carry a `CAP_`
+    /// prefix (e.g., `CAP_NET_ADMIN` or `net_admin`). The special name
+    /// `all` drops all capabilities. This option may be provided
+    /// multiple times to drop multiple capabilities.
+    ///
+    /// The capability is removed from all of the child's capability
+    /// sets, including its bounding set, so that it cannot be regained
+    /// by executing a program. If the child lacks the privileges to
+    /// change its bounding set, it sets the `no_new_privs` flag
+    /// instead. That allows for checking how code behaves without
+    /// certain privileges on a per-test basis.
+    ///
+    /// This option is only available on Linux.
+    ///
+    /// ## Panics
+    ///
+    /// Forking panics if `name` does not refer to a known capability.
+    #[cfg(target_os = "linux")]
+    pub fn drop_capability<S>(&mut self, name: S) -> &mut Self
+    where
+        S: Into<String>,
+    {
+        self.drop_caps.push(name.into());
+        self
+    }
+
+    /// Run the child with its clock faked as per `spec`.
+    ///
+    /// The child is launched with [libfaketime] preloaded, which makes
+    /// the time related functions of the C library (and with them
+    /// [`SystemTime`][std::time::SystemTime] and
+    /// [`Instant`][std::time::Instant]) report a fake time, as
+    /// specified by `spec` in libfaketime's `FAKETIME` format, e.g.,
+    /// `@2000-01-01 00:00:00` for starting the clock at a given time or
+    /// `+2d` for running it two days ahead. The parent, and with it the
+    /// test harness, is unaffected. That way, time dependent code
+    /// (e.g., expiry or scheduling logic) can be tested
+    /// deterministically.
+    ///
+    /// libfaketime is looked up in its common installation locations,
+    /// unless its path is provided via the `TEST_FORK_FAKETIME_LIB`
+    /// environment variable. Note that only dynamically linked calls
+    /// into the C library are intercepted; code issuing system calls
+    /// directly keeps seeing the real time. On other systems, or if a
+    /// dependency on libfaketime is undesired, the child can be
+    /// provided with a clock offset via [`Builder::env`] instead, to be
+    /// applied by a clock abstraction of the code under test.
+    ///
+    /// This option is only available on Linux and not supported in
+    /// conjunction with [`Builder::raw_fork`].
+    ///
+    /// ## Panics
+    ///
+    /// Forking panics if libfaketime can't be found.
+    ///
+    /// [libfaketime]: https://github.com/wolfcw/libfaketime
+    #[cfg(target_os = "linux")]
+    pub fn fake_time<S>(&mut self, spec: S) -> &mut Self
+    where
+        S: Into<String>,
+    {
+        self.fake_time = Some(spec.into());
+        self
+    }
+
+    /// Set the niceness of the child, i.e., its scheduling priority.
+    ///
+    /// Values range from `-20` (highest priority) to `19` (lowest
+    /// priority), with `0` being the default. Raising the priority
+    /// (i.e., using negative values) typically requires special
+    /// privileges and causes spawning the child to fail otherwise.
+    ///
+    /// On Windows, the value is mapped onto the closest priority class.
+    /// On other systems without support for niceness, it is ignored.
+    pub fn nice(&mut self, nice: i32) -> &mut Self {
+        self.nice = Some(nice);
+        self
+    }
+
+    /// Restrict the child to running on the CPUs with the given
+    /// (zero based) indices.
+    ///
+    /// This option is useful for pinning latency sensitive measurements
+    /// to dedicated CPUs. It is only supported on Linux and Windows and
+    /// ignored elsewhere. On Windows, only CPUs in the child's processor
+    /// group can be selected.
+    pub fn cpu_affinity<I>(&mut self, cpus: I) -> &mut Self
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        self.cpu_affinity = Some(cpus.into_iter().collect());
+        self
+    }
+
+    /// Serialize the child with those of all other tests using the
+    /// serialization lock with the given name.
+    ///
+    /// Right before spawning the child, the parent acquires an
+    /// exclusive file lock with the given name, waiting for it to
+    /// become available, and holds it until the child exited. Contrary
+    /// to in-process mutexes, such a lock serializes children spawned
+    /// by different threads and even different test binaries, e.g.,
+    /// when tests share an external resource such as a database.
+    ///
+    /// Tests that are run in-process are not serialized. On targets
+    /// other than Unix and Windows, this option is ignored.
+    pub fn serial<S>(&mut self, name: S) -> &mut Self
+    where
+        S: Into<String>,
+    {
+        self.serial = Some(name.into());
+        self
+    }
+
+    /// Reserve the given number of TCP ports for the child.
+    ///
+    /// The ports are picked by the parent and can be retrieved by the
+    /// child via [`reserved_ports`][crate::reserved_ports]. They are
+    /// guaranteed to differ from the ports reserved for all other
+    /// children running concurrently, including those of other test
+    /// binaries, and not to be in use at the time the child is
+    /// spawned. That way, forked integration tests can each bind their
+    /// own server without stepping on each other's toes.
+    ///
+    /// Tests that are run in-process don't get any ports reserved.
+    pub fn reserve_ports(&mut self, count: usize) -> &mut Self {
+        self.reserve_ports = count;
+        self
+    }
+
+    /// Run the benchmark's function this many times before starting
+    /// to measure.
+    ///
+    /// This option only affects benchmarks run via
+    /// [`Builder::fork_bench`].
+    pub fn warmup_iters(&mut self, iters: u64) -> &mut Self {
+        self.bench.warmup_iters = iters;
+        self
+    }
+
+    /// Measure at least this many iterations of the benchmark's
+    /// function.
+    ///
+    /// By default, a fixed number of samples is taken, the size of
+    /// which depends on how long an iteration takes. This option only
+    /// affects benchmarks run via [`Builder::fork_bench`].
+    pub fn min_iters(&mut self, iters: u64) -> &mut Self {
+        self.bench.min_iters = iters;
+        self
+    }
+
+    /// Set the clock used for measuring benchmark iterations.
+    ///
+    /// This option only affects benchmarks run via
+    /// [`Builder::fork_bench`].
+    pub fn measure(&mut self, measure: Measure) -> &mut Self {
+        self.bench.measure = measure;
+        self
+    }
+
+    /// Launch the child using the provided [`ProcessBackend`].
+    ///
+    /// By default, the child is spawned via
+    /// [`Command::spawn`][std::process::Command::spawn] (see
+    /// [`CommandBackend`]). A custom backend can run it elsewhere,
+    /// for example in a container. Supervision of the child, including
+    /// the evaluation of its output and exit status, happens as usual.
+    pub fn backend<B>(&mut self, backend: B) -> &mut Self
+    where
+        B: ProcessBackend + 'static,
+    {
+        self.backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// Configure the retrying of transient failures to spawn the child.
+    ///
+    /// Under heavy parallelism, spawning the child may fail for
+    /// transient reasons, such as hitting the limit on the number of
+    /// processes (`EAGAIN`). In such a case, spawning is retried up to
+    /// `retries` times, waiting for `backoff` before the first retry
+    /// and doubling the delay with every subsequent one. Other errors
+    /// are reported right away. By default, spawning is retried three
+    /// times, starting with a delay of 10ms. Set `retries` to zero to
+    /// disable retrying.
+    ///
+    /// Retrying applies to any configured [`Builder::backend`] as well.
+    pub fn spawn_retries(&mut self, retries: u32, backoff: Duration) -> &mut Self {
+        self.spawn_retries = Some((retries, backoff));
+        self
+    }
+
+    /// Configure the treatment of flags passed to the test harness
+    /// that `test-fork` does not know about.
+    ///
+    /// The command line of the child is derived from that of the
+    /// current process, which requires knowledge of the flags the test
+    /// harness accepts. By default, an unknown flag, e.g., one added by
+    /// a newer toolchain, causes forking to fail with
+    /// [`Error::UnknownFlag`][crate::Error::UnknownFlag]. With
+    /// [`UnknownFlags::Pass`] or [`UnknownFlags::Drop`], such flags are
+    /// passed on to the child or dropped, respectively, with a warning.
+    /// Unless set here, the treatment is read from the
+    /// `TEST_FORK_UNKNOWN_FLAGS` environment variable (`error`, `pass`,
+    /// or `drop`).
+    pub fn unknown_flags(&mut self, unknown_flags: UnknownFlags) -> &mut Self {
+        self.unknown_flags = Some(unknown_flags);
+        self
+    }
+
+    /// Spawn the child from the executable at `path` instead of the
+    /// current executable.
+    ///
+    /// The executable has to contain the same tests (and fork points)
+    /// as the current one, e.g., because it is a copy of it. That can
+    /// be necessary in environments in which the current executable
+    /// can't be determined reliably. Unless set here, the executable
+    /// is read from the `TEST_FORK_EXE` environment variable. Absent
+    /// both, the current executable is used, as reported by
+    /// [`env::current_exe`] or, should that fail or report a path that
+    /// no longer exists, as found via platform specific means (e.g.,
+    /// `/proc/self/exe` on Linux).
+    pub fn executable<P>(&mut self, path: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.exe = Some(path.into());
+        self
+    }
+
+    /// Prepare the builder for running the fork point with the given
+    /// ID, invoking the [`Builder::before`] hook if we are the parent
+    /// and not merely doing a dry run.
+    fn prepare(&self, fork_id: &str) -> Cow<'_, Self> {
+        if self.raw_fork {
+            let unsupported = [
+                ("env_clear_except", self.env_allow.is_some()),
+                ("stdin", self.stdin.is_some()),
+                ("expected_output", self.expected_output.is_some()),
+                ("backend", self.backend.is_some()),
+                ("executable", self.exe.is_some()),
+                ("spawn_retries", self.spawn_retries.is_some()),
+                #[cfg(target_os = "linux")]
+                ("overlay", !self.overlays.is_empty()),
+                #[cfg(target_os = "linux")]
+                ("pid_namespace", self.pid_namespace),
+                #[cfg(target_os = "linux")]
+                ("fake_time", self.fake_time.is_some()),
+                ("nice", self.nice.is_some()),
+                ("cpu_affinity", self.cpu_affinity.is_some()),
+                #[cfg(all(target_os = "linux", feature = "perf"))]
+                ("perf_counters", self.perf_counters),
+                #[cfg(all(unix, feature = "pty"))]
+                ("pty", self.pty),
+            ];
+            if let Some((option, _)) = unsupported.iter().find(|(_, set)| *set) {
+                panic!("test-fork: `{option}` is not supported in raw fork mode")
+            }
+        }
+
+        match self.before {
+            Some(before) if !is_child(fork_id) && !dry_run::requested() => {
+                let mut builder = self.clone();
+                builder.before = None;
+                let () = before(&mut builder);
+                Cow::Owned(builder)
+            }
+            _ => Cow::Borrowed(self),
+        }
+    }
+
+    /// Determine the treatment of unknown flags.
+    fn resolved_unknown_flags(&self) -> UnknownFlags {
+        UnknownFlags::resolve(self.unknown_flags)
+    }
+
+    /// Retrieve the options to launch the child with, running the test
+    /// in the current process instead if `in_process` is set.
+    fn launch_options(&self, in_process: bool) -> LaunchOptions<'_> {
+        let backend = self.backend.as_deref().unwrap_or(&CommandBackend);
+        let (retries, backoff) = self
+            .spawn_retries
+            .unwrap_or((DEFAULT_SPAWN_RETRIES, DEFAULT_SPAWN_BACKOFF));
+        LaunchOptions {
+            in_process,
+            raw: self.raw_fork,
+            unknown_flags: self.resolved_unknown_flags(),
+            exe: self.exe.as_deref(),
+            backend: Retrying::new(backend, retries, backoff),
+        }
+    }
+
+    /// Retrieve the data to feed to the child's standard input, if any.
+    fn stdin_data(&self) -> Result<Option<Vec<u8>>> {
+        let data = match &self.stdin {
+            None => None,
+            Some(Stdin::Data(data)) => Some(data.clone()),
+            Some(Stdin::File(path)) => Some(fs::read(path)?),
+        };
+        Ok(data)
+    }
+
+    /// Retrieve the options for supervising the child.
+    pub(crate) fn supervision(&self) -> Supervision {
+        let mut supervision = self.supervision;
+        // We need the child's output to compare it against the golden
+        // file.
+        supervision.capture_output |= self.expected_output.is_some();
+        supervision.persist_output |= persist::requested();
+        supervision
+    }
+
+    /// Perform checks on the child's outcome that are not part of the
+    /// child's supervision.
+    pub(crate) fn finish(
+        &self,
+        test_name: &str,
+        outcome: Option<result::Result<ForkOutcome, ChildFailure>>,
+    ) -> result::Result<ForkOutcome, ChildFailure> {
+        let result = match outcome {
+            Some(outcome) => outcome.and_then(|outcome| self.check_output(test_name, outcome)),
+            // A child process skipping over this occurrence of the fork
+            // point.
+            None => return Ok(ForkOutcome::default()),
+        };
+
+        if let Some(after) = self.after {
+            let () = after(&result);
+        }
+        result
+    }
+
+    /// Check the output of a successful child, if requested.
+    fn check_output(
+        &self,
+        test_name: &str,
+        mut outcome: ForkOutcome,
+    ) -> result::Result<ForkOutcome, ChildFailure> {
+        if let Some(path) = &self.expected_output {
+            let () = golden::check(path, &outcome.stdout)?;
+
+            if !self.supervision.capture_output {
+                let () = forward_output(
+                    &outcome.stdout,
+                    &outcome.stderr,
+                    test_name,
+                    self.supervision,
+                );
+                outcome.stdout.clear();
+                outcome.stderr.clear();
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// Configure the child process running the given test according
+    /// to this builder.
+    fn configure(&self, test_name: &str, command: &mut Command) {
+        // Steps with side effects are skipped in a dry run, as the child
+        // is not going to be spawned.
+        let dry_run = dry_run::requested();
+        // The child has to come to the same conclusion as to whether
+        // to fork, even if its environment gets cleared.
+        if let Some(name) = &self.opt_in_env {
+            if let Some(value) = env::var_os(name) {
+                command.env(name, value);
+            }
+        }
+        if self.expected_output.is_some() {
+            let () = golden::configure(command);
+        }
+        if let Some(allow) = &self.env_allow {
+            let () = clear_env_except(command, allow);
+        }
+        if self.supervision.sanitizers {
+            let () = sanitizer::configure(command);
+        }
+        let () = self.supervision.color.configure(command);
+        if self.debug {
+            let () = debug::configure(command);
+        }
+        if self.supervision.artifacts != Artifacts::Disabled && !dry_run {
+            if let Err(err) = artifacts::configure(command, test_name) {
+                panic!("test-fork: failed to create artifact directory: {err}")
+            }
+        }
+        if self.stdin.is_some() {
+            command.stdin(Stdio::piped());
+        }
+        command.envs(self.envs.iter().map(|(key, value)| (key, value)));
+        let () = sched::configure(command, self.nice, self.cpu_affinity.as_deref());
+        #[cfg(target_os = "linux")]
+        if let Some(spec) = &self.fake_time {
+            let lib = faketime::find_lib().unwrap_or_else(|| {
+                panic!("test-fork: failed to find libfaketime; set TEST_FORK_FAKETIME_LIB to provide it")
+            });
+            let () = faketime::configure(command, spec, &lib);
+        }
+        if let Some(name) = &self.serial {
+            if !dry_run {
+                let () = serial::prepare(name);
+            }
+        }
+        if self.reserve_ports > 0 && !dry_run {
+            if let Err(err) = resource::reserve_ports(command, self.reserve_ports) {
+                panic!("test-fork: failed to reserve ports: {err}")
+            }
+        }
+        #[cfg(target_os = "linux")]
+        if (!self.overlays.is_empty() || self.pid_namespace) && !dry_run {
+            if let Err(err) = namespace::configure(command, &self.overlays, self.pid_namespace) {
+                panic!("test-fork: failed to set up namespaces: {err}")
+            }
+        }
+        #[cfg(target_os = "linux")]
+        if !self.drop_caps.is_empty() {
+            let () = caps::configure(command, &self.drop_caps);
+        }
+        #[cfg(all(unix, feature = "pty"))]
+        if self.pty && !dry_run {
+            if let Err(err) = pty::configure(command) {
+                panic!("test-fork: failed to open pseudo-terminal: {err}")
+            }
+        }
+        // The counters are inherited by the child once spawned.
+        #[cfg(all(target_os = "linux", feature = "perf"))]
+        if self.perf_counters && !dry_run {
+            let () = perf::Collector::prepare();
+        }
+    }
+
+    /// Check whether the test is to be run in the current process
+    /// instead of a child process.
+    fn runs_in_process(&self) -> bool {
+        self.in_process
+            || !PROCESS_SUPPORT
+            || self
+                .opt_in_env
+                .as_deref()
+                .is_some_and(|name| !opted_in(name))
+    }
+
+    /// Run `test` in the current process, for a builder configured to
+    /// do so (see [`Builder::runs_in_process`]).
+    fn run_in_process<F, T>(
+        &self,
+        fork_id: &str,
+        test_name: &str,
+        test: F,
+    ) -> result::Result<ForkOutcome, ChildFailure>
+    where
+        F: FnOnce() -> T,
+        T: Termination,
+    {
+        let outcome = fork_int_with(
+            test_name,
+            fork_id,
+            &self.launch_options(true),
+            |_| (),
+            |_| None,
+            test,
+        )?;
+        self.finish(test_name, outcome)
+    }
+
+    /// Check whether a child not joined is to be killed.
+    pub(crate) fn kills_on_drop(&self) -> bool {
+        !self.leave_running
+    }
+
+    /// Convert the result of a `try_*` fork into that of its panicking
+    /// counterpart.
+    fn check(&self, result: result::Result<ForkOutcome, ChildFailure>) -> Result<()> {
+        self.check_outcome(result).map(|_outcome| ())
+    }
+
+    /// Like [`Builder::check`], but hand out the [`ForkOutcome`] on
+    /// success.
+    fn check_outcome(
+        &self,
+        result: result::Result<ForkOutcome, ChildFailure>,
+    ) -> Result<ForkOutcome> {
+        match result {
+            Ok(outcome) => Ok(outcome),
+            Err(ChildFailure::SpawnFailed(err)) => Err(err),
+            // A test expected to panic has to fail if the child failed
+            // otherwise, which it does if we don't panic.
+            Err(failure) if self.should_panic && !failure.is_panic() => {
+                eprintln!("test-fork: {failure} (expected the test to panic)");
+                Ok(ForkOutcome::default())
+            }
+            Err(failure) => failure.raise(),
+        }
+    }
+
+    fn monitor(&self) -> Result<Option<Monitor>> {
+        // A child waiting for a debugger (or being debugged) can't be
+        // expected to emit heartbeats.
+        if self.debug || debug::requested() {
+            return Ok(None)
+        }
+
+        let monitor = self.heartbeat.map(Monitor::new).transpose()?;
+        Ok(monitor)
+    }
+
+    /// Simulate a process fork using this builder's configuration.
+    ///
+    /// Please refer to [`fork`][crate::fork()] for details.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the child process fails, with a message describing
+    /// the [`ChildFailure`]. Use [`Builder::try_fork`] to handle
+    /// child failures programmatically instead.
+    pub fn fork<F, T>(&self, fork_id: &str, test_name: &str, test: F) -> Result<()>
+    where
+        F: Fn() -> T,
+        T: Termination,
+    {
+        self.check(self.try_fork(fork_id, test_name, test))
+    }
+
+    /// Simulate a process fork using this builder's configuration,
+    /// reporting a failure of the child process as a [`ChildFailure`]
+    /// instead of panicking.
+    ///
+    /// In a child process that skips over this occurrence of the fork
+    /// point, a default [`ForkOutcome`] is returned.
+    pub fn try_fork<F, T>(
+        &self,
+        fork_id: &str,
+        test_name: &str,
+        test: F,
+    ) -> result::Result<ForkOutcome, ChildFailure>
+    where
+        F: Fn() -> T,
+        T: Termination,
+    {
+        let slf = self.prepare(fork_id);
+        let monitor = slf.monitor()?;
+        let heartbeat_env = monitor.as_ref().map(Monitor::child_env);
+        let stdin = slf.stdin_data()?;
+
+        let outcome = fork_int_with(
+            test_name,
+            fork_id,
+            &slf.launch_options(slf.runs_in_process()),
+            |cmd| {
+                cmd.envs(heartbeat_env);
+                let () = slf.configure(test_name, cmd);
+            },
+            |mut child| {
+                if let Some(stdin) = stdin {
+                    let () = child.write_stdin(stdin);
+                }
+                Some(supervise_child(child, monitor, slf.supervision()))
+            },
+            test,
+        )?;
+        slf.finish(test_name, outcome)
+    }
+
+    /// Simulate a process fork for each of the given configurations,
+    /// using this builder's configuration.
+    ///
+    /// Each configuration is a set of environment variables to set for
+    /// the respective child, in addition to those configured via
+    /// [`Builder::env`]. Children are run one after the other, but all
+    /// of them are run, even if one fails. The failures of all failed
+    /// children are then reported as a single
+    /// [`ChildFailure::Variants`], labeled with the offending
+    /// configurations.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if any of the children fails, with a message describing
+    /// all failures.
+    pub fn fork_matrix<F, T>(
+        &self,
+        fork_id: &str,
+        test_name: &str,
+        configurations: &[&[(&str, &str)]],
+        test: F,
+    ) -> Result<()>
+    where
+        F: Fn() -> T,
+        T: Termination,
+    {
+        let mut failures = Vec::new();
+        for envs in configurations {
+            let mut builder = self.clone();
+            for (key, value) in *envs {
+                let _builder = builder.env(key, value);
+            }
+
+            match builder.try_fork(fork_id, test_name, &test) {
+                Ok(_outcome) => (),
+                Err(ChildFailure::SpawnFailed(err)) => return Err(err),
+                Err(failure) => {
+                    let label = envs
+                        .iter()
+                        .map(|(key, value)| format!("{key}={value}"))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    let () = failures.push((format!("configuration `{label}`"), failure));
+                }
+            }
+        }
+
+        if failures.is_empty() {
+            return Ok(())
+        }
+
+        let failure = ChildFailure::Variants {
+            total: configurations.len(),
+            failures,
+        };
+        self.check(Err(failure))
+    }
+
+    /// Simulate a process fork, running the given number of instances
+    /// of the child concurrently, using this builder's configuration.
+    ///
+    /// All instances are spawned before any is waited for, which makes
+    /// this function suitable for smoking out races between processes
+    /// (e.g., over file locks or other named resources). Each instance
+    /// finds its (zero based) index in the `TEST_FORK_INSTANCE`
+    /// environment variable. The failures of all failed instances are
+    /// reported as a single [`ChildFailure::Variants`].
+    ///
+    /// ## Panics
+    ///
+    /// Panics if any of the instances fails, with a message describing
+    /// all failures.
+    ///
+    /// Panics if combined with [`Builder::serial`], as instances would
+    /// wait for each other to release the lock.
+    #[expect(clippy::panic_in_result_fn)]
+    pub fn fork_instances<F, T>(
+        &self,
+        fork_id: &str,
+        test_name: &str,
+        instances: usize,
+        test: F,
+    ) -> Result<()>
+    where
+        F: Fn() -> T,
+        T: Termination,
+    {
+        if self.serial.is_some() {
+            panic!("test-fork: `serial` is not supported with concurrent instances")
+        }
+
+        let mut handles = Vec::with_capacity(instances);
+        for instance in 0..instances {
+            let mut builder = self.clone();
+            let handle = builder
+                .env(INSTANCE_ENV, instance.to_string())
+                .spawn_fork(fork_id, test_name, &test)?;
+            let () = handles.push(handle);
+        }
+
+        let mut failures = Vec::new();
+        for (instance, handle) in handles.into_iter().enumerate() {
+            match handle.join() {
+                Ok(_outcome) => (),
+                Err(ChildFailure::SpawnFailed(err)) => return Err(err),
+                Err(failure) => {
+                    let () = failures.push((format!("instance {instance}"), failure));
+                }
+            }
+        }
+
+        if failures.is_empty() {
+            return Ok(())
+        }
+
+        let failure = ChildFailure::Variants {
+            total: instances,
+            failures,
+        };
+        self.check(Err(failure))
+    }
+
+    /// Simulate a process fork without waiting for the child process
+    /// to exit.
+    ///
+    /// The child is spawned just like with [`Builder::try_fork`], but
+    /// the function returns right away with a [`ForkHandle`], allowing
+    /// the parent to carry on (e.g., to interact with the child) and
+    /// to join the child later on.
+    ///
+    /// In a child process that skips over this occurrence of the fork
+    /// point, as well as when the test is run in-process, the returned
+    /// handle does not represent any child.
+    pub fn spawn_fork<F, T>(&self, fork_id: &str, test_name: &str, test: F) -> Result<ForkHandle>
+    where
+        F: Fn() -> T,
+        T: Termination,
+    {
+        let slf = self.prepare(fork_id);
+        let monitor = slf.monitor()?;
+        let heartbeat_env = monitor.as_ref().map(Monitor::child_env);
+        let stdin = slf.stdin_data()?;
+
+        let child = fork_int_with(
+            test_name,
+            fork_id,
+            &slf.launch_options(slf.runs_in_process()),
+            |cmd| {
+                cmd.envs(heartbeat_env);
+                let () = slf.configure(test_name, cmd);
+            },
+            |mut child| {
+                if let Some(stdin) = stdin {
+                    let () = child.write_stdin(stdin);
+                }
+                Some(child)
+            },
+            test,
+        )?;
+        let handle = ForkHandle::new(slf.into_owned(), test_name, child, monitor);
+        Ok(handle)
+    }
+
+    /// Simulate a process fork, supervising the child process using a
+    /// custom function.
+    ///
+    /// Instead of waiting for the child and forwarding its output (as
+    /// [`Builder::fork`] does), `supervisor` is handed the
+    /// [`ChildWrapper`] representing the spawned child and is in charge
+    /// of everything from there on. Its result is returned to the
+    /// caller. Heartbeat based hang detection is not available in this
+    /// mode.
+    ///
+    /// In the child process, `test` is run and the supervisor is not
+    /// invoked. The child's result is communicated to the parent only
+    /// via the process' exit status and output. Occurrences of the
+    /// fork point that a child skips over evaluate to `R::default()`.
+    ///
+    /// Please refer to [`fork`][crate::fork()] for additional details.
+    pub fn fork_supervised<F, T, P, R>(
+        &self,
+        fork_id: &str,
+        test_name: &str,
+        supervisor: P,
+        test: F,
+    ) -> Result<R>
+    where
+        F: Fn() -> T,
+        T: Termination,
+        P: FnOnce(ChildWrapper) -> R,
+        R: Default,
+    {
+        fork_int_with(
+            test_name,
+            fork_id,
+            &self.launch_options(self.runs_in_process()),
+            |_cmd| (),
+            supervisor,
+            test,
+        )
+    }
+
+    /// Simulate a process fork, exchanging data with the child process,
+    /// using this builder's configuration.
+    ///
+    /// Please refer to [`fork_in_out_vec`][crate::fork_in_out_vec] for
+    /// details.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the child process fails, with a message describing
+    /// the [`ChildFailure`]. Use [`Builder::try_fork_in_out`] to handle
+    /// child failures programmatically instead.
+    pub fn fork_in_out<F, T>(
+        &self,
+        fork_id: &str,
+        test_name: &str,
+        test: F,
+        data: &mut Vec<u8>,
+    ) -> Result<()>
+    where
+        F: Fn(&mut Vec<u8>) -> T,
+        T: Termination,
+    {
+        self.check(self.try_fork_in_out(fork_id, test_name, test, data))
+    }
+
+    /// Simulate a process fork, exchanging data with the child process,
+    /// using this builder's configuration and reporting a failure of
+    /// the child process as a [`ChildFailure`] instead of panicking.
+    #[expect(clippy::unwrap_in_result)]
+    pub fn try_fork_in_out<F, T>(
+        &self,
+        fork_id: &str,
+        test_name: &str,
+        test: F,
+        data: &mut Vec<u8>,
+    ) -> result::Result<ForkOutcome, ChildFailure>
+    where
+        F: Fn(&mut Vec<u8>) -> T,
+        T: Termination,
+    {
+        let slf = self.prepare(fork_id);
+        if slf.runs_in_process() {
+            // The test is run in-process and can operate on the data
+            // directly.
+            return slf.run_in_process(fork_id, test_name, || test(data))
+        }
+        let monitor = slf.monitor()?;
+        let heartbeat_env = monitor.as_ref().map(Monitor::child_env);
+        let stdin = slf.stdin_data()?;
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind TCP socket");
+        let addr = listener.local_addr().unwrap();
+
+        let outcome = fork_int_with(
+            test_name,
+            fork_id,
+            &slf.launch_options(false),
+            |cmd| {
+                cmd.env(fork_id, addr.to_string()).envs(heartbeat_env);
+                let () = slf.configure(test_name, cmd);
+            },
+            |mut child| {
+                if let Some(stdin) = stdin {
+                    let () = child.write_stdin(stdin);
+                }
+                let (outcome, received) = supervise_exchange(
+                    child,
+                    monitor,
+                    slf.supervision(),
+                    || {
+                        let (mut stream, _addr) = listener.accept()?;
+                        let () = send_data(&mut stream, data)?;
+                        let mut received = Vec::new();
+                        let () = receive_data(&mut stream, &mut received)?;
+                        Ok(received)
+                    },
+                    || unblock_tcp(addr),
+                );
+                // A child failing before sending its data back is
+                // reported as part of supervising it, in which case the
+                // data are left untouched.
+                if let Some(received) = received {
+                    *data = received;
+                }
+                Some(outcome)
+            },
+            || {
+                let mut stream = connect_to_parent(fork_id);
+                let mut data = Vec::new();
+                let () = receive_data(&mut stream, &mut data)
+                    .expect("failed to receive data from parent");
+                let status = test(&mut data);
+                let () = send_data(&mut stream, &data).expect("failed to send data to parent");
+                status
+            },
+        )?;
+        slf.finish(test_name, outcome)
+    }
+
+    /// Simulate a process fork, sharing a memory mapped buffer with the
+    /// child process, using this builder's configuration.
+    ///
+    /// `test` is invoked in the child with the contents of `data`,
+    /// which are mapped into the child instead of being copied. Changes
+    /// made by the child are visible in `data` once it exited. That
+    /// makes this method well suited for large inputs, for which
+    /// [`Builder::fork_in_out`] would be costly.
+    ///
+    /// The buffer is conveyed to the child by path, which is not
+    /// accessible to children run by a custom
+    /// [process backend][Builder::backend] isolating their file system.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the child process fails, with a message describing
+    /// the [`ChildFailure`]. Use [`Builder::try_fork_shared`] to handle
+    /// child failures programmatically instead.
+    #[cfg(unix)]
+    pub fn fork_shared<F, T>(
+        &self,
+        fork_id: &str,
+        test_name: &str,
+        test: F,
+        data: &mut SharedBuffer,
+    ) -> Result<()>
+    where
+        F: Fn(&mut [u8]) -> T,
+        T: Termination,
+    {
+        self.check(self.try_fork_shared(fork_id, test_name, test, data))
+    }
+
+    /// Simulate a process fork, sharing a memory mapped buffer with the
+    /// child process, using this builder's configuration and reporting
+    /// a failure of the child process as a [`ChildFailure`] instead of
+    /// panicking.
+    #[cfg(unix)]
+    #[expect(clippy::panic_in_result_fn)]
+    pub fn try_fork_shared<F, T>(
+        &self,
+        fork_id: &str,
+        test_name: &str,
+        test: F,
+        data: &mut SharedBuffer,
+    ) -> result::Result<ForkOutcome, ChildFailure>
+    where
+        F: Fn(&mut [u8]) -> T,
+        T: Termination,
+    {
+        let slf = self.prepare(fork_id);
+        if slf.runs_in_process() {
+            return slf.run_in_process(fork_id, test_name, || test(data))
+        }
+        let monitor = slf.monitor()?;
+        let heartbeat_env = monitor.as_ref().map(Monitor::child_env);
+        let stdin = slf.stdin_data()?;
+        let path = data
+            .path()
+            .expect("buffer mapped by a child cannot be shared")
+            .to_path_buf();
+
+        let outcome = fork_int_with(
+            test_name,
+            fork_id,
+            &slf.launch_options(false),
+            |cmd| {
+                cmd.env(fork_id, &path).envs(heartbeat_env);
+                let () = slf.configure(test_name, cmd);
+            },
+            |mut child| {
+                if let Some(stdin) = stdin {
+                    let () = child.write_stdin(stdin);
+                }
+                Some(supervise_child(child, monitor, slf.supervision()))
+            },
+            || {
+                let path = env::var_os(fork_id)
+                    .unwrap_or_else(|| panic!("failed to retrieve {fork_id} environment variable"));
+                // Replacing the child's own buffer drops it, removing
+                // its backing file, as the child won't exit regularly.
+                *data = SharedBuffer::open(Path::new(&path))
+                    .expect("failed to map buffer shared by parent");
+                test(data)
+            },
+        )?;
+        slf.finish(test_name, outcome)
+    }
+
+    /// Simulate a process fork, passing open file descriptors to the
+    /// child process, using this builder's configuration.
+    ///
+    /// `test` is invoked in the child with duplicates of `fds`, in the
+    /// same order, referring to the same open file descriptions (e.g.,
+    /// sharing file offsets and socket connections). The descriptors
+    /// are sent over a Unix domain socket, so at most 253 can be passed.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the child process fails, with a message describing
+    /// the [`ChildFailure`]. Use [`Builder::try_fork_with_fds`] to
+    /// handle child failures programmatically instead.
+    #[cfg(unix)]
+    pub fn fork_with_fds<F, T>(
+        &self,
+        fork_id: &str,
+        test_name: &str,
+        fds: &[BorrowedFd<'_>],
+        test: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(Vec<OwnedFd>) -> T,
+        T: Termination,
+    {
+        self.check(self.try_fork_with_fds(fork_id, test_name, fds, test))
+    }
+
+    /// Simulate a process fork, passing open file descriptors to the
+    /// child process, using this builder's configuration and reporting
+    /// a failure of the child process as a [`ChildFailure`] instead of
+    /// panicking.
+    #[cfg(unix)]
+    #[expect(clippy::panic_in_result_fn, clippy::unwrap_in_result)]
+    pub fn try_fork_with_fds<F, T>(
+        &self,
+        fork_id: &str,
+        test_name: &str,
+        fds: &[BorrowedFd<'_>],
+        test: F,
+    ) -> result::Result<ForkOutcome, ChildFailure>
+    where
+        F: FnOnce(Vec<OwnedFd>) -> T,
+        T: Termination,
+    {
+        let slf = self.prepare(fork_id);
+        if slf.runs_in_process() {
+            let fds = fds
+                .iter()
+                .map(BorrowedFd::try_clone_to_owned)
+                .collect::<io::Result<Vec<_>>>()
+                .expect("failed to duplicate file descriptors");
+            return slf.run_in_process(fork_id, test_name, || test(fds))
+        }
+        let monitor = slf.monitor()?;
+        let heartbeat_env = monitor.as_ref().map(Monitor::child_env);
+        let stdin = slf.stdin_data()?;
+        // The socket is only bound once we actually spawn a child, so
+        // that the child itself doesn't leave a socket file behind.
+        let path = fds::socket_path();
+        let listener = OnceCell::new();
+
+        let result = fork_int_with(
+            test_name,
+            fork_id,
+            &slf.launch_options(false),
+            |cmd| {
+                let _result = listener
+                    .set(UnixListener::bind(&path).expect("failed to bind Unix domain socket"));
+                cmd.env(fork_id, &path).envs(heartbeat_env);
+                let () = slf.configure(test_name, cmd);
+            },
+            |mut child| {
+                if let Some(stdin) = stdin {
+                    let () = child.write_stdin(stdin);
+                }
+                let listener = listener.get().expect("socket not bound");
+                let (outcome, _sent) = supervise_exchange(
+                    child,
+                    monitor,
+                    slf.supervision(),
+                    || {
+                        let (stream, _addr) = listener.accept()?;
+                        fds::send(&stream, fds)
+                    },
+                    || {
+                        // Connecting is only meant to wake up a pending
+                        // `accept`; failure means there is none.
+                        let _result = UnixStream::connect(&path);
+                    },
+                );
+                Some(outcome)
+            },
+            || {
+                let path = env::var_os(fork_id)
+                    .unwrap_or_else(|| panic!("failed to retrieve {fork_id} environment variable"));
+                let stream =
+                    UnixStream::connect(path).expect("failed to establish connection with parent");
+                let fds =
+                    fds::receive(&stream).expect("failed to receive file descriptors from parent");
+                test(fds)
+            },
+        );
+        if listener.get().is_some() {
+            let _result = fs::remove_file(&path);
+        }
+        slf.finish(test_name, result?)
+    }
+
+    /// Run a benchmark in a child process, using this builder's
+    /// configuration.
+    ///
+    /// `bench` is invoked in the child with a [`Bencher`], whose
+    /// measurement loop is controlled by [`Builder::warmup_iters`],
+    /// [`Builder::min_iters`], and [`Builder::measure`]. The child
+    /// reports the individual samples it took to the parent, which
+    /// summarizes them, prints the [`BenchSummary`] to its standard
+    /// error stream, and returns it. The summary also contains the
+    /// overhead of running the benchmark in a child process (see
+    /// [`BenchSummary::overhead`]). If `bench` never calls
+    /// [`Bencher::iter`], nothing is measured and the summary reports
+    /// zero iterations.
+    ///
+    /// If the `TEST_FORK_BENCH_OUTPUT` environment variable is set, the
+    /// summary, including all samples, is additionally appended to the
+    /// file it names: as CSV if the file has a `.csv` extension and as
+    /// JSON Lines otherwise. Results of benchmarks from multiple test
+    /// runs can thereby be accumulated for consumption by other tools.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the child process fails, with a message describing
+    /// the [`ChildFailure`].
+    pub fn fork_bench<F>(&self, fork_id: &str, test_name: &str, bench: F) -> Result<BenchSummary>
+    where
+        F: Fn(&mut Bencher),
+    {
+        let options = self.bench;
+        // The measurements are conveyed as part of the child's output,
+        // so we need to see it before forwarding it.
+        let mut builder = self.clone();
+        let _builder = builder.capture_output(true);
+        let forks = !builder.runs_in_process();
+        // When running in-process, the summary is available directly.
+        let in_process = Mutex::new(None);
+
+        let start = Instant::now();
+        let outcome = builder.check_outcome(builder.try_fork(fork_id, test_name, || {
+            let start = Instant::now();
+            let mut bencher = Bencher::new(options);
+            let () = bench(&mut bencher);
+            let elapsed = start.elapsed();
+
+            if let Some(summary) = bencher.into_summary() {
+                if forks {
+                    let () = summary.emit(elapsed);
+                } else {
+                    *in_process.lock().unwrap_or_else(PoisonError::into_inner) =
+                        Some((summary, elapsed));
+                }
+            }
+        }))?;
+        // Everything but the time spent in the benchmark function
+        // itself is overhead of running it in a separate process.
+        let total = start.elapsed();
+
+        let (measured, stderr) = BenchSummary::extract(&outcome.stderr);
+        if !self.supervision.capture_output {
+            let () = forward_output(&outcome.stdout, &stderr, test_name, self.supervision);
+        }
+
+        let summary = measured
+            .or_else(|| {
+                in_process
+                    .into_inner()
+                    .unwrap_or_else(PoisonError::into_inner)
+            })
+            .map(|(mut summary, elapsed)| {
+                summary.overhead = total.saturating_sub(elapsed);
+                summary
+            })
+            .unwrap_or_default();
+        if summary.iters > 0 {
+            let () = summary.report(test_name, options.measure);
+        }
+        Ok(summary)
+    }
+
+    /// Simulate a process fork, passing a value produced by `fixture`
+    /// in the parent to the test running in the child process.
+    ///
+    /// `fixture` is invoked in the parent only. The value it produces
+    /// is serialized, transmitted to the child, deserialized there,
+    /// and handed to `test`. That way, resources set up in the parent
+    /// (e.g., a server) can be described to the test.
+    ///
+    /// Please refer to [`fork`][crate::fork()] for additional details.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the child process fails, with a message describing
+    /// the [`ChildFailure`]. Use [`Builder::try_fork_with_fixture`] to
+    /// handle child failures programmatically instead.
+    #[cfg(feature = "serde")]
+    pub fn fork_with_fixture<X, V, F, T>(
+        &self,
+        fork_id: &str,
+        test_name: &str,
+        fixture: X,
+        test: F,
+    ) -> Result<()>
+    where
+        X: FnOnce() -> V,
+        V: Serialize + DeserializeOwned,
+        F: Fn(V) -> T,
+        T: Termination,
+    {
+        self.check(self.try_fork_with_fixture(fork_id, test_name, fixture, test))
+    }
+
+    /// Simulate a process fork, passing a value produced by `fixture`
+    /// in the parent to the test running in the child process and
+    /// reporting a failure of the child process as a [`ChildFailure`]
+    /// instead of panicking.
+    #[cfg(feature = "serde")]
+    #[expect(clippy::unwrap_in_result)]
+    pub fn try_fork_with_fixture<X, V, F, T>(
+        &self,
+        fork_id: &str,
+        test_name: &str,
+        fixture: X,
+        test: F,
+    ) -> result::Result<ForkOutcome, ChildFailure>
+    where
+        X: FnOnce() -> V,
+        V: Serialize + DeserializeOwned,
+        F: Fn(V) -> T,
+        T: Termination,
+    {
+        let slf = self.prepare(fork_id);
+        if slf.runs_in_process() {
+            // There is no child to transmit the fixture to; the test is
+            // run in-process.
+            let value = fixture();
+            return slf.run_in_process(fork_id, test_name, || test(value))
+        }
+        let monitor = slf.monitor()?;
+        let heartbeat_env = monitor.as_ref().map(Monitor::child_env);
+        let stdin = slf.stdin_data()?;
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind TCP socket");
+        let addr = listener.local_addr().unwrap();
+
+        let outcome = fork_int_with(
+            test_name,
+            fork_id,
+            &slf.launch_options(false),
+            |cmd| {
+                cmd.env(fork_id, addr.to_string()).envs(heartbeat_env);
+                let () = slf.configure(test_name, cmd);
+            },
+            |mut child| {
+                if let Some(stdin) = stdin {
+                    let () = child.write_stdin(stdin);
+                }
+                let value = serde_json::to_vec(&fixture()).expect("failed to serialize fixture");
+                let (outcome, _sent) = supervise_exchange(
+                    child,
+                    monitor,
+                    slf.supervision(),
+                    || {
+                        let (mut stream, _addr) = listener.accept()?;
+                        // The connection gets closed once we are done,
+                        // signaling the end of the data.
+                        stream.write_all(&value)
+                    },
+                    || unblock_tcp(addr),
+                );
+                Some(outcome)
+            },
+            || {
+                let mut stream = connect_to_parent(fork_id);
+                let mut data = Vec::new();
+                let _count = stream
+                    .read_to_end(&mut data)
+                    .expect("failed to receive fixture from parent");
+                let value = serde_json::from_slice(&data).expect("failed to deserialize fixture");
+                test(value)
+            },
+        )?;
+        slf.finish(test_name, outcome)
+    }
+
+    /// Run a `proptest` property test, running each generated test case
+    /// in a child process of its own.
+    ///
+    /// Test cases are generated by `strategy` in the parent and
+    /// transferred to the child in serialized form, just like
+    /// [fixtures][Builder::fork_with_fixture]. A test case that
+    /// crashes or aborts the child, hangs (see [`Builder::heartbeat`]),
+    /// or otherwise fails in a way that [`ChildFailure`] describes, is
+    /// considered failed and gets shrunk as usual, without affecting
+    /// the parent or any other test case. Test cases rejected in the
+    /// child (e.g., via `prop_assume!`) are reported as rejected.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the property does not hold, with a message containing
+    /// the minimal failing input.
+    #[cfg(feature = "proptest")]
+    #[expect(clippy::panic_in_result_fn)]
+    pub fn fork_proptest<S, F>(
+        &self,
+        fork_id: &str,
+        test_name: &str,
+        config: ProptestConfig,
+        strategy: S,
+        test: F,
+    ) -> Result<()>
+    where
+        S: Strategy,
+        S::Value: Serialize + DeserializeOwned,
+        F: Fn(S::Value) -> TestCaseResult,
+    {
+        let mut runner = TestRunner::new(config);
+        if self.runs_in_process() {
+            // Test cases are run in-process and can report their result
+            // directly.
+            return report_proptest(runner.run(&strategy, test))
+        }
+
+        let run_case = |value| match test(value) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(TestCaseError::Reject(reason)) => {
+                eprintln!("test-fork: test case rejected: {reason}");
+                ExitCode::from(EX_REJECTED)
+            }
+            Err(TestCaseError::Fail(reason)) => panic!("{reason}"),
+        };
+
+        if is_child(fork_id) {
+            self.run_child_cases(fork_id, test_name, run_case)
+        }
+
+        let spawn_error = RefCell::new(None);
+        let result = runner.run(&strategy, |value| {
+            match self.try_fork_with_fixture(fork_id, test_name, move || value, run_case) {
+                Ok(_outcome) => Ok(()),
+                Err(ChildFailure::ExitCode { code }) if code == i32::from(EX_REJECTED) => {
+                    Err(TestCaseError::reject("test case rejected in child"))
+                }
+                Err(ChildFailure::SpawnFailed(err)) => {
+                    let failure = TestCaseError::fail(format!("failed to spawn child: {err}"));
+                    let _prev = spawn_error.borrow_mut().replace(err);
+                    Err(failure)
+                }
+                Err(failure) => Err(TestCaseError::fail(failure.to_string())),
+            }
+        });
+
+        if let Some(err) = spawn_error.into_inner() {
+            return Err(err)
+        }
+        report_proptest(result)
+    }
+
+    /// Run a `quickcheck` property, running each generated test case in
+    /// a child process of its own.
+    ///
+    /// Inputs are generated by `quickcheck` in the parent and
+    /// transferred to the child in serialized form, just like
+    /// [fixtures][Builder::fork_with_fixture]. A test case for which
+    /// the property does not hold, that crashes or aborts the child,
+    /// hangs (see [`Builder::heartbeat`]), or otherwise fails in a way
+    /// that [`ChildFailure`] describes, is considered failed and its
+    /// input gets shrunk, again running each candidate in a child of
+    /// its own. Test cases discarded in the child (e.g., via
+    /// `TestResult::discard`) are reported as discarded.
+    ///
+    /// Note that the minimum number of passed tests configured via
+    /// `QuickCheck::min_tests_passed` is not enforced.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the property does not hold, with a message containing
+    /// the minimal failing input.
+    #[cfg(feature = "quickcheck")]
+    #[expect(clippy::panic_in_result_fn)]
+    pub fn fork_quickcheck<A, F, T>(
+        &self,
+        fork_id: &str,
+        test_name: &str,
+        mut quickcheck: QuickCheck,
+        property: F,
+    ) -> Result<()>
+    where
+        A: Arbitrary + Debug + Serialize + DeserializeOwned,
+        F: Fn(A) -> T + 'static,
+        T: Testable,
+    {
+        let property = ForkedProperty {
+            builder: self.clone(),
+            fork_id: fork_id.to_string(),
+            test_name: test_name.to_string(),
+            property,
+            spawn_error: Rc::default(),
+            failure: Rc::default(),
+        };
+        if is_child(fork_id) {
+            self.run_child_cases(fork_id, test_name, |value| property.run_case(value))
+        }
+
+        let spawn_error = Rc::clone(&property.spawn_error);
+        let failure = Rc::clone(&property.failure);
+        let result = quickcheck.quicktest(property);
+
+        if let Some(err) = spawn_error.take() {
+            return Err(err)
+        }
+        match (result, failure.take()) {
+            (Ok(_passed), _) => Ok(()),
+            (Err(_result), Some((reason, value))) => {
+                panic!(
+                    "test-fork: property test failed: {reason}; minimal failing input: {value:?}"
+                )
+            }
+            (Err(result), None) => panic!("test-fork: property test failed: {result:?}"),
+        }
+    }
+
+    /// Act as the child for one of the test cases of a property test,
+    /// running it via `run_case`.
+    #[cfg(any(feature = "proptest", feature = "quickcheck"))]
+    fn run_child_cases<V, F>(&self, fork_id: &str, test_name: &str, run_case: F) -> !
+    where
+        V: Serialize + DeserializeOwned,
+        F: Fn(V) -> ExitCode,
+    {
+        // The test case to run is provided by the parent. Generating
+        // test cases here would be pointless and, as the fork point is
+        // encountered once per test case, prone to not reaching the
+        // occurrence we were spawned for. Hence, we just loop until we
+        // get there, which ends with us exiting.
+        loop {
+            let _result = self.try_fork_with_fixture(
+                fork_id,
+                test_name,
+                || -> V { unreachable!("test case is only generated in the parent") },
+                &run_case,
+            );
+        }
+    }
+}
+
+
+/// A `quickcheck` property running each test case in a child process.
+#[cfg(feature = "quickcheck")]
+struct ForkedProperty<A, F> {
+    /// The builder used for spawning children.
+    builder: Builder,
+    /// The ID of the fork point.
+    fork_id: String,
+    /// The name of the test.
+    test_name: String,
+    /// The property to check.
+    property: F,
+    /// The error encountered spawning a child, if any.
+    spawn_error: Rc<RefCell<Option<Error>>>,
+    /// The reason for the failure of the property along with the
+    /// minimal failing input, if any.
+    failure: Rc<RefCell<Option<(String, A)>>>,
+}
+
+#[cfg(feature = "quickcheck")]
+impl<A, F, T> ForkedProperty<A, F>
+where
+    A: Arbitrary + Debug + Serialize + DeserializeOwned,
+    F: Fn(A) -> T + 'static,
+    T: Testable,
+{
+    /// Run a single test case in the child.
+    fn run_case(&self, value: A) -> ExitCode {
+        match evaluate_quickcheck((self.property)(value)) {
+            Ok(true) => ExitCode::SUCCESS,
+            Ok(false) => {
+                eprintln!("test-fork: test case discarded");
+                ExitCode::from(EX_REJECTED)
+            }
+            Err(result) => panic!("property does not hold: {result:?}"),
+        }
+    }
+
+    /// Check the property for the given input.
+    ///
+    /// Returns `Ok(true)` if the property holds, `Ok(false)` if the
+    /// test case got discarded, and the reason otherwise.
+    fn check(&self, value: A) -> result::Result<bool, String> {
+        if self.builder.runs_in_process() {
+            // Test cases are run in-process and can report their result
+            // directly.
+            return evaluate_quickcheck((self.property)(value))
+                .map_err(|result| format!("{result:?}"))
+        }
+
+        let result = self.builder.try_fork_with_fixture(
+            &self.fork_id,
+            &self.test_name,
+            move || value,
+            |value| self.run_case(value),
+        );
+        match result {
+            Ok(_outcome) => Ok(true),
+            Err(ChildFailure::ExitCode { code }) if code == i32::from(EX_REJECTED) => Ok(false),
+            Err(ChildFailure::SpawnFailed(err)) => {
+                let reason = format!("failed to spawn child: {err}");
+                let _prev = self.spawn_error.borrow_mut().replace(err);
+                Err(reason)
+            }
+            Err(failure) => Err(failure.to_string()),
+        }
+    }
+
+    /// Shrink the given failing input to a minimal one for which the
+    /// property still fails.
+    fn shrink(&self, mut value: A, mut reason: String) -> (String, A) {
+        'outer: while self.spawn_error.borrow().is_none() {
+            for candidate in value.shrink() {
+                if let Err(candidate_reason) = self.check(candidate.clone()) {
+                    value = candidate;
+                    reason = candidate_reason;
+                    continue 'outer
+                }
+            }
+            break
+        }
+        (reason, value)
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+impl<A, F, T> Testable for ForkedProperty<A, F>
+where
+    A: Arbitrary + Debug + Serialize + DeserializeOwned,
+    F: Fn(A) -> T + 'static,
+    T: Testable,
+{
+    fn result(&self, g: &mut Gen) -> TestResult {
+        let value = A::arbitrary(g);
+        match self.check(value.clone()) {
+            Ok(true) => TestResult::passed(),
+            Ok(false) => TestResult::discard(),
+            Err(reason) => {
+                let failure = self.shrink(value, reason);
+                let _prev = self.failure.borrow_mut().replace(failure);
+                TestResult::failed()
+            }
+        }
+    }
+}
+
+
+/// Check whether the environment variable with the given name is to
+/// be inherited by the child even if the environment is otherwise
+/// cleared.
+fn always_inherited(name: &str) -> bool {
+    name.starts_with("TEST_FORK_")
+        || name == LLVM_PROFILE_FILE_ENV
+        || sanitizer::OPTIONS_ENVS.contains(&name)
+}
+
+/// Check whether the environment variable with the given name opts in
+/// to running tests in child processes.
+fn opted_in(name: &OsStr) -> bool {
+    env::var_os(name).is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Clear the environment of the child process spawned by `command`,
+/// except for the variables in `allow` and those set explicitly on
+/// `command` already.
+fn clear_env_except(command: &mut Command, allow: &[String]) {
+    let explicit = command
+        .get_envs()
+        .filter_map(|(name, value)| Some((name.to_owned(), value?.to_owned())))
+        .collect::<Vec<_>>();
+    let inherited = env::vars_os().filter(|(name, _value)| {
+        name.to_str()
+            .is_some_and(|name| always_inherited(name) || allow.iter().any(|allow| allow == name))
+    });
+
+    command.env_clear().envs(inherited).envs(explicit);
+    let () = dry_run::note_env_cleared();
+}
+
+/// Supervise `child` while exchanging data with it on a separate
+/// thread.
+///
+/// `exchange` accepts the child's connection and communicates with it,
+/// returning the data received, if any. Because the child is
+/// supervised in the meantime, a child hanging during the exchange is
+/// detected as such by the heartbeat `monitor`. A child exiting without
+/// ever connecting would leave the exchange waiting for a connection
+/// forever, though, so in that case `unblock` is invoked to connect in
+/// its stead.
+///
+/// An exchange that failed (e.g., because the child failed before
+/// completing it) is reported as `None`; the child's failure is
+/// reported as part of its outcome.
+fn supervise_exchange<X, U, R>(
+    child: ChildWrapper,
+    monitor: Option<Monitor>,
+    supervision: Supervision,
+    exchange: X,
+    unblock: U,
+) -> (result::Result<ForkOutcome, ChildFailure>, Option<R>)
+where
+    X: FnOnce() -> io::Result<R> + Send,
+    U: FnOnce(),
+    R: Send,
+{
+    thread::scope(|scope| {
+        let exchange = scope.spawn(exchange);
+        let outcome = supervise_child(child, monitor, supervision);
+        if !exchange.is_finished() {
+            let () = unblock();
+        }
+        let result = exchange
+            .join()
+            .unwrap_or_else(|payload| panic::resume_unwind(payload));
+        (outcome, result.ok())
+    })
+}
+
+/// Wake up a pending `accept` on the TCP listener bound to `addr`.
+fn unblock_tcp(addr: SocketAddr) {
+    // The connection is closed right away. An exchange accepting it
+    // will fail, which is what we want.
+    let _result = TcpStream::connect(addr);
+}
+
+/// Connect to the parent, as a child spawned for the fork point with
+/// the given ID.
+fn connect_to_parent(fork_id: &str) -> TcpStream {
+    let addr = env::var(fork_id)
+        .unwrap_or_else(|err| panic!("failed to retrieve {fork_id} environment variable: {err}"));
+    TcpStream::connect(addr).expect("failed to establish connection with parent")
+}
+
+/// Send `data` over `stream`, prefixed with its length.
+fn send_data(stream: &mut TcpStream, data: &[u8]) -> io::Result<()> {
+    let len = u64::try_from(data.len()).map_err(io::Error::other)?;
+    let () = stream.write_all(&len.to_le_bytes())?;
+    stream.write_all(data)
+}
+
+/// Receive data sent via [`send_data`] from `stream`, appending them
+/// to `data`.
+fn receive_data(stream: &mut TcpStream, data: &mut Vec<u8>) -> io::Result<()> {
+    let mut len = [0; 8];
+    let () = stream.read_exact(&mut len)?;
+    let len = u64::from_le_bytes(len);
+    let count = stream.take(len).read_to_end(data)?;
+    if u64::try_from(count).map_err(io::Error::other)? != len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof))
+    }
+    Ok(())
+}
+
+/// Report the result of a `proptest` run, panicking if the property
+/// does not hold.
+#[cfg(feature = "proptest")]
+#[expect(clippy::panic_in_result_fn)]
+fn report_proptest<V>(result: result::Result<(), TestError<V>>) -> Result<()>
+where
+    V: Debug,
+{
+    match result {
+        Ok(()) => Ok(()),
+        Err(TestError::Abort(reason)) => panic!("test-fork: property test aborted: {reason}"),
+        Err(TestError::Fail(reason, value)) => {
+            panic!("test-fork: property test failed: {reason}; minimal failing input: {value:?}")
+        }
+    }
+}
+
+/// Evaluate the result of a single `quickcheck` test case.
+///
+/// Returns `Ok(true)` if the test case passed, `Ok(false)` if it got
+/// discarded, and the failed result otherwise.
+#[cfg(feature = "quickcheck")]
+fn evaluate_quickcheck<T>(testable: T) -> result::Result<bool, TestResult>
+where
+    T: Testable,
+{
+    QuickCheck::new()
+        .tests(1)
+        .max_tests(1)
+        .quicktest(testable)
+        .map(|passed| passed > 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io;
+    use std::panic;
+    use std::process;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::thread::sleep;
+
+    use crate::backend::Launched;
+    use crate::fork::OCCURS_ENV;
+
+
+    /// Check that a child outliving the heartbeat timeout is not
+    /// considered hung as long as it sends heartbeats.
+    #[test]
+    fn heartbeat_keeps_child_alive() {
+        Builder::new()
+            .heartbeat(Duration::from_millis(200))
+            .fork(
+                fork_id!(),
+                fork_test_name!(heartbeat_keeps_child_alive),
+                || {
+                    for _ in 0..12 {
+                        let () = sleep(Duration::from_millis(50));
+                        let () = crate::heartbeat();
+                    }
+                },
+            )
+            .unwrap()
+    }
+
+    /// Check that a deadlocked child is detected as hung.
+    #[test]
+    #[should_panic(expected = "child appears hung")]
+    fn deadlocked_child_detected() {
+        use std::sync::mpsc;
+
+        Builder::new()
+            .heartbeat(Duration::from_millis(200))
+            .fork(
+                fork_id!(),
+                fork_test_name!(deadlocked_child_detected),
+                || {
+                    let (_sender, receiver) = mpsc::channel::<()>();
+                    // We keep the sender alive, so this call never returns.
+                    let _result = receiver.recv();
+                },
+            )
+            .unwrap()
+    }
+
+    /// Check that a custom supervisor gets to see the child process.
+    #[test]
+    fn custom_supervisor() {
+        let (success, stdout) = Builder::new()
+            .fork_supervised(
+                fork_id!(),
+                fork_test_name!(custom_supervisor),
+                |child| {
+                    let output = child.wait_with_output().unwrap();
+                    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+                    (output.status.success(), stdout)
+                },
+                || println!("hello from child"),
+            )
+            .unwrap();
+
+        assert!(success);
+        assert!(stdout.contains("hello from child"), "{stdout}");
+    }
+
+    /// Check that child failures are classified correctly.
+    #[test]
+    fn failure_classification() {
+        let failure = Builder::new()
+            .try_fork(fork_id!(), fork_test_name!(failure_classification), || {
+                panic!("classify me")
+            })
+            .unwrap_err();
+        assert!(
+            matches!(
+                &failure,
+                ChildFailure::Panicked { message, location: Some(location), .. }
+                  if message == "classify me" && location.starts_with("core/src/builder.rs:")
+            ),
+            "{failure:?}"
+        );
+
+        let failure = Builder::new()
+            .try_fork(fork_id!(), fork_test_name!(failure_classification), || {
+                process::exit(3)
+            })
+            .unwrap_err();
+        assert!(
+            matches!(failure, ChildFailure::ExitCode { code: 3 }),
+            "{failure:?}"
+        );
+
+        #[cfg(unix)]
+        {
+            let failure = Builder::new()
+                .try_fork(fork_id!(), fork_test_name!(failure_classification), || {
+                    process::abort()
+                })
+                .unwrap_err();
+            assert!(
+                matches!(failure, ChildFailure::Signaled { signal: 6 }),
+                "{failure:?}"
+            );
+        }
+    }
+
+    /// Check that only genuine panics of the child satisfy a test
+    /// expected to panic.
+    #[test]
+    fn should_panic_strictness() {
+        let result = panic::catch_unwind(|| {
+            Builder::new().should_panic(true).fork(
+                fork_id!(),
+                fork_test_name!(should_panic_strictness),
+                || panic!("expected"),
+            )
+        });
+        assert!(result.is_err());
+
+        let () = Builder::new()
+            .should_panic(true)
+            .fork(fork_id!(), fork_test_name!(should_panic_strictness), || {
+                process::exit(3)
+            })
+            .unwrap();
+
+        let () = Builder::new()
+            .should_panic(true)
+            .fork(fork_id!(), fork_test_name!(should_panic_strictness), || {
+                process::abort()
+            })
+            .unwrap();
+    }
+
+    /// Check that the child can be spawned from an explicitly provided
+    /// executable.
+    #[test]
+    fn explicit_executable() {
+        let exe = env::current_exe().unwrap();
+        let () = Builder::new()
+            .executable(exe)
+            .fork(fork_id!(), fork_test_name!(explicit_executable), || ())
+            .unwrap();
+
+        let failure = Builder::new()
+            .executable("/does/not/exist")
+            .try_fork(fork_id!(), fork_test_name!(explicit_executable), || ())
+            .unwrap_err();
+        assert!(
+            matches!(
+                failure,
+                ChildFailure::SpawnFailed(crate::Error::SpawnError(..))
+            ),
+            "{failure:?}"
+        );
+    }
+
+    /// Check that sanitizer reports are detected if requested.
+    #[test]
+    fn sanitizer_report_detected() {
+        let test = || eprintln!("==42==ERROR: AddressSanitizer: heap-use-after-free");
+
+        let _outcome = Builder::new()
+            .try_fork(fork_id!(), fork_test_name!(sanitizer_report_detected), test)
+            .unwrap();
+
+        let failure = Builder::new()
+            .sanitizer_check(true)
+            .try_fork(fork_id!(), fork_test_name!(sanitizer_report_detected), test)
+            .unwrap_err();
+        assert!(
+            matches!(
+                &failure,
+                ChildFailure::Sanitizer { report }
+                  if report == "==42==ERROR: AddressSanitizer: heap-use-after-free"
+            ),
+            "{failure:?}"
+        );
+    }
+
+    /// Check that processes leaked by the child are detected if
+    /// requested.
+    #[cfg(unix)]
+    #[test]
+    fn leaked_process_detected() {
+        use std::process::Command;
+
+        let test = || {
+            // Leak a process that would also keep our output pipes
+            // open.
+            #[expect(clippy::zombie_processes)]
+            let _child = Command::new("sleep").arg("30").spawn().unwrap();
+        };
+
+        let failure = Builder::new()
+            .leak_check(true)
+            .try_fork(fork_id!(), fork_test_name!(leaked_process_detected), test)
+            .unwrap_err();
+
+        if cfg!(target_os = "linux") {
+            assert!(
+                matches!(
+                    &failure,
+                    ChildFailure::Leaked { processes }
+                      if matches!(processes.as_slice(), [process] if process.ends_with("(sleep)"))
+                ),
+                "{failure:?}"
+            );
+        } else {
+            assert!(
+                matches!(failure, ChildFailure::Leaked { .. }),
+                "{failure:?}"
+            );
+        }
+    }
+
+    /// Check that the child's environment can be cleared.
+    #[test]
+    fn env_clearing() {
+        Builder::new()
+            .env_clear_except(["PATH"])
+            .fork(fork_id!(), fork_test_name!(env_clearing), || {
+                let () = env::vars_os().for_each(|(name, _value)| {
+                    let name = name.to_str().unwrap();
+                    assert!(name == "PATH" || always_inherited(name), "{name}");
+                });
+            })
+            .unwrap()
+    }
+
+    /// Check that the child's output can be captured.
+    #[test]
+    fn output_capturing() {
+        let outcome = Builder::new()
+            .capture_output(true)
+            .try_fork(fork_id!(), fork_test_name!(output_capturing), || {
+                println!("to stdout");
+                eprintln!("to stderr");
+            })
+            .unwrap();
+
+        assert!(outcome.status.success());
+        let stdout = String::from_utf8_lossy(&outcome.stdout);
+        assert!(stdout.contains("to stdout"), "{stdout}");
+        let stderr = String::from_utf8_lossy(&outcome.stderr);
+        assert!(stderr.contains("to stderr"), "{stderr}");
+    }
+
+    /// Check that the child's output can be compared against a golden
+    /// file.
+    #[test]
+    fn golden_output() {
+        let dir = env::temp_dir().join(format!("test-fork-golden-{}", process::id()));
+        let path = dir.join("golden.txt");
+        let test = || {
+            println!("line 1");
+            println!("line 2");
+        };
+
+        let () = fs::create_dir_all(&dir).unwrap();
+        let () = fs::write(&path, "line 1\nline 2\n").unwrap();
+        let _outcome = Builder::new()
+            .expected_output(&path)
+            .try_fork(fork_id!(), fork_test_name!(golden_output), test)
+            .unwrap();
+
+        let () = fs::write(&path, "line 1\nline 3\n").unwrap();
+        let failure = Builder::new()
+            .expected_output(&path)
+            .try_fork(fork_id!(), fork_test_name!(golden_output), test)
+            .unwrap_err();
+        let _result = fs::remove_dir_all(&dir);
+
+        assert!(
+            matches!(
+                &failure,
+                ChildFailure::OutputMismatch { difference, .. }
+                  if difference.contains("\"line 3\"") && difference.contains("\"line 2\"")
+            ),
+            "{failure:?}"
+        );
+    }
+
+    /// Check that data can be fed to the child's standard input.
+    #[test]
+    fn stdin_provisioning() {
+        let test = || {
+            let mut input = String::new();
+            let _count = io::stdin().read_to_string(&mut input).unwrap();
+            assert_eq!(input, "line 1\nline 2\n");
+        };
+
+        let () = Builder::new()
+            .stdin("line 1\nline 2\n")
+            .fork(fork_id!(), fork_test_name!(stdin_provisioning), test)
+            .unwrap();
+
+        let path = env::temp_dir().join(format!("test-fork-stdin-{}", process::id()));
+        let () = fs::write(&path, "line 1\nline 2\n").unwrap();
+        let result = Builder::new().stdin_file(&path).fork(
+            fork_id!(),
+            fork_test_name!(stdin_provisioning),
+            test,
+        );
+        let _result = fs::remove_file(&path);
+        let () = result.unwrap();
+    }
+
+    /// Check that hooks run in the parent around the child's lifetime.
+    #[test]
+    fn hooks() {
+        static AFTER_RAN: AtomicBool = AtomicBool::new(false);
+
+        fn before(builder: &mut Builder) {
+            // The hook must only ever run in the parent.
+            assert_eq!(env::var_os(OCCURS_ENV), None);
+            let _builder = builder.env("TEST_FORK_HOOKS_VAR", "set-by-hook");
+        }
+
+        fn after(result: &result::Result<ForkOutcome, ChildFailure>) {
+            assert!(result.is_ok());
+            let () = AFTER_RAN.store(true, Ordering::Relaxed);
+        }
+
+        let () = Builder::new()
+            .before(before)
+            .after(after)
+            .fork(fork_id!(), fork_test_name!(hooks), || {
+                assert_eq!(env::var("TEST_FORK_HOOKS_VAR").unwrap(), "set-by-hook");
+            })
+            .unwrap();
+        assert!(AFTER_RAN.load(Ordering::Relaxed));
+    }
+
+    /// Check that children are launched via a custom backend, if one
+    /// is configured.
+    #[test]
+    fn custom_backend() {
+        #[derive(Debug)]
+        struct Backend;
+
+        impl ProcessBackend for Backend {
+            fn launch(&self, command: &mut Command) -> io::Result<Launched> {
+                let _command = command.env("TEST_FORK_BACKEND_VAR", "set-by-backend");
+                CommandBackend.launch(command)
+            }
+        }
+
+        let outcome = Builder::new()
+            .backend(Backend)
+            .try_fork(fork_id!(), fork_test_name!(custom_backend), || {
+                assert_eq!(env::var("TEST_FORK_BACKEND_VAR").unwrap(), "set-by-backend");
+            })
+            .unwrap();
+        assert!(outcome.status.success());
+    }
+
+    /// Check that a child is run for each configuration of a matrix
+    /// and that failing configurations get reported.
+    #[test]
+    fn matrix_failure_reporting() {
+        let payload = panic::catch_unwind(|| {
+            Builder::new().fork_matrix(
+                fork_id!(),
+                fork_test_name!(matrix_failure_reporting),
+                &[&[("TEST_FORK_MODE", "a")], &[("TEST_FORK_MODE", "b")]],
+                || assert_eq!(env::var("TEST_FORK_MODE").unwrap(), "a"),
+            )
+        })
+        .unwrap_err();
+
+        let message = payload.downcast_ref::<String>().unwrap();
+        assert!(message.starts_with("1 of 2 children failed"), "{message}");
+        assert!(
+            message.contains("configuration `TEST_FORK_MODE=b`: child panicked"),
+            "{message}"
+        );
+    }
+
+    /// Check that instances of a child are run concurrently and that
+    /// failing instances get reported.
+    #[test]
+    fn instances_failure_reporting() {
+        let payload = panic::catch_unwind(|| {
+            Builder::new().fork_instances(
+                fork_id!(),
+                fork_test_name!(instances_failure_reporting),
+                3,
+                || assert_ne!(env::var("TEST_FORK_INSTANCE").unwrap(), "1"),
+            )
+        })
+        .unwrap_err();
+
+        let message = payload.downcast_ref::<String>().unwrap();
+        assert!(message.starts_with("1 of 3 children failed"), "{message}");
+        assert!(message.contains("instance 1: child panicked"), "{message}");
+    }
+
+    /// Check that all instances of a child are running at the same
+    /// time.
+    #[test]
+    fn instances_concurrency() {
+        let dir = env::temp_dir().join(format!("test-fork-instances-{}", process::id()));
+        let () = fs::create_dir_all(&dir).unwrap();
+        let result = Builder::new().env("TEST_FORK_DIR", &dir).fork_instances(
+            fork_id!(),
+            fork_test_name!(instances_concurrency),
+            3,
+            || {
+                let dir = PathBuf::from(env::var_os("TEST_FORK_DIR").unwrap());
+                let instance = env::var("TEST_FORK_INSTANCE").unwrap();
+                let () = fs::write(dir.join(instance), "").unwrap();
+                // Wait for all instances to have checked in, which
+                // only happens if they run concurrently.
+                while fs::read_dir(&dir).unwrap().count() < 3 {
+                    let () = sleep(Duration::from_millis(10));
+                }
+            },
+        );
+        let _result = fs::remove_dir_all(&dir);
+        let () = result.unwrap();
+    }
+
+    /// Check that transient failures to spawn the child are retried.
+    #[test]
+    fn spawn_retries() {
+        #[derive(Debug, Default)]
+        struct Flaky {
+            failures: AtomicUsize,
+        }
+
+        impl ProcessBackend for Flaky {
+            fn launch(&self, command: &mut Command) -> io::Result<Launched> {
+                if self.failures.fetch_add(1, Ordering::Relaxed) < 2 {
+                    return Err(io::Error::from(io::ErrorKind::WouldBlock))
+                }
+                CommandBackend.launch(command)
+            }
+        }
+
+        let () = Builder::new()
+            .backend(Flaky::default())
+            .spawn_retries(2, Duration::from_millis(1))
+            .fork(fork_id!(), fork_test_name!(spawn_retries), || ())
+            .unwrap();
+
+        let failure = Builder::new()
+            .backend(Flaky::default())
+            .spawn_retries(0, Duration::from_millis(1))
+            .try_fork(fork_id!(), fork_test_name!(spawn_retries), || ())
+            .unwrap_err();
+        assert!(
+            matches!(
+                failure,
+                ChildFailure::SpawnFailed(crate::Error::SpawnError(..))
+            ),
+            "{failure:?}"
+        );
+    }
+
+    /// Check that a fixture value is transmitted to the child.
+    #[cfg(feature = "serde")]
+    #[test]
+    fn fixture_transmission() {
+        let () = Builder::new()
+            .fork_with_fixture(
+                fork_id!(),
+                fork_test_name!(fixture_transmission),
+                || (process::id(), vec!["a".to_string(), "b".to_string()]),
+                |(pid, strings): (u32, Vec<String>)| {
+                    assert_ne!(pid, process::id());
+                    assert_eq!(strings, ["a", "b"]);
+                },
+            )
+            .unwrap();
+    }
+
+    /// Check that a shared buffer is mapped into the child and that
+    /// its changes are visible in the parent.
+    #[cfg(unix)]
+    #[test]
+    fn shared_buffer() {
+        let mut data = SharedBuffer::new(8 << 20).unwrap();
+        let () = data.fill(1);
+
+        let () = Builder::new()
+            .fork_shared(
+                fork_id!(),
+                fork_test_name!(shared_buffer),
+                |data| {
+                    assert_eq!(data.len(), 8 << 20);
+                    assert!(data.iter().all(|x| *x == 1));
+                    let () = data.fill(2);
+                },
+                &mut data,
+            )
+            .unwrap();
+
+        assert!(data.iter().all(|x| *x == 2));
+    }
+
+    /// Check that file descriptors are passed to the child.
+    #[cfg(unix)]
+    #[test]
+    fn fd_passing() {
+        use std::fs::File;
+        use std::io::Write as _;
+        use std::os::fd::AsFd as _;
+
+        let (mut reader, writer) = UnixStream::pair().unwrap();
+
+        let () = Builder::new()
+            .fork_with_fds(
+                fork_id!(),
+                fork_test_name!(fd_passing),
+                &[writer.as_fd()],
+                |fds| {
+                    let [fd] = <[OwnedFd; 1]>::try_from(fds).unwrap();
+                    let () = File::from(fd).write_all(b"hello").unwrap();
+                },
+            )
+            .unwrap();
+        drop(writer);
+
+        let mut data = Vec::new();
+        let _count = reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"hello");
+    }
+
+    /// Check that each `proptest` test case is run in a child process
+    /// of its own.
+    #[cfg(feature = "proptest")]
+    #[test]
+    fn proptest_cases() {
+        use proptest::prop_assert;
+        use proptest::prop_assert_eq;
+        use proptest::prop_assume;
+        use proptest::test_runner::Config;
+
+        static CASES: AtomicUsize = AtomicUsize::new(0);
+
+        let () = Builder::new()
+            .fork_proptest(
+                fork_id!(),
+                fork_test_name!(proptest_cases),
+                Config {
+                    cases: 8,
+                    failure_persistence: None,
+                    ..Config::default()
+                },
+                0u32..100,
+                |value| {
+                    prop_assume!(value % 2 == 0);
+                    prop_assert!(value < 100);
+                    // Each test case starts out with pristine state.
+                    prop_assert_eq!(CASES.fetch_add(1, Ordering::Relaxed), 0);
+                    Ok(())
+                },
+            )
+            .unwrap();
+    }
+
+    /// Check that a `proptest` test case crashing the child gets
+    /// shrunk to the minimal failing input.
+    #[cfg(feature = "proptest")]
+    #[test]
+    #[should_panic(expected = "minimal failing input: 42")]
+    fn proptest_crash_shrinking() {
+        use proptest::test_runner::Config;
+
+        let () = Builder::new()
+            .fork_proptest(
+                fork_id!(),
+                fork_test_name!(proptest_crash_shrinking),
+                Config {
+                    cases: 64,
+                    failure_persistence: None,
+                    ..Config::default()
+                },
+                0u32..1000,
+                |value| {
+                    if value >= 42 {
+                        process::abort()
+                    }
+                    Ok(())
+                },
+            )
+            .unwrap();
+    }
+
+    /// Check that each `quickcheck` test case is run in a child process
+    /// of its own.
+    #[cfg(feature = "quickcheck")]
+    #[test]
+    fn quickcheck_cases() {
+        use quickcheck::QuickCheck;
+        use quickcheck::TestResult;
+
+        static CASES: AtomicUsize = AtomicUsize::new(0);
+
+        let () = Builder::new()
+            .fork_quickcheck(
+                fork_id!(),
+                fork_test_name!(quickcheck_cases),
+                QuickCheck::new().tests(8),
+                |value: u32| {
+                    if value % 2 != 0 {
+                        return TestResult::discard()
+                    }
+                    // Each test case starts out with pristine state.
+                    TestResult::from_bool(CASES.fetch_add(1, Ordering::Relaxed) == 0)
+                },
+            )
+            .unwrap();
+    }
+
+    /// Check that a `quickcheck` test case crashing the child gets
+    /// shrunk to the minimal failing input.
+    #[cfg(feature = "quickcheck")]
+    #[test]
+    #[should_panic(expected = "minimal failing input: 42")]
+    fn quickcheck_crash_shrinking() {
+        use quickcheck::QuickCheck;
+
+        let () = Builder::new()
+            .fork_quickcheck(
+                fork_id!(),
+                fork_test_name!(quickcheck_crash_shrinking),
+                QuickCheck::new().tests(64),
+                |value: u16| {
+                    if value >= 42 {
+                        process::abort()
+                    }
+                },
+            )
+            .unwrap();
+    }
+
+    /// Check that the child's output is truncated once it exceeds the
+    /// configured limit.
+    #[test]
+    fn output_truncation() {
+        let outcome = Builder::new()
+            .output_limit(8)
+            .capture_output(true)
+            .try_fork(fork_id!(), fork_test_name!(output_truncation), || {
+                let () = io::stdout().write_all(&[b'x'; 1000]).unwrap();
+                let () = io::stderr().write_all(b"short\n").unwrap();
+            })
+            .unwrap();
+        // The test harness in the child emits some output of its own,
+        // so we can't tell the exact number of bytes omitted.
+        let stdout = String::from_utf8(outcome.stdout).unwrap();
+        let (retained, marker) = stdout.split_once("\n... output truncated (").unwrap();
+        assert_eq!(retained.len(), 8, "{stdout}");
+        assert!(marker.ends_with(" bytes omitted) ...\n"), "{stdout}");
+        assert_eq!(outcome.stderr, b"short\n");
+    }
+
+    /// Check that the child is asked to use colors as configured.
+    #[test]
+    fn color_configuration() {
+        let () = Builder::new()
+            .color(Color::Always)
+            .fork(fork_id!(), fork_test_name!(color_configuration), || {
+                assert_eq!(env::var("CLICOLOR_FORCE").unwrap(), "1");
+                assert_eq!(env::var_os("NO_COLOR"), None);
+            })
+            .unwrap();
+
+        let outcome = Builder::new()
+            .color(Color::Never)
+            .capture_output(true)
+            .try_fork(fork_id!(), fork_test_name!(color_configuration), || {
+                assert_eq!(env::var("NO_COLOR").unwrap(), "1");
+                assert_eq!(env::var_os("CLICOLOR_FORCE"), None);
+                let () = io::stderr().write_all(b"\x1b[31mred\x1b[0m\n").unwrap();
+            })
+            .unwrap();
+        // Captured output is left untouched.
+        assert_eq!(outcome.stderr, b"\x1b[31mred\x1b[0m\n");
+    }
+
+    /// Check that the child's standard output can be connected to a
+    /// pseudo-terminal.
+    #[cfg(all(unix, feature = "pty"))]
+    #[test]
+    fn pty() {
+        let outcome = Builder::new()
+            .pty(true)
+            .capture_output(true)
+            .try_fork(fork_id!(), fork_test_name!(pty), || {
+                // SAFETY: `isatty` is always safe to call.
+                assert_eq!(unsafe { libc::isatty(1) }, 1);
+                // SAFETY: `isatty` is always safe to call.
+                assert_eq!(unsafe { libc::isatty(2) }, 0);
+                println!("terminal");
+            })
+            .unwrap();
+
+        let stdout = String::from_utf8(outcome.stdout).unwrap();
+        assert!(stdout.contains("terminal\n"), "{stdout}");
+        assert!(!stdout.contains('\r'), "{stdout}");
+    }
+
+    /// Check that a test can be run in the current process instead of
+    /// a child process.
+    #[test]
+    fn in_process() {
+        static STATE: AtomicUsize = AtomicUsize::new(0);
+
+        let () = Builder::new()
+            .in_process(true)
+            .fork(fork_id!(), fork_test_name!(in_process), || {
+                assert_eq!(STATE.swap(1, Ordering::Relaxed), 0);
+            })
+            .unwrap();
+        assert_eq!(STATE.load(Ordering::Relaxed), 1);
+
+        let () = Builder::new()
+            .in_process(false)
+            .fork(fork_id!(), fork_test_name!(in_process), || {
+                // The child ran the first part of the test in-process
+                // as well.
+                assert_eq!(STATE.swap(2, Ordering::Relaxed), 1);
+            })
+            .unwrap();
+        assert_eq!(STATE.load(Ordering::Relaxed), 1);
+    }
+
+    /// Check that a test is run in a child process only if the
+    /// configured environment variable is set.
+    #[test]
+    fn opt_in_env() {
+        static STATE: AtomicUsize = AtomicUsize::new(0);
+
+        let () = Builder::new()
+            .opt_in_env("TEST_FORK_UNIT_TEST_UNSET")
+            .fork(fork_id!(), fork_test_name!(opt_in_env), || {
+                assert_eq!(STATE.swap(1, Ordering::Relaxed), 0);
+            })
+            .unwrap();
+        assert_eq!(STATE.load(Ordering::Relaxed), 1);
+
+        // `PATH` is set and forwarded to the child even if its
+        // environment is cleared.
+        let () = Builder::new()
+            .opt_in_env("PATH")
+            .env_clear_except([] as [&str; 0])
+            .fork(fork_id!(), fork_test_name!(opt_in_env), || {
+                assert_eq!(STATE.swap(2, Ordering::Relaxed), 1);
+                assert!(env::var_os("PATH").is_some());
+            })
+            .unwrap();
+        assert_eq!(STATE.load(Ordering::Relaxed), 1);
+    }
+
+    /// Check that a child can be created via a plain `fork`.
+    #[cfg(all(unix, feature = "raw-fork"))]
+    #[test]
+    fn raw_fork() {
+        use std::io::Write as _;
+
+        static STATE: AtomicUsize = AtomicUsize::new(0);
+
+        let () = STATE.store(1, Ordering::Relaxed);
+        let outcome = Builder::new()
+            .raw_fork(true)
+            .capture_output(true)
+            .try_fork(fork_id!(), fork_test_name!(raw_fork), || {
+                assert_eq!(STATE.swap(2, Ordering::Relaxed), 1);
+                let _result = writeln!(io::stdout(), "raw child");
+            })
+            .unwrap();
+        assert_eq!(STATE.load(Ordering::Relaxed), 1);
+        assert_eq!(outcome.stdout, b"raw child\n");
+
+        let failure = Builder::new()
+            .raw_fork(true)
+            .try_fork(fork_id!(), fork_test_name!(raw_fork), || {
+                panic!("raw child panicked")
+            })
+            .unwrap_err();
+        assert!(
+            matches!(&failure, ChildFailure::Panicked { message, .. } if message == "raw child panicked"),
+            "{failure:?}"
+        );
+    }
+
+    /// Check that a child that stops sending heartbeats is detected as
+    /// hung.
+    #[cfg(unix)]
+    #[test]
+    #[should_panic(expected = "child appears hung")]
+    fn hung_child_detected() {
+        use std::process::Command;
+
+        Builder::new()
+            .heartbeat(Duration::from_millis(200))
+            .fork(fork_id!(), fork_test_name!(hung_child_detected), || {
+                let pid = process::id().to_string();
+                // Suspend ourselves, which stops all threads, including
+                // the one sending heartbeats.
+                let status = Command::new("kill").args(["-STOP", &pid]).status().unwrap();
+                assert!(status.success(), "{status}");
+                // Signal delivery is asynchronous. Make sure that we
+                // don't just exit before the stop took effect.
+                let () = sleep(Duration::from_secs(10));
+            })
+            .unwrap()
+    }
+
+    /// Check that a child hanging while exchanging data with the
+    /// parent is detected as hung.
+    #[cfg(unix)]
+    #[test]
+    fn hung_child_detected_in_out() {
+        use std::process::Command;
+
+        let mut data = b"input".to_vec();
+        let failure = Builder::new()
+            .heartbeat(Duration::from_millis(200))
+            .try_fork_in_out(
+                fork_id!(),
+                fork_test_name!(hung_child_detected_in_out),
+                |_data| {
+                    let pid = process::id().to_string();
+                    let status = Command::new("kill").args(["-STOP", &pid]).status().unwrap();
+                    assert!(status.success(), "{status}");
+                    let () = sleep(Duration::from_secs(10));
+                },
+                &mut data,
+            )
+            .unwrap_err();
+        assert!(
+            matches!(failure, ChildFailure::TimedOut { .. }),
+            "{failure:?}"
+        );
+        assert_eq!(data, b"input");
+    }
+
+    /// Check that we don't wait forever for a child that exits without
+    /// ever connecting to exchange data.
+    #[cfg(unix)]
+    #[test]
+    fn unconnected_child_in_out() {
+        let mut data = b"input".to_vec();
+        let outcome = Builder::new()
+            .executable("/bin/true")
+            .try_fork_in_out(
+                fork_id!(),
+                fork_test_name!(unconnected_child_in_out),
+                Vec::clear,
+                &mut data,
+            )
+            .unwrap();
+        assert!(outcome.status.success());
+        assert_eq!(data, b"input");
+    }
+}
@@ -0,0 +1,260 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Dropping of Linux capabilities in the child.
+//!
+//! The parent conveys the capabilities to drop to the child, which
+//! drops them right after startup, before running the test. Each
+//! capability is removed from the bounding set (so that it cannot be
+//! regained by executing a program) as well as from the ambient,
+//! inheritable, permitted, and effective sets. If the child lacks the
+//! privileges for changing its bounding set, it sets the
+//! `no_new_privs` flag instead, which equally prevents executed
+//! programs from gaining capabilities.
+
+use std::env;
+use std::ffi::c_int;
+use std::fs;
+use std::io;
+use std::process::Command;
+
+use crate::sys::check;
+
+
+/// The environment variable used for conveying the capabilities to drop
+/// to the child.
+const DROP_CAPS_ENV: &str = "TEST_FORK_DROP_CAPS";
+/// The special name referring to all capabilities.
+const ALL: &str = "all";
+/// The capability ABI version we use (`_LINUX_CAPABILITY_VERSION_3`).
+const CAPABILITY_VERSION: u32 = 0x2008_0522;
+/// The names of all capabilities, indexed by their numeric value.
+const NAMES: [&str; 41] = [
+    "chown",
+    "dac_override",
+    "dac_read_search",
+    "fowner",
+    "fsetid",
+    "kill",
+    "setgid",
+    "setuid",
+    "setpcap",
+    "linux_immutable",
+    "net_bind_service",
+    "net_broadcast",
+    "net_admin",
+    "net_raw",
+    "ipc_lock",
+    "ipc_owner",
+    "sys_module",
+    "sys_rawio",
+    "sys_chroot",
+    "sys_ptrace",
+    "sys_pacct",
+    "sys_admin",
+    "sys_boot",
+    "sys_nice",
+    "sys_resource",
+    "sys_time",
+    "sys_tty_config",
+    "mknod",
+    "lease",
+    "audit_write",
+    "audit_control",
+    "setfcap",
+    "mac_override",
+    "mac_admin",
+    "syslog",
+    "wake_alarm",
+    "block_suspend",
+    "audit_read",
+    "perfmon",
+    "bpf",
+    "checkpoint_restore",
+];
+
+
+/// The header used by the `capget` & `capset` system calls.
+#[repr(C)]
+struct CapHeader {
+    version: u32,
+    pid: c_int,
+}
+
+/// The data used by the `capget` & `capset` system calls, with one
+/// instance covering 32 capabilities.
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct CapData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+
+/// Look up the numeric value of the capability with the given name.
+///
+/// The name is matched case insensitively and may carry a `CAP_`
+/// prefix.
+fn lookup(name: &str) -> Option<u32> {
+    let name = name.to_ascii_lowercase();
+    let name = name.strip_prefix("cap_").unwrap_or(&name);
+    let cap = NAMES.iter().position(|candidate| *candidate == name)?;
+    u32::try_from(cap).ok()
+}
+
+/// Retrieve the highest capability supported by the running kernel.
+fn last_cap() -> u32 {
+    fs::read_to_string("/proc/sys/kernel/cap_last_cap")
+        .ok()
+        .and_then(|last| last.trim().parse().ok())
+        .unwrap_or_else(|| u32::try_from(NAMES.len() - 1).unwrap_or_default())
+}
+
+/// Configure the child process spawned by `command` to drop the
+/// capabilities with the given names.
+pub(crate) fn configure(command: &mut Command, names: &[String]) {
+    let value = if names.iter().any(|name| name.eq_ignore_ascii_case(ALL)) {
+        ALL.to_string()
+    } else {
+        names
+            .iter()
+            .map(|name| {
+                lookup(name)
+                    .unwrap_or_else(|| panic!("test-fork: unknown capability `{name}`"))
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    command.env(DROP_CAPS_ENV, value);
+}
+
+/// Drop the capabilities `caps` from the current process.
+fn drop_caps(caps: &[u32]) -> io::Result<()> {
+    let mut no_new_privs = false;
+    for cap in caps {
+        let cap = libc::c_ulong::from(*cap);
+        // SAFETY: `prctl` with these arguments has no memory safety
+        //         implications.
+        let bounded = check(unsafe { libc::prctl(libc::PR_CAPBSET_READ, cap) })?;
+        if bounded == 1 {
+            // SAFETY: See above.
+            match check(unsafe { libc::prctl(libc::PR_CAPBSET_DROP, cap) }) {
+                Ok(_) => (),
+                Err(err) if err.raw_os_error() == Some(libc::EPERM) => no_new_privs = true,
+                Err(err) => return Err(err),
+            }
+        }
+
+        // SAFETY: See above.
+        let rc =
+            unsafe { libc::prctl(libc::PR_CAP_AMBIENT, libc::PR_CAP_AMBIENT_LOWER, cap, 0, 0) };
+        match check(rc) {
+            Ok(_) => (),
+            // Ambient capabilities are not supported by the kernel.
+            Err(err) if err.raw_os_error() == Some(libc::EINVAL) => (),
+            Err(err) => return Err(err),
+        }
+    }
+
+    if no_new_privs {
+        // SAFETY: See above.
+        let _rc = check(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) })?;
+    }
+
+    let mut header = CapHeader {
+        version: CAPABILITY_VERSION,
+        pid: 0,
+    };
+    let mut data = [CapData::default(); 2];
+    // SAFETY: `header` and `data` are valid for the duration of the
+    //         call and `data` has room for two instances, as required
+    //         by version 3 of the ABI.
+    let rc = unsafe { libc::syscall(libc::SYS_capget, &mut header, data.as_mut_ptr()) };
+    let _rc = check(c_int::try_from(rc).unwrap_or(-1))?;
+
+    for cap in caps {
+        let (index, bit) = (cap / 32, 1 << (cap % 32));
+        if let Some(data) = data.get_mut(usize::try_from(index).unwrap_or(usize::MAX)) {
+            data.effective &= !bit;
+            data.permitted &= !bit;
+            data.inheritable &= !bit;
+        }
+    }
+
+    // SAFETY: See above.
+    let rc = unsafe { libc::syscall(libc::SYS_capset, &mut header, data.as_ptr()) };
+    let _rc = check(c_int::try_from(rc).unwrap_or(-1))?;
+    Ok(())
+}
+
+/// Drop the capabilities the parent requested to drop from the current
+/// process, if any.
+pub(crate) fn apply() {
+    let Ok(value) = env::var(DROP_CAPS_ENV) else {
+        return
+    };
+
+    let caps = if value == ALL {
+        (0..=last_cap()).collect::<Vec<_>>()
+    } else {
+        value
+            .split(',')
+            .filter_map(|cap| cap.parse().ok())
+            .collect::<Vec<_>>()
+    };
+
+    if let Err(err) = drop_caps(&caps) {
+        panic!("test-fork: failed to drop capabilities: {err}")
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::Builder;
+
+
+    /// Check that capability names are looked up correctly.
+    #[test]
+    fn capability_lookup() {
+        assert_eq!(lookup("chown"), Some(0));
+        assert_eq!(lookup("CAP_NET_ADMIN"), Some(12));
+        assert_eq!(lookup("cap_sys_admin"), Some(21));
+        assert_eq!(lookup("checkpoint_restore"), Some(40));
+        assert_eq!(lookup("net_admin2"), None);
+        assert_eq!(lookup(""), None);
+    }
+
+    /// Check that capabilities are dropped in the child.
+    #[test]
+    fn capability_dropping() {
+        /// Check whether the current process has the capability `cap`
+        /// in its bounding set or would be able to regain it.
+        fn has_cap(cap: u32) -> bool {
+            // SAFETY: `prctl` with these arguments has no memory
+            //         safety implications.
+            let bounded = unsafe { libc::prctl(libc::PR_CAPBSET_READ, libc::c_ulong::from(cap)) };
+            // SAFETY: See above.
+            let no_new_privs = unsafe { libc::prctl(libc::PR_GET_NO_NEW_PRIVS, 0, 0, 0, 0) };
+            bounded == 1 && no_new_privs != 1
+        }
+
+        let () = Builder::new()
+            .drop_capability("CAP_NET_ADMIN")
+            .fork(fork_id!(), fork_test_name!(capability_dropping), || {
+                assert!(!has_cap(12));
+            })
+            .unwrap();
+
+        let () = Builder::new()
+            .drop_capability("all")
+            .fork(fork_id!(), fork_test_name!(capability_dropping), || {
+                assert!((0..=last_cap()).all(|cap| !has_cap(cap)));
+            })
+            .unwrap();
+    }
+}
@@ -0,0 +1,708 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::mem;
+use std::process::Child;
+use std::process::ExitStatus;
+use std::process::Output;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::backend::ChildProcess;
+use crate::backend::Launched;
+use crate::events;
+use crate::events::Value;
+use crate::interrupt;
+use crate::serial::SerialLock;
+use crate::summary;
+
+
+/// The interval in which we check up on a child that we can't just
+/// block waiting for.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+
+/// Children that got detached and have not been reaped yet.
+static DETACHED: Mutex<Vec<ChildWrapper>> = Mutex::new(Vec::new());
+
+
+/// The data read from one of a child's output pipes.
+#[derive(Debug, Default)]
+struct Buffer {
+    /// The data read so far and not yet retrieved.
+    data: Vec<u8>,
+    /// The total number of bytes retained so far, retrieved or not.
+    retained: usize,
+    /// The number of bytes dropped for exceeding the limit.
+    omitted: usize,
+    /// The maximum number of bytes to retain, if any.
+    limit: Option<usize>,
+    /// The file to additionally write all data to, if any.
+    tee: Option<File>,
+}
+
+impl Buffer {
+    /// Append `bytes`, dropping everything beyond the limit.
+    fn push(&mut self, bytes: &[u8]) {
+        if let Some(tee) = &mut self.tee {
+            if tee.write_all(bytes).is_err() {
+                self.tee = None;
+            }
+        }
+
+        let room = self
+            .limit
+            .map_or(bytes.len(), |limit| limit.saturating_sub(self.retained));
+        let (kept, dropped) = bytes.split_at(room.min(bytes.len()));
+        let () = self.data.extend_from_slice(kept);
+        self.retained += kept.len();
+        self.omitted += dropped.len();
+    }
+
+    /// Limit the amount of data retained to `limit` bytes, dropping
+    /// what was read beyond it already.
+    fn limit(&mut self, limit: usize) {
+        self.limit = Some(limit);
+        // Only data not yet retrieved can still be dropped.
+        let excess = self.retained.saturating_sub(limit).min(self.data.len());
+        let () = self.data.truncate(self.data.len() - excess);
+        self.retained -= excess;
+        self.omitted += excess;
+    }
+
+    /// Drop all data not yet retrieved as well as everything read from
+    /// now on.
+    fn discard(&mut self) {
+        let () = self.limit(0);
+        self.tee = None;
+    }
+
+    /// Additionally write all data to `file`, starting with what was
+    /// read and not yet retrieved so far.
+    fn tee(&mut self, mut file: File) {
+        if file.write_all(&self.data).is_ok() {
+            self.tee = Some(file);
+        }
+    }
+}
+
+/// A collector of the data written to one of a child's output pipes.
+///
+/// Data are read on a background thread, so that the child never
+/// blocks on a full pipe, regardless of what the supervisor is doing.
+#[derive(Debug)]
+struct Collector {
+    /// The data read so far.
+    buffer: Arc<Mutex<Buffer>>,
+    /// The thread reading the pipe.
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Collector {
+    fn new<R>(pipe: Option<R>) -> Self
+    where
+        R: Read + Send + 'static,
+    {
+        let buffer = Arc::new(Mutex::new(Buffer::default()));
+        let thread = pipe.map(|mut pipe| {
+            let buffer = Arc::clone(&buffer);
+            thread::spawn(move || {
+                let mut buf = [0; 4096];
+                loop {
+                    match pipe.read(&mut buf) {
+                        Ok(0) => break,
+                        Ok(n) => {
+                            let mut buffer = buffer.lock().unwrap_or_else(PoisonError::into_inner);
+                            let () = buffer.push(buf.get(..n).unwrap_or_default());
+                        }
+                        Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(_) => break,
+                    }
+                }
+            })
+        });
+
+        Self { buffer, thread }
+    }
+
+    /// Limit the amount of data retained to `limit` bytes.
+    fn limit(&self, limit: usize) {
+        let mut buffer = self.buffer.lock().unwrap_or_else(PoisonError::into_inner);
+        let () = buffer.limit(limit);
+    }
+
+    /// Drop all data read so far as well as everything read from now
+    /// on.
+    fn discard(&self) {
+        let mut buffer = self.buffer.lock().unwrap_or_else(PoisonError::into_inner);
+        let () = buffer.discard();
+    }
+
+    /// Additionally write all data to `file`.
+    fn tee(&self, file: File) {
+        let mut buffer = self.buffer.lock().unwrap_or_else(PoisonError::into_inner);
+        let () = buffer.tee(file);
+    }
+
+    /// Retrieve all data read since the last call.
+    fn take(&self) -> Vec<u8> {
+        let mut buffer = self.buffer.lock().unwrap_or_else(PoisonError::into_inner);
+        mem::take(&mut buffer.data)
+    }
+
+    /// Wait for the pipe to be closed and retrieve all data not yet
+    /// retrieved, followed by a marker if data got dropped for
+    /// exceeding the limit.
+    fn finish(&mut self) -> Vec<u8> {
+        if let Some(thread) = self.thread.take() {
+            let _result = thread.join();
+        }
+
+        let mut buffer = self.buffer.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut data = mem::take(&mut buffer.data);
+        if buffer.omitted > 0 {
+            if !data.is_empty() && !data.ends_with(b"\n") {
+                let () = data.push(b'\n');
+            }
+            let marker = format!(
+                "... output truncated ({} bytes omitted) ...\n",
+                buffer.omitted
+            );
+            let () = data.extend_from_slice(marker.as_bytes());
+        }
+        data
+    }
+}
+
+
+/// A wrapper around a child process spawned by `test-fork`.
+///
+/// The wrapper continuously collects the child's output in the
+/// background and provides a few conveniences not offered by
+/// [`Child`], such as waiting with a timeout and killing the child's
+/// entire process tree.
+///
+/// If the child is still running when the wrapper is dropped, its
+/// process group gets killed. Use [`ChildWrapper::detach`] to leave it
+/// running instead.
+pub struct ChildWrapper {
+    /// The wrapped child process.
+    process: Box<dyn ChildProcess>,
+    /// The writing end of the child's standard input, if piped.
+    stdin: Option<Box<dyn Write + Send>>,
+    /// The collector of the child's standard output.
+    stdout: Collector,
+    /// The collector of the child's standard error.
+    stderr: Collector,
+    /// The child's exit status, once it exited and was reaped.
+    status: Option<ExitStatus>,
+    /// The name of the test the child runs.
+    test_name: String,
+    /// The ID of the fork point the child was spawned from.
+    fork_id: String,
+    /// The time the child was spawned.
+    start: Instant,
+    /// The serialization locks held for the child.
+    locks: Vec<SerialLock>,
+}
+
+impl ChildWrapper {
+    pub(crate) fn new(launched: Launched, test_name: &str, fork_id: &str) -> Self {
+        let Launched {
+            process,
+            stdin,
+            stdout,
+            stderr,
+        } = launched;
+
+        let slf = Self {
+            process,
+            stdin,
+            stdout: Collector::new(stdout),
+            stderr: Collector::new(stderr),
+            status: None,
+            test_name: test_name.to_string(),
+            fork_id: fork_id.to_string(),
+            start: Instant::now(),
+            locks: Vec::new(),
+        };
+        let () = interrupt::track(slf.id());
+        let () = summary::spawned();
+        let () = slf.emit("spawn", &[]);
+        slf
+    }
+
+    /// Write `data` to the child's standard input (if it is piped) on a
+    /// background thread, closing it afterwards.
+    pub(crate) fn write_stdin(&mut self, data: Vec<u8>) {
+        if let Some(mut stdin) = self.stdin.take() {
+            let _handle = thread::spawn(move || {
+                // The child may very well exit without consuming all
+                // its input, in which case we get an error that is of
+                // no concern to us.
+                let _result = stdin.write_all(&data);
+            });
+        }
+    }
+
+    /// Retrieve the name of the test the child runs.
+    pub(crate) fn test_name(&self) -> &str {
+        &self.test_name
+    }
+
+    /// Retrieve the time the child was spawned.
+    pub(crate) fn start(&self) -> Instant {
+        self.start
+    }
+
+    /// Hold on to the given serialization locks until the child got
+    /// dropped.
+    pub(crate) fn hold_locks(&mut self, locks: Vec<SerialLock>) {
+        self.locks = locks;
+    }
+
+    /// Emit a lifecycle event for the child.
+    fn emit(&self, event: &str, fields: &[(&str, Value<'_>)]) {
+        let common = [
+            ("test", Value::from(self.test_name.as_str())),
+            ("fork_id", Value::from(self.fork_id.as_str())),
+            ("pid", Value::from(Some(self.id()))),
+        ];
+        let fields = common.iter().chain(fields).cloned().collect::<Vec<_>>();
+        let () = events::emit(event, &fields);
+    }
+
+    /// Record the child's exit status.
+    fn set_status(&mut self, status: ExitStatus) {
+        if self.status.is_none() {
+            self.status = Some(status);
+            let () = interrupt::untrack(self.id());
+
+            #[cfg(unix)]
+            let signal = {
+                use std::os::unix::process::ExitStatusExt as _;
+                status.signal()
+            };
+            #[cfg(not(unix))]
+            let signal = None::<i32>;
+
+            let duration_ms = i64::try_from(self.start.elapsed().as_millis()).ok();
+            let () = self.emit(
+                "exit",
+                &[
+                    ("success", Value::from(status.success())),
+                    ("code", Value::from(status.code())),
+                    ("signal", Value::from(signal)),
+                    ("duration_ms", Value::from(duration_ms)),
+                ],
+            );
+        }
+    }
+
+    /// Report that the child was considered hung after `elapsed`
+    /// passed without any sign of life.
+    pub(crate) fn report_timeout(&self, elapsed: Duration) {
+        let elapsed_ms = i64::try_from(elapsed.as_millis()).ok();
+        let () = self.emit("timeout", &[("elapsed_ms", Value::from(elapsed_ms))]);
+    }
+
+    /// Retrieve the OS-assigned process identifier of the child.
+    #[inline]
+    pub fn id(&self) -> u32 {
+        self.process.id()
+    }
+
+    /// Retrieve a reference to the wrapped [`Child`].
+    ///
+    /// Note that the child's standard output and error pipes are owned
+    /// by the wrapper and not available through the returned object.
+    /// For a child created via a plain `fork` (see
+    /// [`Builder::raw_fork`][crate::Builder::raw_fork]) or by a
+    /// [`ProcessBackend`][crate::ProcessBackend] not based on
+    /// [`Child`], there is no such object and `None` is returned.
+    #[inline]
+    pub fn inner(&self) -> Option<&Child> {
+        self.process.as_child()
+    }
+
+    /// Check whether the child has exited, without blocking.
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        if self.status.is_none() {
+            if let Some(status) = self.process.try_wait()? {
+                let () = self.set_status(status);
+            }
+        }
+        Ok(self.status)
+    }
+
+    /// Wait for the child to exit.
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        if let Some(status) = self.status {
+            return Ok(status)
+        }
+
+        let status = self.process.wait()?;
+        let () = self.set_status(status);
+        Ok(status)
+    }
+
+    /// Wait for the child to exit for at most `timeout`.
+    ///
+    /// Returns `None` if the child is still running after `timeout`
+    /// has passed.
+    pub fn wait_timeout(&mut self, timeout: Duration) -> io::Result<Option<ExitStatus>> {
+        let start = Instant::now();
+        loop {
+            if let Some(status) = self.try_wait()? {
+                return Ok(Some(status))
+            }
+
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Ok(None)
+            }
+            let () = thread::sleep(POLL_INTERVAL.min(timeout.saturating_sub(elapsed)));
+        }
+    }
+
+    /// Kill the child process.
+    ///
+    /// Processes spawned by the child are left running. Use
+    /// [`ChildWrapper::kill_group`] to kill those as well.
+    pub fn kill(&mut self) -> io::Result<()> {
+        if self.status.is_some() {
+            return Ok(())
+        }
+        self.process.kill()
+    }
+
+    /// Kill the child process along with all processes it spawned.
+    ///
+    /// On Unix systems the child runs in a process group of its own
+    /// and the entire group is sent `SIGKILL`. Processes that moved
+    /// into a different process group (or session) are not affected.
+    /// On Windows, the child's process tree is terminated.
+    pub fn kill_group(&mut self) -> io::Result<()> {
+        if self.status.is_some() {
+            return Ok(())
+        }
+        log!(
+            "killing process group of child {} of `{}`",
+            self.id(),
+            self.test_name
+        );
+        self.process.kill_group()
+    }
+
+    /// Limit the amount of output retained for each of the child's
+    /// standard output and standard error to `limit` bytes.
+    ///
+    /// Output beyond the limit is dropped and a marker stating the
+    /// number of bytes omitted is appended to the output retrieved via
+    /// [`ChildWrapper::wait_with_output`].
+    pub fn limit_output(&mut self, limit: usize) {
+        let () = self.stdout.limit(limit);
+        let () = self.stderr.limit(limit);
+    }
+
+    /// Additionally write everything the child writes to its standard
+    /// output and standard error to the given files, regardless of any
+    /// output limit.
+    pub(crate) fn tee_output(&mut self, stdout: File, stderr: File) {
+        let () = self.stdout.tee(stdout);
+        let () = self.stderr.tee(stderr);
+    }
+
+    /// Retrieve the data the child wrote to its standard output since
+    /// the last call, without blocking.
+    pub fn read_stdout(&mut self) -> Vec<u8> {
+        self.stdout.take()
+    }
+
+    /// Retrieve the data the child wrote to its standard error since
+    /// the last call, without blocking.
+    pub fn read_stderr(&mut self) -> Vec<u8> {
+        self.stderr.take()
+    }
+
+    /// Leave the child running, even beyond the lifetime of the
+    /// wrapper.
+    ///
+    /// This is dangerous: a detached child may outlive the parent,
+    /// interfering with other tests or lingering indefinitely. It is
+    /// only meant for tests verifying daemonization or the handling of
+    /// orphaned processes. Detached children are not reaped
+    /// automatically; use [`reap_detached`] to do so. Their output is
+    /// discarded and they are not terminated when the parent gets
+    /// interrupted. Serialization locks and port reservations held for
+    /// them (see [`Builder::serial`][crate::Builder::serial] and
+    /// [`Builder::reserve_ports`][crate::Builder::reserve_ports]) are
+    /// released right away.
+    pub fn detach(mut self) {
+        let () = interrupt::untrack(self.id());
+        // Output is still being read, so that the child never blocks on
+        // a full pipe, but no longer retained.
+        let () = self.stdout.discard();
+        let () = self.stderr.discard();
+        let () = self.locks.clear();
+        let mut detached = DETACHED.lock().unwrap_or_else(PoisonError::into_inner);
+        let () = detached.push(self);
+    }
+
+    /// Wait for the child to exit and retrieve its exit status along
+    /// with all output not yet retrieved via
+    /// [`ChildWrapper::read_stdout`] or [`ChildWrapper::read_stderr`].
+    pub fn wait_with_output(mut self) -> io::Result<Output> {
+        let status = self.wait()?;
+        let output = Output {
+            status,
+            stdout: self.stdout.finish(),
+            stderr: self.stderr.finish(),
+        };
+        Ok(output)
+    }
+}
+
+impl Debug for ChildWrapper {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("ChildWrapper")
+            .field("process", &self.process)
+            .field("stdout", &self.stdout)
+            .field("stderr", &self.stderr)
+            .field("status", &self.status)
+            .field("test_name", &self.test_name)
+            .field("fork_id", &self.fork_id)
+            .field("start", &self.start)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for ChildWrapper {
+    fn drop(&mut self) {
+        if let Ok(None) = self.try_wait() {
+            let _result = self.kill_group();
+            let _status = self.wait();
+        }
+    }
+}
+
+
+/// Reap children previously detached via [`ChildWrapper::detach`] that
+/// have exited, without blocking.
+///
+/// The process identifiers and exit statuses of all children reaped
+/// are returned. Children still running are left alone.
+pub fn reap_detached() -> io::Result<Vec<(u32, ExitStatus)>> {
+    let mut detached = DETACHED.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut reaped = Vec::new();
+    let mut idx = 0;
+    while let Some(child) = detached.get_mut(idx) {
+        match child.try_wait()? {
+            Some(status) => {
+                let () = reaped.push((child.id(), status));
+                let _child = detached.swap_remove(idx);
+            }
+            None => idx += 1,
+        }
+    }
+    Ok(reaped)
+}
+
+
+#[cfg(unix)]
+pub(crate) fn kill_tree(pid: u32) -> io::Result<()> {
+    let pid = libc::pid_t::try_from(pid).map_err(io::Error::other)?;
+    // The child is the leader of its own process group, which shares
+    // its ID. A negative PID instructs `kill` to signal the group.
+    // SAFETY: `kill` is always safe to call.
+    let rc = unsafe { libc::kill(-pid, libc::SIGKILL) };
+    if rc != 0 {
+        let err = io::Error::last_os_error();
+        // The group may be gone already.
+        if err.raw_os_error() != Some(libc::ESRCH) {
+            return Err(err)
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub(crate) fn kill_tree(pid: u32) -> io::Result<()> {
+    use std::process::Command;
+    use std::process::Stdio;
+
+    let _status = Command::new("taskkill")
+        .args(["/F", "/T", "/PID", &pid.to_string()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn kill_tree(_pid: u32) -> io::Result<()> {
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::process::Command;
+
+    use crate::fork::fork_int;
+
+
+    /// Check that buffered output is limited as configured.
+    #[test]
+    fn output_limiting() {
+        let mut buffer = Buffer::default();
+        let () = buffer.push(b"0123456789");
+        assert_eq!(mem::take(&mut buffer.data), b"0123456789");
+        let () = buffer.push(b"abcdef");
+        let () = buffer.limit(12);
+        assert_eq!(buffer.data, b"ab");
+        assert_eq!(buffer.omitted, 4);
+
+        let () = buffer.push(b"ghi");
+        assert_eq!(buffer.data, b"ab");
+        assert_eq!(buffer.omitted, 7);
+
+        let mut buffer = Buffer::default();
+        let () = buffer.limit(4);
+        let () = buffer.push(b"012");
+        let () = buffer.push(b"345");
+        assert_eq!(buffer.data, b"0123");
+        assert_eq!(buffer.omitted, 2);
+    }
+
+    /// Check that discarded output is not retained.
+    #[test]
+    fn output_discarding() {
+        let mut buffer = Buffer::default();
+        let () = buffer.push(b"0123");
+        let () = buffer.discard();
+        assert_eq!(buffer.data, b"");
+
+        let () = buffer.push(b"4567");
+        assert_eq!(buffer.data, b"");
+    }
+
+    /// Read from the child's standard output until `pred` is satisfied
+    /// by the accumulated data.
+    fn read_stdout_until(child: &mut ChildWrapper, pred: impl Fn(&str) -> bool) -> String {
+        let start = Instant::now();
+        let mut stdout = String::new();
+        while !pred(&stdout) {
+            assert!(start.elapsed() < Duration::from_secs(30), "{stdout}");
+            let () = stdout.push_str(&String::from_utf8_lossy(&child.read_stdout()));
+            let () = thread::sleep(POLL_INTERVAL);
+        }
+        stdout
+    }
+
+    /// Check that we can wait for a child with a timeout and kill it.
+    #[test]
+    fn wait_timeout_and_kill() {
+        fork_int(
+            fork_test_name!(wait_timeout_and_kill),
+            fork_id!(),
+            |_| (),
+            |mut child| {
+                let status = child.wait_timeout(Duration::from_millis(50)).unwrap();
+                assert_eq!(status, None);
+                let () = child.kill().unwrap();
+                let status = child.wait_timeout(Duration::from_secs(30)).unwrap();
+                assert!(!status.unwrap().success());
+            },
+            || thread::sleep(Duration::from_secs(30)),
+        )
+        .unwrap()
+    }
+
+    /// Check that we can read the child's output incrementally.
+    #[test]
+    fn incremental_output() {
+        fork_int(
+            fork_test_name!(incremental_output),
+            fork_id!(),
+            |_| (),
+            |mut child| {
+                let stdout = read_stdout_until(&mut child, |stdout| stdout.contains("first"));
+                assert!(!stdout.contains("second"), "{stdout}");
+
+                let output = child.wait_with_output().unwrap();
+                assert!(output.status.success());
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                assert!(stdout.contains("second"), "{stdout}");
+                assert!(!stdout.contains("first"), "{stdout}");
+            },
+            || {
+                println!("first");
+                let () = thread::sleep(Duration::from_millis(500));
+                println!("second");
+            },
+        )
+        .unwrap()
+    }
+
+    /// Check that `ChildWrapper::kill_group` kills processes spawned by
+    /// the child.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn kill_group_kills_descendants() {
+        use std::fs;
+
+        fn is_alive(pid: u32) -> bool {
+            fs::read_to_string(format!("/proc/{pid}/status"))
+                .map(|status| !status.lines().any(|line| line.starts_with("State:\tZ")))
+                .unwrap_or(false)
+        }
+
+        fork_int(
+            fork_test_name!(kill_group_kills_descendants),
+            fork_id!(),
+            |_| (),
+            |mut child| {
+                let stdout = read_stdout_until(&mut child, |stdout| {
+                    stdout
+                        .split_once("pid=")
+                        .is_some_and(|(_, rest)| rest.contains('\n'))
+                });
+                let (_, rest) = stdout.split_once("pid=").unwrap();
+                let (pid, _) = rest.split_once('\n').unwrap();
+                let pid = pid.parse::<u32>().unwrap();
+                assert!(is_alive(pid));
+
+                let () = child.kill_group().unwrap();
+                let _status = child.wait().unwrap();
+
+                let start = Instant::now();
+                while is_alive(pid) {
+                    assert!(start.elapsed() < Duration::from_secs(30));
+                    let () = thread::sleep(POLL_INTERVAL);
+                }
+            },
+            || {
+                let mut grandchild = Command::new("sleep").arg("30").spawn().unwrap();
+                println!("pid={}", grandchild.id());
+                let _status = grandchild.wait();
+            },
+        )
+        .unwrap()
+    }
+}
@@ -33,6 +33,61 @@ enum FlagType {
     Error(&'static str),
 }
 
+/// The environment variable selecting the treatment of unknown flags,
+/// unless configured explicitly.
+const UNKNOWN_FLAGS_ENV: &str = "TEST_FORK_UNKNOWN_FLAGS";
+
+
+/// The treatment of flags passed to the test harness that `test-fork`
+/// does not know about.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownFlags {
+    /// Fail with [`Error::UnknownFlag`].
+    #[default]
+    Error,
+    /// Pass the flag on to the child, with a warning.
+    ///
+    /// The flag is assumed not to be followed by a separate argument.
+    Pass,
+    /// Drop the flag, with a warning.
+    ///
+    /// The flag is assumed not to be followed by a separate argument.
+    Drop,
+}
+
+impl UnknownFlags {
+    /// Determine the treatment of unknown flags, given the one
+    /// configured explicitly, if any.
+    ///
+    /// Absent an explicit configuration, the treatment is read from
+    /// the `TEST_FORK_UNKNOWN_FLAGS` environment variable, which may be
+    /// one of `error`, `pass`, and `drop`.
+    pub(crate) fn resolve(configured: Option<Self>) -> Self {
+        if let Some(configured) = configured {
+            return configured
+        }
+
+        match env::var(UNKNOWN_FLAGS_ENV).as_deref() {
+            Ok("error") | Err(_) => Self::Error,
+            Ok("pass") => Self::Pass,
+            Ok("drop") => Self::Drop,
+            Ok(value) => panic!(
+                "test-fork: invalid {UNKNOWN_FLAGS_ENV} value `{value}` \
+                 (expected `error`, `pass`, or `drop`)"
+            ),
+        }
+    }
+
+    /// Retrieve a dense index for the variant.
+    pub(crate) fn index(self) -> usize {
+        match self {
+            Self::Error => 0,
+            Self::Pass => 1,
+            Self::Drop => 2,
+        }
+    }
+}
+
 /// Table of all flags in the 2020-05-26 nightly build.
 ///
 /// A number of these that affect output are dropped because we append our own
@@ -58,7 +113,7 @@ static KNOWN_FLAGS: &[(&str, FlagType)] = &[
     ("--logfile", FlagType::Drop(true)),
     ("--nocapture", FlagType::Drop(true)),
     ("--quiet", FlagType::Drop(false)),
-    ("--report-time", FlagType::Drop(true)),
+    ("--report-time", FlagType::Drop(false)),
     ("--show-output", FlagType::Pass(false)),
     ("--skip", FlagType::Drop(true)),
     ("--test", FlagType::Pass(false)),
@@ -108,9 +163,19 @@ fn look_up_flag(flag: &str) -> Option<FlagType> {
     look_up_flag_from_table(flag).or_else(|| look_up_flag_from_env(flag))
 }
 
-fn look_up_flag_or_err(flag: &str) -> Result<(bool, bool)> {
+fn look_up_flag_or_err(flag: &str, unknown: UnknownFlags) -> Result<(bool, bool)> {
     match look_up_flag(flag) {
-        None => Err(Error::UnknownFlag(flag.to_owned())),
+        None => match unknown {
+            UnknownFlags::Error => Err(Error::UnknownFlag(flag.to_owned())),
+            UnknownFlags::Pass => {
+                eprintln!("test-fork: passing unknown flag `{flag}` on to child");
+                Ok((true, false))
+            }
+            UnknownFlags::Drop => {
+                eprintln!("test-fork: dropping unknown flag `{flag}`");
+                Ok((false, false))
+            }
+        },
         Some(FlagType::Error(message)) => {
             Err(Error::DisallowedFlag(flag.to_owned(), message.to_owned()))
         }
@@ -124,8 +189,12 @@ fn look_up_flag_or_err(flag: &str) -> Result<(bool, bool)> {
 /// resulting argument list is also guaranteed to not have "--", so that new
 /// flags can be appended.
 ///
-/// The zeroth argument (the command name) is also dropped.
-pub(crate) fn strip_cmdline<A: Iterator<Item = String>>(args: A) -> Result<Vec<String>> {
+/// The zeroth argument (the command name) is also dropped. Flags not
+/// known to us are treated as `unknown` says.
+pub(crate) fn strip_cmdline<A: Iterator<Item = String>>(
+    args: A,
+    unknown: UnknownFlags,
+) -> Result<Vec<String>> {
     #[derive(Clone, Copy)]
     enum State {
         Ground,
@@ -156,8 +225,10 @@ pub(crate) fn strip_cmdline<A: Iterator<Item = String>>(args: A) -> Result<Vec<S
                     // "-" by itself is interpreted as a filter
                     continue;
                 } else if arg.starts_with("--") {
-                    let (pass, has_arg) =
-                        look_up_flag_or_err(arg.split('=').next().expect("split returned empty"))?;
+                    let (pass, has_arg) = look_up_flag_or_err(
+                        arg.split('=').next().expect("split returned empty"),
+                        unknown,
+                    )?;
                     // If there's an = sign, the physical argument also
                     // contains the associated value, so don't pay attention to
                     // has_arg.
@@ -177,7 +248,7 @@ pub(crate) fn strip_cmdline<A: Iterator<Item = String>>(args: A) -> Result<Vec<S
                     chars.next(); // skip initial '-'
                     while let Some(flag_ch) = chars.next() {
                         let flag = format!("-{flag_ch}");
-                        let (pass, has_arg) = look_up_flag_or_err(&flag)?;
+                        let (pass, has_arg) = look_up_flag_or_err(&flag, unknown)?;
                         if pass {
                             to_pass.push(flag_ch);
                             if has_arg {
@@ -212,6 +283,15 @@ pub(crate) fn strip_cmdline<A: Iterator<Item = String>>(args: A) -> Result<Vec<S
     Ok(ret)
 }
 
+/// Check whether the test harness was asked to report the execution
+/// time of each test.
+pub(crate) fn report_time() -> bool {
+    env::args()
+        .skip(1)
+        .take_while(|arg| arg != "--")
+        .any(|arg| arg == "--report-time")
+}
+
 /// Extra arguments to add after the stripped command line when running a
 /// single test.
 pub(crate) static RUN_TEST_ARGS: &[&str] = &[
@@ -238,8 +318,13 @@ mod test {
     use crate::fork;
 
 
+    fn strip_with(cmdline: &str, unknown: UnknownFlags) -> Result<String> {
+        strip_cmdline(cmdline.split_whitespace().map(ToOwned::to_owned), unknown)
+            .map(|strs| strs.join(" "))
+    }
+
     fn strip(cmdline: &str) -> Result<String> {
-        strip_cmdline(cmdline.split_whitespace().map(ToOwned::to_owned)).map(|strs| strs.join(" "))
+        strip_with(cmdline, UnknownFlags::Error)
     }
 
     #[test]
@@ -262,6 +347,10 @@ mod test {
             "-Zunstable-options",
             &strip("test -qZunstable-options").unwrap()
         );
+        assert_eq!(
+            "-Zunstable-options",
+            &strip("test --report-time -Zunstable-options").unwrap()
+        );
         assert_eq!("--color auto", &strip("test --color auto").unwrap());
         assert_eq!("--color=auto", &strip("test --color=auto").unwrap());
         assert_eq!("", &strip("test filter filter2").unwrap());
@@ -277,6 +366,48 @@ mod test {
         }
     }
 
+    /// Check that unknown flags are passed on or dropped, if so
+    /// configured.
+    #[test]
+    fn lenient_unknown_flags() {
+        assert_eq!(
+            "--plugh --ignored",
+            &strip_with("test --plugh --ignored filter", UnknownFlags::Pass).unwrap()
+        );
+        assert_eq!(
+            "--plugh=42",
+            &strip_with("test --plugh=42", UnknownFlags::Pass).unwrap()
+        );
+        assert_eq!("-Y", &strip_with("test -qY", UnknownFlags::Pass).unwrap());
+        assert_eq!(
+            "--ignored",
+            &strip_with("test --plugh --ignored -Y", UnknownFlags::Drop).unwrap()
+        );
+
+        match strip_with("test --help", UnknownFlags::Pass).unwrap_err() {
+            Error::DisallowedFlag(ref flag, _) => assert_eq!("--help", flag),
+            e => panic!("Unexpected error: {e}"),
+        }
+    }
+
+    /// Check that the treatment of unknown flags can be configured via
+    /// the environment.
+    #[test]
+    fn unknown_flags_via_env() {
+        fork(fork_id!(), fork_test_name!(unknown_flags_via_env), || {
+            assert_eq!(UnknownFlags::resolve(None), UnknownFlags::Error);
+            // SAFETY: We are running in a single threaded processes
+            //         after we worked.
+            unsafe { env::set_var(UNKNOWN_FLAGS_ENV, "drop") };
+            assert_eq!(UnknownFlags::resolve(None), UnknownFlags::Drop);
+            assert_eq!(
+                UnknownFlags::resolve(Some(UnknownFlags::Pass)),
+                UnknownFlags::Pass
+            );
+        })
+        .unwrap()
+    }
+
     #[test]
     fn define_args_via_env() {
         // Run in subprocess so we can change the environment without
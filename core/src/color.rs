@@ -0,0 +1,124 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Handling of ANSI colors in the output of child processes.
+//!
+//! The child's output is piped to the parent, which commonly causes
+//! programs and libraries to disable colors. Colors can be forced via
+//! the conventional `CLICOLOR_FORCE` and `FORCE_COLOR` environment
+//! variables, and disabled via `NO_COLOR`. Escape sequences emitted
+//! nevertheless can be stripped from the output being forwarded.
+
+use std::borrow::Cow;
+use std::process::Command;
+
+
+/// The environment variables forcing the use of colors.
+const FORCE_ENVS: [&str; 2] = ["CLICOLOR_FORCE", "FORCE_COLOR"];
+/// The environment variable disabling the use of colors.
+const NO_COLOR_ENV: &str = "NO_COLOR";
+/// The ASCII escape character, introducing escape sequences.
+const ESC: char = '\x1b';
+/// The ASCII bell character, which may terminate OSC sequences.
+const BEL: char = '\x07';
+
+
+/// The treatment of colors in the output of a child process.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Color {
+    /// Leave it to the child to decide whether to use colors and
+    /// forward its output untouched.
+    #[default]
+    Auto,
+    /// Ask the child to use colors even though its output is piped,
+    /// and forward its output untouched.
+    Always,
+    /// Ask the child not to use colors and strip any ANSI escape
+    /// sequences from the output being forwarded.
+    Never,
+}
+
+impl Color {
+    /// Configure the child process spawned by `command` accordingly.
+    pub(crate) fn configure(self, command: &mut Command) {
+        match self {
+            Self::Auto => (),
+            Self::Always => {
+                command.env_remove(NO_COLOR_ENV);
+                for var in FORCE_ENVS {
+                    command.env(var, "1");
+                }
+            }
+            Self::Never => {
+                command.env(NO_COLOR_ENV, "1");
+                for var in FORCE_ENVS {
+                    command.env_remove(var);
+                }
+            }
+        }
+    }
+}
+
+
+/// Strip ANSI escape sequences from `s`.
+///
+/// CSI sequences (e.g., `ESC [ 31 m` for selecting a color), OSC
+/// sequences (e.g., hyperlinks), and two character escape sequences
+/// are removed.
+pub(crate) fn strip(s: Cow<'_, str>) -> Cow<'_, str> {
+    if !s.contains(ESC) {
+        return s
+    }
+
+    let mut stripped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != ESC {
+            let () = stripped.push(c);
+            continue
+        }
+
+        match chars.next() {
+            // CSI: parameter and intermediate bytes, terminated by a
+            // final byte in the range `@` to `~`.
+            Some('[') => {
+                let _final = chars.find(|c| ('@'..='~').contains(c));
+            }
+            // OSC: terminated by BEL or ST (`ESC \`).
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == BEL || (c == ESC && chars.next().is_some()) {
+                        break
+                    }
+                }
+            }
+            Some(_) | None => (),
+        }
+    }
+    Cow::Owned(stripped)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that we strip ANSI escape sequences correctly.
+    #[test]
+    fn escape_stripping() {
+        let strip = |s: &'static str| strip(Cow::Borrowed(s)).into_owned();
+
+        assert_eq!(strip(""), "");
+        assert_eq!(strip("plain\n"), "plain\n");
+        assert_eq!(strip("\x1b[31mred\x1b[0m\n"), "red\n");
+        assert_eq!(strip("\x1b[1;38;5;196mbold\x1b[m"), "bold");
+        assert_eq!(
+            strip("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x07!"),
+            "link!"
+        );
+        assert_eq!(strip("a\x1b7b\x1b8c"), "abc");
+        assert_eq!(strip("truncated\x1b["), "truncated");
+        assert_eq!(strip("trailing\x1b"), "trailing");
+    }
+}
@@ -0,0 +1,289 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! A [`ProcessBackend`] running children inside an OCI container.
+
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::process::Child;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Stdio;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use crate::backend::ChildProcess;
+use crate::backend::Launched;
+use crate::backend::ProcessBackend;
+
+
+/// Generate a name for a new container that is unique on the system.
+fn container_name() -> String {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    let next = NEXT.fetch_add(1, Ordering::Relaxed);
+    format!("test-fork-{}-{next}", process::id())
+}
+
+
+/// A child process running inside a container.
+#[derive(Debug)]
+struct ContainerProcess {
+    /// The container engine client running the container.
+    client: Child,
+    /// The container engine in use.
+    engine: OsString,
+    /// The name of the container.
+    name: String,
+}
+
+impl ChildProcess for ContainerProcess {
+    fn id(&self) -> u32 {
+        self.client.id()
+    }
+
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        self.client.try_wait()
+    }
+
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        self.client.wait()
+    }
+
+    fn kill(&mut self) -> io::Result<()> {
+        // Killing just the client would leave the container running.
+        let _status = Command::new(&self.engine)
+            .arg("kill")
+            .arg(&self.name)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+        self.client.kill()
+    }
+
+    fn kill_group(&mut self) -> io::Result<()> {
+        // All processes spawned by the child live inside the container
+        // and go down along with it.
+        self.kill()
+    }
+}
+
+
+/// A [`ProcessBackend`] running each child inside a container, using
+/// `docker` or a compatible container engine such as `podman`.
+///
+/// The test executable is bind-mounted into the container (read-only,
+/// at the same path as on the host) and run from there, so the image
+/// has to provide a compatible environment (e.g., the same C library).
+/// The child's output and exit status are relayed by the container
+/// engine's client and supervised as usual.
+///
+/// The container starts out with an environment containing only the
+/// variables explicitly configured for the child (as opposed to the
+/// ones it would inherit), without any network access, and with empty
+/// standard input. Consequently, options requiring communication with
+/// the parent beyond the child's output, such as
+/// [`Builder::stdin`][crate::Builder::stdin],
+/// [`Builder::fork_in_out`][crate::Builder::fork_in_out], and
+/// fixtures, are not supported. Also note that a child killed by a
+/// signal is reported by the container engine as having exited with
+/// code `128` plus the signal number.
+#[derive(Clone, Debug)]
+pub struct ContainerBackend {
+    /// The container engine to use.
+    engine: OsString,
+    /// The image to run the child in.
+    image: String,
+    /// Host paths to bind-mount into the container, along with their
+    /// path inside the container and whether they are read-only.
+    mounts: Vec<(PathBuf, PathBuf, bool)>,
+    /// The network to connect the container to.
+    network: String,
+    /// Additional arguments to the engine's `run` command.
+    args: Vec<OsString>,
+}
+
+impl ContainerBackend {
+    /// Create a new backend running children in containers using the
+    /// given image.
+    pub fn new<I>(image: I) -> Self
+    where
+        I: Into<String>,
+    {
+        Self {
+            engine: OsString::from("docker"),
+            image: image.into(),
+            mounts: Vec::new(),
+            network: "none".to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Set the container engine to use (`docker` by default).
+    pub fn engine<E>(mut self, engine: E) -> Self
+    where
+        E: Into<OsString>,
+    {
+        self.engine = engine.into();
+        self
+    }
+
+    /// Bind-mount the host path `host` into the container at
+    /// `container`.
+    pub fn mount<H, C>(mut self, host: H, container: C, read_only: bool) -> Self
+    where
+        H: Into<PathBuf>,
+        C: Into<PathBuf>,
+    {
+        self.mounts.push((host.into(), container.into(), read_only));
+        self
+    }
+
+    /// Connect the container to the given network (`none` by default).
+    pub fn network<N>(mut self, network: N) -> Self
+    where
+        N: Into<String>,
+    {
+        self.network = network.into();
+        self
+    }
+
+    /// Pass an additional argument to the engine's `run` command.
+    pub fn arg<A>(mut self, arg: A) -> Self
+    where
+        A: Into<OsString>,
+    {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Assemble the arguments to the engine for running the child
+    /// described by `command` in a container with the given name.
+    fn run_args(&self, command: &Command, name: &str) -> Vec<OsString> {
+        let mount = |host: &Path, container: &Path, read_only: bool| {
+            let mut mount = OsString::from("type=bind,source=");
+            let () = mount.push(host);
+            let () = mount.push(",target=");
+            let () = mount.push(container);
+            if read_only {
+                let () = mount.push(",readonly");
+            }
+            mount
+        };
+
+        let program = Path::new(command.get_program());
+        let mut args = vec![
+            OsString::from("run"),
+            OsString::from("--rm"),
+            OsString::from("--name"),
+            OsString::from(name),
+            OsString::from("--network"),
+            OsString::from(&self.network),
+            OsString::from("--mount"),
+            mount(program, program, true),
+        ];
+
+        for (host, container, read_only) in &self.mounts {
+            let () = args.push(OsString::from("--mount"));
+            let () = args.push(mount(host, container, *read_only));
+        }
+
+        if let Some(dir) = command.get_current_dir() {
+            let () = args.push(OsString::from("--workdir"));
+            let () = args.push(dir.into());
+        }
+
+        for (var, value) in command.get_envs() {
+            if let Some(value) = value {
+                let mut env = var.to_os_string();
+                let () = env.push("=");
+                let () = env.push(value);
+                let () = args.push(OsString::from("--env"));
+                let () = args.push(env);
+            }
+        }
+
+        let () = args.extend(self.args.iter().cloned());
+        let () = args.push(OsString::from(&self.image));
+        let () = args.push(program.into());
+        let () = args.extend(command.get_args().map(OsStr::to_os_string));
+        args
+    }
+}
+
+impl ProcessBackend for ContainerBackend {
+    fn launch(&self, command: &mut Command) -> io::Result<Launched> {
+        let name = container_name();
+        let mut client = Command::new(&self.engine)
+            .args(self.run_args(command, &name))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let launched = Launched {
+            stdin: None,
+            stdout: client.stdout.take().map(|stdout| Box::new(stdout) as _),
+            stderr: client.stderr.take().map(|stderr| Box::new(stderr) as _),
+            process: Box::new(ContainerProcess {
+                client,
+                engine: self.engine.clone(),
+                name,
+            }),
+        };
+        Ok(launched)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that we assemble the container engine's arguments as
+    /// expected.
+    #[test]
+    fn run_arguments() {
+        let backend = ContainerBackend::new("debian:stable")
+            .engine("podman")
+            .mount("/data", "/mnt/data", false)
+            .arg("--cap-drop=ALL");
+        let mut command = Command::new("/target/debug/deps/tests");
+        let _command = command
+            .args(["--exact", "my_test"])
+            .env("TEST_FORK_OCCURS", "id")
+            .env_remove("HOME")
+            .current_dir("/src");
+
+        let args = backend.run_args(&command, "test-fork-1-0");
+        let expected = [
+            "run",
+            "--rm",
+            "--name",
+            "test-fork-1-0",
+            "--network",
+            "none",
+            "--mount",
+            "type=bind,source=/target/debug/deps/tests,target=/target/debug/deps/tests,readonly",
+            "--mount",
+            "type=bind,source=/data,target=/mnt/data",
+            "--workdir",
+            "/src",
+            "--env",
+            "TEST_FORK_OCCURS=id",
+            "--cap-drop=ALL",
+            "debian:stable",
+            "/target/debug/deps/tests",
+            "--exact",
+            "my_test",
+        ];
+        assert_eq!(args, expected);
+        assert_eq!(backend.engine, "podman");
+    }
+}
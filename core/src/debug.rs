@@ -0,0 +1,168 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Pausing of child processes for attaching a debugger.
+//!
+//! If the `TEST_FORK_DEBUG` environment variable is set to a non-empty
+//! value other than `0` (or debugging is requested via
+//! [`Builder::debug`][crate::Builder::debug]), each child pauses before
+//! running the test, until a debugger attached to it (or, on Unix
+//! systems, it got continued via `SIGCONT`). The parent prints the
+//! child's process identifier along with a hint on how to attach.
+
+use std::env;
+use std::ffi::OsStr;
+use std::io;
+use std::io::Write as _;
+use std::process::Command;
+
+
+/// The environment variable requesting that children wait for a
+/// debugger.
+pub(crate) const DEBUG_ENV: &str = "TEST_FORK_DEBUG";
+
+
+/// Check whether the given value of [`DEBUG_ENV`] enables debugging.
+fn enables(value: &OsStr) -> bool {
+    !value.is_empty() && value != "0"
+}
+
+/// Check whether debugging is requested via the environment of the
+/// current process.
+pub(crate) fn requested() -> bool {
+    env::var_os(DEBUG_ENV).is_some_and(|value| enables(&value))
+}
+
+/// Check whether the child process spawned by `command` is going to
+/// wait for a debugger.
+pub(crate) fn enabled(command: &Command) -> bool {
+    let explicit = command
+        .get_envs()
+        .find_map(|(name, value)| (name == DEBUG_ENV).then_some(value));
+    match explicit {
+        Some(value) => value.is_some_and(enables),
+        None => requested(),
+    }
+}
+
+/// Ask the child process spawned by `command` to wait for a debugger.
+pub(crate) fn configure(command: &mut Command) {
+    command.env(DEBUG_ENV, "1");
+}
+
+/// Tell the user how to attach to the child with the given process
+/// identifier, which is waiting for a debugger.
+pub(crate) fn announce(test_name: &str, pid: u32) {
+    let resume = if cfg!(unix) {
+        format!(" (or resume it with: kill -CONT {pid})")
+    } else {
+        String::new()
+    };
+    // Write to standard error directly, bypassing the test harness'
+    // output capturing, as the hint is of no use once the test is done.
+    let _result = writeln!(
+        io::stderr(),
+        "test-fork: child {pid} of `{test_name}` is waiting for a debugger\n\
+         test-fork: attach with: gdb -p {pid}{resume}"
+    );
+}
+
+
+/// Wait for a debugger to attach to the current process, which is a
+/// child process, if requested.
+#[cfg(unix)]
+pub(crate) fn wait() {
+    if requested() {
+        // Stop ourselves until a debugger continues us (or somebody
+        // sends `SIGCONT`).
+        // SAFETY: `raise` is always safe to call.
+        let _rc = unsafe { libc::raise(libc::SIGSTOP) };
+    }
+}
+
+/// Wait for a debugger to attach to the current process, which is a
+/// child process, if requested.
+#[cfg(windows)]
+pub(crate) fn wait() {
+    use std::thread;
+
+    use crate::child::POLL_INTERVAL;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn IsDebuggerPresent() -> i32;
+    }
+
+    if requested() {
+        // SAFETY: `IsDebuggerPresent` is always safe to call.
+        while unsafe { IsDebuggerPresent() } == 0 {
+            let () = thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// Wait for a debugger to attach to the current process, which is a
+/// child process, if requested.
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn wait() {}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that we determine correctly whether a child is going to
+    /// wait for a debugger.
+    #[test]
+    fn enabling() {
+        let mut command = Command::new("true");
+        let () = configure(&mut command);
+        assert!(enabled(&command));
+
+        command.env(DEBUG_ENV, "0");
+        assert!(!enabled(&command));
+
+        command.env_remove(DEBUG_ENV);
+        assert!(!enabled(&command));
+    }
+
+    /// Check that a child waits for a debugger until continued.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn child_waiting() {
+        use std::fs;
+        use std::thread;
+        use std::time::Duration;
+        use std::time::Instant;
+
+        use crate::fork::fork_int;
+
+        fork_int(
+            fork_test_name!(child_waiting),
+            fork_id!(),
+            configure,
+            |mut child| {
+                let pid = child.id();
+                let start = Instant::now();
+                loop {
+                    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).unwrap();
+                    let (_, rest) = stat.rsplit_once(')').unwrap();
+                    if rest.trim_start().starts_with('T') {
+                        break
+                    }
+                    assert!(start.elapsed() < Duration::from_secs(30), "{stat}");
+                    let () = thread::sleep(Duration::from_millis(10));
+                }
+
+                let pid = libc::pid_t::try_from(pid).unwrap();
+                // SAFETY: `kill` is always safe to call.
+                let rc = unsafe { libc::kill(pid, libc::SIGCONT) };
+                assert_eq!(rc, 0);
+                assert!(child.wait().unwrap().success());
+            },
+            || (),
+        )
+        .unwrap()
+    }
+}
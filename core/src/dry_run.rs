@@ -0,0 +1,175 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Printing of the child command instead of spawning the child.
+//!
+//! If the `TEST_FORK_DRY_RUN` environment variable is set, no child
+//! processes are spawned. Instead, the command that would have been
+//! used for spawning each child is printed in a form that can be
+//! pasted into a POSIX shell, including all changes to the
+//! environment. The command is printed once the child has been
+//! configured, but the process backend is not taken into account.
+//! Configuration steps with side effects, such as running the `before`
+//! hook, acquiring serialization locks, reserving ports, or creating
+//! the artifact directory, are skipped altogether.
+
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::env;
+use std::ffi::OsStr;
+use std::io;
+use std::io::Write as _;
+use std::process::Command;
+
+
+/// The environment variable requesting a dry run.
+const DRY_RUN_ENV: &str = "TEST_FORK_DRY_RUN";
+
+
+thread_local! {
+    /// Whether the environment of the next child spawned by the current
+    /// thread got cleared.
+    static ENV_CLEARED: Cell<bool> = const { Cell::new(false) };
+}
+
+
+/// Check whether printing the child command instead of spawning the
+/// child is requested.
+pub(crate) fn requested() -> bool {
+    env::var_os(DRY_RUN_ENV).is_some()
+}
+
+/// Note that the environment of the next child spawned by the calling
+/// thread got cleared, which can't be inferred from its command.
+pub(crate) fn note_env_cleared() {
+    let () = ENV_CLEARED.set(true);
+}
+
+/// Check whether the environment of the next child spawned by the
+/// calling thread got cleared, resetting the state for the next one.
+pub(crate) fn take_env_cleared() -> bool {
+    ENV_CLEARED.replace(false)
+}
+
+/// Quote `s` for use in a POSIX shell, if necessary.
+fn quote(s: &OsStr) -> Cow<'_, str> {
+    let s = s.to_string_lossy();
+    let safe = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+    if safe {
+        s
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''")).into()
+    }
+}
+
+/// Format `command` as a shell command line, starting the child with
+/// an empty environment if `env_cleared` is set.
+pub(crate) fn format(command: &Command, env_cleared: bool) -> String {
+    let mut parts = Vec::new();
+    if let Some(dir) = command.get_current_dir() {
+        parts.push(format!("cd {} &&", quote(dir.as_os_str())));
+    }
+
+    let mut envs = command.get_envs().collect::<Vec<_>>();
+    let () = envs.sort();
+    if !envs.is_empty() || env_cleared {
+        parts.push("env".to_string());
+    }
+    if env_cleared {
+        parts.push("-i".to_string());
+    }
+    // `env` expects options, i.e., removals, ahead of any assignments.
+    for (name, _) in envs.iter().filter(|(_, value)| value.is_none()) {
+        parts.push(format!("-u {}", quote(name)));
+    }
+    for (name, value) in &envs {
+        if let Some(value) = value {
+            parts.push(format!("{}={}", quote(name), quote(value)));
+        }
+    }
+
+    parts.push(quote(command.get_program()).into_owned());
+    parts.extend(command.get_args().map(|arg| quote(arg).into_owned()));
+    parts.join(" ")
+}
+
+/// Print the command that would spawn the child running the given
+/// test.
+pub(crate) fn print(test_name: &str, command: &Command, env_cleared: bool) {
+    // Write to standard error directly, bypassing the test harness'
+    // output capturing.
+    let _result = writeln!(
+        io::stderr(),
+        "test-fork: child of `{test_name}` would be spawned as:\n{}",
+        format(command, env_cleared)
+    );
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::fork::fork_int;
+    use crate::Builder;
+
+
+    /// Check that commands are formatted as expected.
+    #[test]
+    fn command_formatting() {
+        let mut command = Command::new("/bin/test exe");
+        command
+            .args(["--exact", "it's"])
+            .env("B", "2")
+            .env("A", "")
+            .env_remove("C")
+            .current_dir("/tmp");
+        assert_eq!(
+            format(&command, false),
+            "cd /tmp && env -u C A='' B=2 '/bin/test exe' --exact 'it'\\''s'"
+        );
+
+        let command = Command::new("test");
+        assert_eq!(format(&command, false), "test");
+        assert_eq!(format(&command, true), "env -i test");
+
+        let mut command = Command::new("test");
+        let _command = command.env_clear().env("PATH", "/bin");
+        assert_eq!(format(&command, true), "env -i PATH=/bin test");
+    }
+
+    /// Check that the child command is printed instead of spawning the
+    /// child if requested.
+    #[test]
+    fn dry_run() {
+        fork_int(
+            fork_test_name!(dry_run),
+            fork_id!(),
+            |cmd| {
+                cmd.env(DRY_RUN_ENV, "1");
+            },
+            |child| {
+                let output = child.wait_with_output().unwrap();
+                assert!(output.status.success());
+                let stderr = String::from_utf8(output.stderr).unwrap();
+                assert!(
+                    stderr.contains("child of `dry_run::test::spawned` would be spawned as:"),
+                    "{stderr}"
+                );
+                assert!(stderr.contains(":spawned "), "{stderr}");
+                assert!(stderr.contains(" dry_run::test::spawned\n"), "{stderr}");
+            },
+            || {
+                let () = Builder::new()
+                    .before(|_builder| panic!("hook should not run"))
+                    .fork(fork_id!("spawned"), "dry_run::test::spawned", || {
+                        panic!("test should not run")
+                    })
+                    .unwrap();
+            },
+        )
+        .unwrap()
+    }
+}
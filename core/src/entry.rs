@@ -0,0 +1,56 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Support for forking from binaries not run by the standard test
+//! harness.
+//!
+//! Examples, `harness = false` test targets, and fuzz drivers come with
+//! their own `main` function. The command line of such a binary is not
+//! that of the test harness, so it is passed on to children verbatim.
+//! Each child starts out running `main` again, which either dispatches
+//! to the body registered for the child's test right away or proceeds
+//! as usual, eventually reaching the fork point the child was spawned
+//! for.
+
+use std::process::ExitCode;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+use crate::fork::run_child;
+use crate::info::fork_info;
+
+
+/// Whether the current process runs a custom `main` function.
+static CUSTOM_MAIN: AtomicBool = AtomicBool::new(false);
+
+
+/// Check whether the current process runs a custom `main` function,
+/// as opposed to the test harness.
+pub(crate) fn custom_main() -> bool {
+    CUSTOM_MAIN.load(Ordering::Relaxed)
+}
+
+/// Enter a custom `main` function, as done by the `#[test_fork::main]`
+/// attribute.
+///
+/// This function has to be invoked before the first fork point is
+/// reached. In a child process spawned for one of the tests named in
+/// `bodies`, the corresponding body is run right away and the process
+/// exits with its result. Otherwise, the function returns and `main`
+/// is expected to proceed as usual. A child spawned for a test without
+/// registered body then reaches its fork point by running `main` up to
+/// it.
+pub fn enter_main(bodies: &[(&str, fn() -> ExitCode)]) {
+    let () = CUSTOM_MAIN.store(true, Ordering::Relaxed);
+
+    if let Some(info) = fork_info() {
+        if let Some((test_name, body)) = bodies
+            .iter()
+            .find(|(test_name, _body)| *test_name == info.test_name())
+        {
+            log!("dispatching to registered body of `{test_name}`");
+            let mut body = *body;
+            run_child(&mut body)
+        }
+    }
+}
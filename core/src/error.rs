@@ -33,7 +33,6 @@ pub enum Error {
     /// about why the flag could not be handled.
     DisallowedFlag(String, String),
     /// Spawning a subprocess failed.
-    #[expect(clippy::enum_variant_names)]
     SpawnError(io::Error),
 }
 
@@ -0,0 +1,217 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Emission of machine-readable lifecycle events for child processes.
+//!
+//! If the `TEST_FORK_EVENTS` environment variable is set, one JSON
+//! object per line is appended to the file it names for each event
+//! (e.g., a child being spawned or exiting). On Unix systems, a value
+//! of the form `fd:<n>` refers to an already open file descriptor
+//! instead.
+
+use std::env;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Write as _;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::sync::PoisonError;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use crate::record::write_json_str;
+
+
+/// The environment variable specifying where to write events to.
+const EVENTS_ENV: &str = "TEST_FORK_EVENTS";
+
+
+/// A value of an event field.
+#[derive(Clone, Debug)]
+pub(crate) enum Value<'a> {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Str(&'a str),
+}
+
+impl From<bool> for Value<'_> {
+    fn from(other: bool) -> Self {
+        Self::Bool(other)
+    }
+}
+
+impl<'a> From<&'a str> for Value<'a> {
+    fn from(other: &'a str) -> Self {
+        Self::Str(other)
+    }
+}
+
+impl<T> From<Option<T>> for Value<'_>
+where
+    T: Into<i64>,
+{
+    fn from(other: Option<T>) -> Self {
+        other.map_or(Self::Null, |value| Self::Int(value.into()))
+    }
+}
+
+
+/// Format an event as a single line JSON object.
+fn format_event(event: &str, fields: &[(&str, Value<'_>)]) -> String {
+    let time_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis())
+        .unwrap_or_default();
+
+    let mut json = String::from("{\"event\":");
+    let () = write_json_str(&mut json, event);
+    let _result = write!(json, ",\"time_ms\":{time_ms}");
+
+    for (name, value) in fields {
+        let () = json.push(',');
+        let () = write_json_str(&mut json, name);
+        let () = json.push(':');
+        match value {
+            Value::Null => json.push_str("null"),
+            Value::Bool(value) => {
+                let _result = write!(json, "{value}");
+            }
+            Value::Int(value) => {
+                let _result = write!(json, "{value}");
+            }
+            Value::Str(value) => write_json_str(&mut json, value),
+        }
+    }
+    let () = json.push_str("}\n");
+    json
+}
+
+
+/// Open the event sink described by `spec`.
+fn open(spec: &str) -> Option<File> {
+    #[cfg(unix)]
+    if let Some(fd) = spec.strip_prefix("fd:") {
+        use std::os::fd::FromRawFd as _;
+
+        let fd = fd.parse().ok()?;
+        // SAFETY: The user asserts that the file descriptor is open
+        //         and usable for writing by providing it to us. The
+        //         `File` is kept alive for the remainder of the
+        //         program, so we never close a descriptor we don't own
+        //         while someone else may still use it.
+        let file = unsafe { File::from_raw_fd(fd) };
+        return Some(file)
+    }
+
+    OpenOptions::new().create(true).append(true).open(spec).ok()
+}
+
+/// Retrieve the configured event sink, if any.
+fn sink() -> Option<&'static Mutex<File>> {
+    static SINK: OnceLock<Option<Mutex<File>>> = OnceLock::new();
+
+    SINK.get_or_init(|| {
+        let spec = env::var_os(EVENTS_ENV)?;
+        let spec = spec.to_str()?;
+        let file = open(spec);
+        if file.is_none() {
+            eprintln!("test-fork: failed to open {EVENTS_ENV} sink `{spec}`; not emitting events");
+        }
+        file.map(Mutex::new)
+    })
+    .as_ref()
+}
+
+/// Emit an event with the given fields, if emission of events is
+/// enabled.
+pub(crate) fn emit(event: &str, fields: &[(&str, Value<'_>)]) {
+    if let Some(sink) = sink() {
+        let line = format_event(event, fields);
+        let mut file = sink.lock().unwrap_or_else(PoisonError::into_inner);
+        // Events are purely informational. Failure to emit one should
+        // not fail the test.
+        let _result = file.write_all(line.as_bytes());
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+    use std::process;
+
+    use crate::fork::fork_int;
+
+
+    /// Check that events are formatted as expected.
+    #[test]
+    fn event_formatting() {
+        let event = format_event(
+            "exit",
+            &[
+                ("test", Value::from("foo::\"bar\"\n")),
+                ("success", Value::from(false)),
+                ("code", Value::from(Some(70))),
+                ("signal", Value::from(None::<i32>)),
+            ],
+        );
+        let (prefix, rest) = event.split_once(",\"time_ms\":").unwrap();
+        assert_eq!(prefix, r#"{"event":"exit""#);
+        let (_time, rest) = rest.split_once(',').unwrap();
+        assert_eq!(
+            rest,
+            "\"test\":\"foo::\\\"bar\\\"\\n\",\"success\":false,\"code\":70,\"signal\":null}\n"
+        );
+    }
+
+    /// Check that lifecycle events are emitted for a child process.
+    #[test]
+    fn events_emitted() {
+        let test_name = fork_test_name!(events_emitted);
+        let path = env::temp_dir().join(format!("test-fork-events-{}.jsonl", process::id()));
+
+        fork_int(
+            test_name,
+            fork_id!(),
+            |cmd| {
+                cmd.env(EVENTS_ENV, &path);
+            },
+            |child| {
+                let output = child.wait_with_output().unwrap();
+                assert!(output.status.success());
+            },
+            || {
+                fork_int(
+                    test_name,
+                    fork_id!(),
+                    |_| (),
+                    |mut child| assert!(child.wait().unwrap().success()),
+                    || (),
+                )
+                .unwrap()
+            },
+        )
+        .unwrap();
+
+        let events = fs::read_to_string(&path).unwrap();
+        let _result = fs::remove_file(&path);
+        let lines = events.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2, "{events}");
+        let spawn = lines.first().unwrap();
+        assert!(spawn.starts_with(r#"{"event":"spawn""#), "{spawn}");
+        assert!(
+            spawn.contains(r#""test":"events::test::events_emitted""#),
+            "{spawn}"
+        );
+        let exit = lines.get(1).unwrap();
+        assert!(exit.starts_with(r#"{"event":"exit""#), "{exit}");
+        assert!(
+            exit.contains(r#""success":true,"code":0,"signal":null"#),
+            "{exit}"
+        );
+    }
+}
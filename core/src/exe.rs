@@ -0,0 +1,135 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Resolution of the executable to spawn child processes from.
+//!
+//! By default, children are new instances of the current executable.
+//! [`env::current_exe`] may fail to report it, though, or report a path
+//! that no longer refers to it (e.g., because the binary got replaced
+//! while running, in which case Linux reports a path with a
+//! ` (deleted)` suffix). In such cases we fall back to platform
+//! specific links to the executable of the running process, which keep
+//! working even if it got deleted, and eventually to the zeroth
+//! argument of the process. The `TEST_FORK_EXE` environment variable
+//! overrides the executable altogether.
+
+use std::borrow::Cow;
+use std::env;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+
+/// The environment variable overriding the executable to spawn child
+/// processes from.
+const EXE_ENV: &str = "TEST_FORK_EXE";
+
+/// The platform specific link to the executable of the current process,
+/// if any.
+const PROC_EXE: Option<&str> = if cfg!(any(target_os = "linux", target_os = "android")) {
+    Some("/proc/self/exe")
+} else if cfg!(any(target_os = "freebsd", target_os = "dragonfly")) {
+    Some("/proc/curproc/file")
+} else if cfg!(target_os = "netbsd") {
+    Some("/proc/curproc/exe")
+} else if cfg!(any(target_os = "solaris", target_os = "illumos")) {
+    Some("/proc/self/path/a.out")
+} else {
+    None
+};
+
+
+/// Find the current executable, trying the various candidates in turn.
+fn find_current() -> Option<PathBuf> {
+    match env::current_exe() {
+        Ok(exe) if exe.is_file() => return Some(exe),
+        Ok(exe) => log!("current executable {} does not exist", exe.display()),
+        Err(err) => log!("failed to determine current executable: {err}"),
+    }
+
+    if let Some(exe) = PROC_EXE.map(PathBuf::from) {
+        if exe.is_file() {
+            return Some(exe)
+        }
+    }
+
+    // The zeroth argument is only meaningful if it contains a path, as
+    // opposed to a name looked up in `PATH`.
+    let arg0 = PathBuf::from(env::args_os().next()?);
+    if arg0.components().count() > 1 && arg0.is_file() {
+        // The working directory may change later on, so make the path
+        // absolute right away.
+        return env::current_dir().ok().map(|dir| dir.join(arg0))
+    }
+    None
+}
+
+/// Retrieve the current executable.
+///
+/// The executable is only determined once, as it is needed for every
+/// child spawned.
+fn current() -> Option<&'static Path> {
+    static CURRENT: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+    CURRENT
+        .get_or_init(|| {
+            let exe = find_current();
+            log!("resolved current executable to {exe:?}");
+            exe
+        })
+        .as_deref()
+}
+
+/// Resolve the executable to spawn child processes from, given the one
+/// configured explicitly, if any.
+///
+/// Absent an explicit configuration, the executable is read from the
+/// `TEST_FORK_EXE` environment variable, falling back to the current
+/// executable.
+pub(crate) fn resolve(configured: Option<&Path>) -> io::Result<Cow<'_, Path>> {
+    if let Some(exe) = configured {
+        return Ok(Cow::Borrowed(exe))
+    }
+
+    if let Some(exe) = env::var_os(EXE_ENV) {
+        return Ok(Cow::Owned(PathBuf::from(exe)))
+    }
+
+    current().map(Cow::Borrowed).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("failed to determine current executable; set {EXE_ENV} to provide it"),
+        )
+    })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that an explicitly configured executable takes precedence.
+    #[test]
+    fn configured_resolution() {
+        let exe = Path::new("/does/not/exist");
+        assert_eq!(resolve(Some(exe)).unwrap(), exe);
+    }
+
+    /// Check that the current executable is found.
+    #[test]
+    fn current_resolution() {
+        let exe = current().unwrap();
+        assert!(exe.is_file(), "{}", exe.display());
+    }
+
+    /// Check that the platform specific link to the current executable,
+    /// if any, is usable.
+    #[test]
+    fn proc_exe() {
+        if let Some(exe) = PROC_EXE {
+            assert!(Path::new(exe).is_file());
+        }
+    }
+}
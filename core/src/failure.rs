@@ -0,0 +1,496 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+use std::any::Any;
+use std::error::Error as StdError;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::panic;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::thread;
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::panic_hook;
+use crate::panic_hook::PanicRecord;
+use crate::panic_hook::PayloadType;
+
+
+/// Symbolic names of common `NTSTATUS` codes indicating a crash.
+#[cfg(any(windows, test))]
+const NTSTATUS_NAMES: [(u32, &str); 14] = [
+    (0x8000_0003, "STATUS_BREAKPOINT"),
+    (0xC000_0005, "STATUS_ACCESS_VIOLATION"),
+    (0xC000_001D, "STATUS_ILLEGAL_INSTRUCTION"),
+    (0xC000_0025, "STATUS_NONCONTINUABLE_EXCEPTION"),
+    (0xC000_008C, "STATUS_ARRAY_BOUNDS_EXCEEDED"),
+    (0xC000_0094, "STATUS_INTEGER_DIVIDE_BY_ZERO"),
+    (0xC000_0095, "STATUS_INTEGER_OVERFLOW"),
+    (0xC000_00FD, "STATUS_STACK_OVERFLOW"),
+    (0xC000_0135, "STATUS_DLL_NOT_FOUND"),
+    (0xC000_0139, "STATUS_ENTRYPOINT_NOT_FOUND"),
+    (0xC000_013A, "STATUS_CONTROL_C_EXIT"),
+    (0xC000_0142, "STATUS_DLL_INIT_FAILED"),
+    (0xC000_0374, "STATUS_HEAP_CORRUPTION"),
+    (0xC000_0409, "STATUS_STACK_BUFFER_OVERRUN"),
+];
+/// The `NTSTATUS` code a process exits with when aborting.
+#[cfg(windows)]
+const STATUS_STACK_BUFFER_OVERRUN: u32 = 0xC000_0409;
+
+
+/// A classification of the ways in which a child process can fail.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ChildFailure {
+    /// The child panicked.
+    ///
+    /// If multiple threads panicked, the first panic is reported.
+    Panicked {
+        /// The panic message.
+        message: String,
+        /// The name of the panicking thread, if any.
+        thread: Option<String>,
+        /// The source location of the panic, as
+        /// `<file>:<line>:<column>`, if known.
+        location: Option<String>,
+        /// The type of the panic payload.
+        payload: PayloadType,
+    },
+    /// The child was terminated by a signal.
+    Signaled {
+        /// The number of the signal.
+        signal: i32,
+    },
+    /// The child crashed, as indicated by an `NTSTATUS` error code as
+    /// its exit code (on Windows).
+    Crashed {
+        /// The `NTSTATUS` code.
+        code: u32,
+        /// The symbolic name of the code, if known.
+        name: Option<&'static str>,
+    },
+    /// The child exited with a non-zero exit code without panicking.
+    ExitCode {
+        /// The exit code.
+        code: i32,
+    },
+    /// The child was considered hung and got killed.
+    TimedOut {
+        /// The time that passed without any sign of life from the
+        /// child.
+        elapsed: Duration,
+        /// The stacks of the child's threads, as captured before
+        /// killing it, if available.
+        stacks: Option<String>,
+    },
+    /// The child emitted a sanitizer report.
+    Sanitizer {
+        /// The line introducing the report.
+        report: String,
+    },
+    /// The child left behind processes it spawned, which got killed.
+    Leaked {
+        /// Descriptions of the leaked processes.
+        processes: Vec<String>,
+    },
+    /// The child's output did not match the expected output.
+    OutputMismatch {
+        /// The path to the file containing the expected output.
+        path: PathBuf,
+        /// A description of the (first) difference.
+        difference: String,
+    },
+    /// Some of the children run for variants of the same test failed.
+    Variants {
+        /// The total number of children run.
+        total: usize,
+        /// The failures of the respective children, each labeled with
+        /// a description of its variant.
+        failures: Vec<(String, Self)>,
+    },
+    /// The child process could not be spawned.
+    SpawnFailed(Error),
+}
+
+impl ChildFailure {
+    /// Interpret the exit code of a child as an `NTSTATUS` code,
+    /// returning a failure if it indicates a crash.
+    ///
+    /// Codes with error severity are always considered crashes, as are
+    /// the other well-known codes we have names for.
+    #[cfg(any(windows, test))]
+    fn crash(code: i32) -> Option<Self> {
+        let code = u32::from_ne_bytes(code.to_ne_bytes());
+        let name = NTSTATUS_NAMES
+            .iter()
+            .find(|(status, _name)| *status == code)
+            .map(|(_status, name)| *name);
+
+        if code & 0xC000_0000 == 0xC000_0000 || name.is_some() {
+            Some(Self::Crashed { code, name })
+        } else {
+            None
+        }
+    }
+
+    /// Classify the failure of a child that exited with `status`,
+    /// given the panics it reported. Returns `None` if the child did
+    /// not fail.
+    pub(crate) fn classify(status: ExitStatus, panics: Vec<PanicRecord>) -> Option<Self> {
+        if status.success() {
+            return None
+        }
+
+        let panic = panics.into_iter().next().map(|panic| {
+            let PanicRecord {
+                message,
+                thread,
+                location,
+                payload,
+            } = panic;
+            Self::Panicked {
+                message,
+                thread,
+                location,
+                payload,
+            }
+        });
+
+        // When built with `panic = "abort"`, a panicking child aborts,
+        // unless its panic hook got to exit first. Either way, the
+        // failure is the panic and not the abort.
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt as _;
+
+            if let Some(signal) = status.signal() {
+                return match panic {
+                    Some(panic) if signal == libc::SIGABRT => Some(panic),
+                    _ => Some(Self::Signaled { signal }),
+                }
+            }
+        }
+
+        #[cfg(windows)]
+        if let Some(crash) = status.code().and_then(Self::crash) {
+            return match (panic, &crash) {
+                (
+                    Some(panic),
+                    Self::Crashed {
+                        code: STATUS_STACK_BUFFER_OVERRUN,
+                        ..
+                    },
+                ) => Some(panic),
+                _ => Some(crash),
+            }
+        }
+
+        if panic.is_some() {
+            return panic
+        }
+
+        // A process that was neither signaled nor exited with a code
+        // does not exist on the platforms we care about, but be
+        // defensive.
+        let code = status.code().unwrap_or(-1);
+        Some(Self::ExitCode { code })
+    }
+
+    /// Check whether the failure corresponds to the test panicking,
+    /// i.e., whether the test would have panicked had it been run
+    /// in-process.
+    ///
+    /// That is not the case for crashes, hangs, a child exiting
+    /// unsuccessfully on its own, and the like.
+    pub(crate) fn is_panic(&self) -> bool {
+        match self {
+            Self::Panicked { .. } => true,
+            Self::Variants { failures, .. } => {
+                failures.iter().all(|(_label, failure)| failure.is_panic())
+            }
+            _ => false,
+        }
+    }
+
+    /// Panic with a description of the failure.
+    ///
+    /// If the child panicked, the panic payload is of the same type as
+    /// the child's was, provided it was a `&str` or a `String`, so that
+    /// users of [`catch_unwind`][panic::catch_unwind] downcasting the
+    /// payload keep working. Note that the payload contains the
+    /// failure's description, which includes the child's panic
+    /// message. For payloads of other types, an [`OpaquePayload`] is
+    /// used.
+    ///
+    /// If the location of the child's panic is known, the panic is
+    /// reported the way it would be reported had the test been run
+    /// in-process, i.e., as having happened at the child's location.
+    /// That lets tools jump to the failing assertion.
+    pub fn raise(self) -> ! {
+        let description = self.to_string();
+        match self {
+            Self::Panicked {
+                message,
+                thread,
+                location: Some(location),
+                payload,
+            } => {
+                // Report the child's panic as the default panic hook
+                // would report it in-process, including the child's
+                // location, and unwind without running the hook, which
+                // would report our own location instead.
+                let thread_name = thread.as_deref().unwrap_or("<unnamed>");
+                eprintln!("thread '{thread_name}' panicked at {location}:\n{message}");
+                let () = panic_hook::report_unhooked(&PanicRecord {
+                    thread: thread::current().name().map(str::to_string),
+                    location: Some(location),
+                    message: description.clone(),
+                    payload,
+                });
+
+                let payload: Box<dyn Any + Send> = match payload {
+                    PayloadType::Str => Box::new(&*Box::leak(description.into_boxed_str())),
+                    PayloadType::String => Box::new(description),
+                    PayloadType::Other => Box::new(OpaquePayload { description }),
+                };
+                panic::resume_unwind(payload)
+            }
+            Self::Panicked {
+                payload: PayloadType::Str,
+                ..
+            } => {
+                // The payload has to be `'static`, but we are about to
+                // unwind anyway.
+                let description: &'static str = Box::leak(description.into_boxed_str());
+                panic::panic_any(description)
+            }
+            Self::Panicked {
+                payload: PayloadType::Other,
+                ..
+            } => {
+                // The default panic hook does not know how to print
+                // our payload, so print the description ourselves.
+                eprintln!("test-fork: {description}");
+                panic::panic_any(OpaquePayload { description })
+            }
+            _ => panic::panic_any(description),
+        }
+    }
+}
+
+impl Display for ChildFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Panicked {
+                message,
+                thread,
+                location,
+                payload: _,
+            } => {
+                let thread = thread.as_deref().unwrap_or("<unnamed>");
+                write!(f, "child panicked: thread '{thread}' panicked")?;
+                if let Some(location) = location {
+                    write!(f, " at {location}")?;
+                }
+                write!(f, ": {message}")
+            }
+            Self::Signaled { signal } => write!(f, "child was terminated by signal {signal}"),
+            Self::Crashed { code, name } => {
+                write!(f, "child crashed with status {code:#010X}")?;
+                if let Some(name) = name {
+                    write!(f, " ({name})")?;
+                }
+                Ok(())
+            }
+            Self::ExitCode { code } => {
+                write!(f, "child exited unsuccessfully with exit code {code}")
+            }
+            Self::TimedOut { elapsed, stacks } => {
+                write!(
+                    f,
+                    "child appears hung: no heartbeat received for {elapsed:?}; killed it"
+                )?;
+                if let Some(stacks) = stacks {
+                    write!(f, "\nstacks of the child at the time:\n{stacks}")?;
+                }
+                Ok(())
+            }
+            Self::Sanitizer { report } => write!(f, "child emitted sanitizer report: {report}"),
+            Self::Leaked { processes } => {
+                write!(f, "child leaked processes: {}", processes.join(", "))
+            }
+            Self::OutputMismatch { path, difference } => write!(
+                f,
+                "child output does not match golden file {} (set TEST_FORK_UPDATE_GOLDEN=1 to update)\n{difference}",
+                path.display()
+            ),
+            Self::Variants { total, failures } => {
+                write!(f, "{} of {total} children failed", failures.len())?;
+                for (label, failure) in failures {
+                    write!(f, "\n  {label}: {failure}")?;
+                }
+                Ok(())
+            }
+            Self::SpawnFailed(err) => write!(f, "failed to spawn child: {err}"),
+        }
+    }
+}
+
+impl StdError for ChildFailure {}
+
+impl From<Error> for ChildFailure {
+    fn from(other: Error) -> Self {
+        Self::SpawnFailed(other)
+    }
+}
+
+
+/// The payload of a panic raised by [`ChildFailure::raise`] for a
+/// child that panicked with a payload other than a `&str` or `String`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpaquePayload {
+    /// The description of the failure.
+    description: String,
+}
+
+impl OpaquePayload {
+    /// Retrieve the description of the failure.
+    #[inline]
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+impl Display for OpaquePayload {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(&self.description)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that we interpret `NTSTATUS` exit codes correctly.
+    #[test]
+    fn ntstatus_crash_detection() {
+        let crash = ChildFailure::crash(0xC000_0005_u32 as i32).unwrap();
+        assert_eq!(
+            crash.to_string(),
+            "child crashed with status 0xC0000005 (STATUS_ACCESS_VIOLATION)"
+        );
+
+        let crash = ChildFailure::crash(0xC000_0001_u32 as i32).unwrap();
+        assert_eq!(crash.to_string(), "child crashed with status 0xC0000001");
+
+        assert!(ChildFailure::crash(0x8000_0003_u32 as i32).is_some());
+        assert!(ChildFailure::crash(70).is_none());
+        assert!(ChildFailure::crash(1).is_none());
+    }
+
+    /// Check that a child aborting due to a panic, as happens with
+    /// `panic = "abort"`, is classified as having panicked.
+    #[cfg(unix)]
+    #[test]
+    fn abort_classification() {
+        use std::os::unix::process::ExitStatusExt as _;
+
+        let panic = PanicRecord {
+            thread: Some("main".to_string()),
+            location: None,
+            message: "boom".to_string(),
+            payload: PayloadType::Str,
+        };
+        let aborted = ExitStatus::from_raw(libc::SIGABRT);
+
+        let failure = ChildFailure::classify(aborted, vec![panic.clone()]).unwrap();
+        assert!(
+            matches!(&failure, ChildFailure::Panicked { message, .. } if message == "boom"),
+            "{failure:?}"
+        );
+
+        let failure = ChildFailure::classify(aborted, Vec::new()).unwrap();
+        assert!(
+            matches!(failure, ChildFailure::Signaled { signal } if signal == libc::SIGABRT),
+            "{failure:?}"
+        );
+
+        let killed = ExitStatus::from_raw(libc::SIGKILL);
+        let failure = ChildFailure::classify(killed, vec![panic]).unwrap();
+        assert!(
+            matches!(failure, ChildFailure::Signaled { signal } if signal == libc::SIGKILL),
+            "{failure:?}"
+        );
+    }
+
+    /// Check that the payload type of a child's panic is preserved
+    /// when raising the failure.
+    #[test]
+    fn panic_payload_raising() {
+        let panicked = |payload| ChildFailure::Panicked {
+            message: "boom".to_string(),
+            thread: None,
+            location: None,
+            payload,
+        };
+
+        let payload = panic::catch_unwind(|| panicked(PayloadType::Str).raise()).unwrap_err();
+        let message = payload.downcast_ref::<&str>().unwrap();
+        assert_eq!(
+            *message,
+            "child panicked: thread '<unnamed>' panicked: boom"
+        );
+
+        let payload = panic::catch_unwind(|| panicked(PayloadType::String).raise()).unwrap_err();
+        assert!(payload
+            .downcast_ref::<String>()
+            .unwrap()
+            .ends_with(": boom"));
+
+        let payload = panic::catch_unwind(|| panicked(PayloadType::Other).raise()).unwrap_err();
+        let opaque = payload.downcast_ref::<OpaquePayload>().unwrap();
+        assert!(opaque.description().ends_with(": boom"));
+
+        let payload =
+            panic::catch_unwind(|| ChildFailure::ExitCode { code: 1 }.raise()).unwrap_err();
+        assert_eq!(
+            payload.downcast_ref::<String>().unwrap(),
+            "child exited unsuccessfully with exit code 1"
+        );
+    }
+
+    /// Check that the payload type of a child's panic is preserved
+    /// when raising the failure as having happened at the child's
+    /// location.
+    #[test]
+    fn located_panic_payload_raising() {
+        let panicked = |payload| ChildFailure::Panicked {
+            message: "boom".to_string(),
+            thread: Some("main".to_string()),
+            location: Some("src/lib.rs:42:7".to_string()),
+            payload,
+        };
+
+        let payload = panic::catch_unwind(|| panicked(PayloadType::Str).raise()).unwrap_err();
+        let message = payload.downcast_ref::<&str>().unwrap();
+        assert_eq!(
+            *message,
+            "child panicked: thread 'main' panicked at src/lib.rs:42:7: boom"
+        );
+
+        let payload = panic::catch_unwind(|| panicked(PayloadType::String).raise()).unwrap_err();
+        assert!(payload
+            .downcast_ref::<String>()
+            .unwrap()
+            .ends_with(": boom"));
+
+        let payload = panic::catch_unwind(|| panicked(PayloadType::Other).raise()).unwrap_err();
+        let opaque = payload.downcast_ref::<OpaquePayload>().unwrap();
+        assert!(opaque.description().ends_with(": boom"));
+    }
+}
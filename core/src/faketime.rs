@@ -0,0 +1,112 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Running child processes with a fake clock.
+//!
+//! The child is launched with [libfaketime] preloaded, which intercepts
+//! the time related functions of the C library and reports a time
+//! derived from the specification in the `FAKETIME` environment
+//! variable (e.g., `@2000-01-01 00:00:00` for a fixed start time or
+//! `+2d` for an offset). Because the library gets preloaded into the
+//! child only, the parent and with it the test harness keep seeing the
+//! real time.
+//!
+//! [libfaketime]: https://github.com/wolfcw/libfaketime
+
+use std::env;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+
+/// The environment variable providing the path to libfaketime.
+const LIB_ENV: &str = "TEST_FORK_FAKETIME_LIB";
+/// The environment variable libfaketime reads the fake time
+/// specification from.
+const FAKETIME_ENV: &str = "FAKETIME";
+/// The environment variable listing the libraries to preload.
+const PRELOAD_ENV: &str = "LD_PRELOAD";
+/// The locations libfaketime is commonly installed to.
+const LIB_CANDIDATES: [&str; 5] = [
+    "/usr/lib/x86_64-linux-gnu/faketime/libfaketime.so.1",
+    "/usr/lib/aarch64-linux-gnu/faketime/libfaketime.so.1",
+    "/usr/lib64/faketime/libfaketime.so.1",
+    "/usr/lib/faketime/libfaketime.so.1",
+    "/usr/local/lib/faketime/libfaketime.so.1",
+];
+
+
+/// Find libfaketime, preferring the path provided via the
+/// `TEST_FORK_FAKETIME_LIB` environment variable.
+pub(crate) fn find_lib() -> Option<PathBuf> {
+    if let Some(lib) = env::var_os(LIB_ENV) {
+        return Some(PathBuf::from(lib))
+    }
+
+    LIB_CANDIDATES
+        .iter()
+        .map(PathBuf::from)
+        .find(|lib| lib.is_file())
+}
+
+/// Configure the child process spawned by `command` to run with the
+/// clock faked as per `spec`, using the libfaketime at `lib`.
+///
+/// Libraries the child would preload otherwise are preserved.
+pub(crate) fn configure(command: &mut Command, spec: &str, lib: &Path) {
+    let preload = command
+        .get_envs()
+        .find(|(key, _value)| *key == PRELOAD_ENV)
+        .map(|(_key, value)| value.map(OsStr::to_os_string))
+        .unwrap_or_else(|| env::var_os(PRELOAD_ENV))
+        .filter(|preload| !preload.is_empty());
+
+    let mut libs = OsString::from(lib);
+    if let Some(preload) = preload {
+        libs.push(":");
+        libs.push(preload);
+    }
+    command.env(PRELOAD_ENV, libs).env(FAKETIME_ENV, spec);
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Look up the value of the given variable in `command`'s
+    /// environment.
+    fn get_env<'cmd>(command: &'cmd Command, var: &str) -> Option<&'cmd OsStr> {
+        command
+            .get_envs()
+            .find(|(key, _value)| *key == var)
+            .and_then(|(_key, value)| value)
+    }
+
+    /// Check that the child is configured for running with a fake
+    /// clock, preserving libraries preloaded already.
+    #[test]
+    fn configuration() {
+        let lib = Path::new("/lib/libfaketime.so.1");
+        let mut command = Command::new("true");
+        command.env(PRELOAD_ENV, "/lib/libfoo.so");
+        let () = configure(&mut command, "+2d", lib);
+
+        assert_eq!(
+            get_env(&command, PRELOAD_ENV),
+            Some(OsStr::new("/lib/libfaketime.so.1:/lib/libfoo.so"))
+        );
+        assert_eq!(get_env(&command, FAKETIME_ENV), Some(OsStr::new("+2d")));
+
+        let mut command = Command::new("true");
+        command.env(PRELOAD_ENV, "");
+        let () = configure(&mut command, "@2000-01-01 00:00:00", lib);
+        assert_eq!(
+            get_env(&command, PRELOAD_ENV),
+            Some(OsStr::new("/lib/libfaketime.so.1"))
+        );
+    }
+}
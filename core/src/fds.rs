@@ -0,0 +1,230 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Passing of open file descriptors from the parent to the child.
+//!
+//! File descriptors are sent over a Unix domain socket as `SCM_RIGHTS`
+//! ancillary data, along with their count as regular payload. The
+//! child ends up with duplicates of the parent's descriptors, referring
+//! to the same open file descriptions.
+
+use std::env;
+use std::error::Error;
+use std::ffi::c_int;
+use std::ffi::c_uint;
+use std::io;
+use std::mem;
+use std::os::fd::AsRawFd as _;
+use std::os::fd::BorrowedFd;
+use std::os::fd::FromRawFd as _;
+use std::os::fd::OwnedFd;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process;
+use std::ptr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+
+/// The maximum number of file descriptors that can be passed at once,
+/// corresponding to Linux's `SCM_MAX_FD`.
+pub(crate) const MAX_FDS: usize = 253;
+
+/// The flags used for receiving file descriptors.
+#[cfg(any(target_os = "android", target_os = "freebsd", target_os = "linux"))]
+const RECV_FLAGS: c_int = libc::MSG_CMSG_CLOEXEC;
+/// The flags used for receiving file descriptors.
+#[cfg(not(any(target_os = "android", target_os = "freebsd", target_os = "linux")))]
+const RECV_FLAGS: c_int = 0;
+
+
+/// Create a unique path for the socket used for passing file
+/// descriptors to a child.
+pub(crate) fn socket_path() -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    env::temp_dir().join(format!(
+        "test-fork-fds-{}-{}",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Convert `value` into the integer type `T`, whose definition may
+/// vary between platforms.
+fn convert<T, U>(value: U) -> io::Result<T>
+where
+    T: TryFrom<U>,
+    T::Error: Into<Box<dyn Error + Send + Sync>>,
+{
+    T::try_from(value).map_err(io::Error::other)
+}
+
+/// Calculate the size of the ancillary data buffer for `count` file
+/// descriptors, in bytes.
+fn control_space(count: usize) -> io::Result<usize> {
+    let len = c_uint::try_from(count * mem::size_of::<c_int>()).map_err(io::Error::other)?;
+    // SAFETY: `CMSG_SPACE` merely performs arithmetic.
+    let space = unsafe { libc::CMSG_SPACE(len) };
+    usize::try_from(space).map_err(io::Error::other)
+}
+
+/// Allocate a suitably aligned ancillary data buffer of at least
+/// `space` bytes.
+fn control_buffer(space: usize) -> Vec<u64> {
+    vec![0; space.div_ceil(mem::size_of::<u64>())]
+}
+
+
+/// Send `fds` over `stream`.
+pub(crate) fn send(stream: &UnixStream, fds: &[BorrowedFd<'_>]) -> io::Result<()> {
+    if fds.len() > MAX_FDS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot pass more than {MAX_FDS} file descriptors"),
+        ))
+    }
+
+    let mut payload = u32::try_from(fds.len())
+        .map_err(io::Error::other)?
+        .to_le_bytes();
+    let mut iov = libc::iovec {
+        iov_base: payload.as_mut_ptr().cast(),
+        iov_len: payload.len(),
+    };
+    // SAFETY: `msghdr` is plain data, for which all zeroes is a valid
+    //         value.
+    let mut msg = unsafe { mem::zeroed::<libc::msghdr>() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+
+    let space = control_space(fds.len())?;
+    let mut control = control_buffer(space);
+    if !fds.is_empty() {
+        msg.msg_control = control.as_mut_ptr().cast();
+        msg.msg_controllen = convert(space)?;
+
+        let len =
+            c_uint::try_from(fds.len() * mem::size_of::<c_int>()).map_err(io::Error::other)?;
+        // SAFETY: `msg` references a control buffer large enough for a
+        //         header, so the result is a valid pointer.
+        let cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+        // SAFETY: `cmsg` points into our zero initialized control
+        //         buffer, which is not otherwise referenced.
+        let header = unsafe { &mut *cmsg };
+        header.cmsg_level = libc::SOL_SOCKET;
+        header.cmsg_type = libc::SCM_RIGHTS;
+        // SAFETY: `CMSG_LEN` merely performs arithmetic.
+        header.cmsg_len = convert(unsafe { libc::CMSG_LEN(len) })?;
+
+        // SAFETY: `cmsg` is a valid header pointer.
+        let data = unsafe { libc::CMSG_DATA(cmsg) }.cast::<c_int>();
+        for (i, fd) in fds.iter().enumerate() {
+            // SAFETY: The control buffer has room for all descriptors
+            //         after the header, but the data may be unaligned.
+            let () = unsafe { data.wrapping_add(i).write_unaligned(fd.as_raw_fd()) };
+        }
+    }
+
+    // SAFETY: `msg` and everything it references is valid for the
+    //         duration of the call.
+    let rc = unsafe { libc::sendmsg(stream.as_raw_fd(), &msg, 0) };
+    match usize::try_from(rc) {
+        Ok(sent) if sent == payload.len() => Ok(()),
+        Ok(_) => Err(io::Error::from(io::ErrorKind::WriteZero)),
+        Err(_) => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Receive file descriptors sent via [`send`] from `stream`.
+pub(crate) fn receive(stream: &UnixStream) -> io::Result<Vec<OwnedFd>> {
+    let mut payload = [0; 4];
+    let mut iov = libc::iovec {
+        iov_base: payload.as_mut_ptr().cast(),
+        iov_len: payload.len(),
+    };
+    // SAFETY: `msghdr` is plain data, for which all zeroes is a valid
+    //         value.
+    let mut msg = unsafe { mem::zeroed::<libc::msghdr>() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+
+    let space = control_space(MAX_FDS)?;
+    let mut control = control_buffer(space);
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = convert(space)?;
+
+    // SAFETY: `msg` and everything it references is valid for the
+    //         duration of the call.
+    let rc = unsafe { libc::recvmsg(stream.as_raw_fd(), &mut msg, RECV_FLAGS) };
+    let received = usize::try_from(rc).map_err(|_| io::Error::last_os_error())?;
+
+    let mut fds = Vec::new();
+    // SAFETY: `msg` references a valid control buffer, filled in by
+    //         `recvmsg`.
+    let cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+    if !cmsg.is_null() {
+        // SAFETY: `cmsg` is non-null and so points to a valid header.
+        let header = unsafe { &*cmsg };
+        if header.cmsg_level == libc::SOL_SOCKET && header.cmsg_type == libc::SCM_RIGHTS {
+            // SAFETY: `CMSG_LEN` merely performs arithmetic.
+            let offset = convert::<usize, _>(unsafe { libc::CMSG_LEN(0) })?;
+            let len = convert::<usize, _>(header.cmsg_len)?;
+            let count = len.saturating_sub(offset) / mem::size_of::<c_int>();
+            // SAFETY: `cmsg` is a valid header pointer.
+            let data = unsafe { libc::CMSG_DATA(cmsg) }.cast::<c_int>();
+            for i in 0..count {
+                // SAFETY: The header reports `count` descriptors
+                //         following it, but they may be unaligned.
+                let fd = unsafe { ptr::read_unaligned(data.wrapping_add(i)) };
+                // SAFETY: The kernel installed a new file descriptor
+                //         for us, owned by nobody else.
+                let () = fds.push(unsafe { OwnedFd::from_raw_fd(fd) });
+            }
+        }
+    }
+
+    if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+        return Err(io::Error::other("file descriptors got truncated"))
+    }
+    let expected = u32::from_le_bytes(payload);
+    if received != payload.len() || u32::try_from(fds.len()).ok() != Some(expected) {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof))
+    }
+    Ok(fds)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs::File;
+    use std::io::Read as _;
+    use std::io::Write as _;
+    use std::os::fd::AsFd as _;
+
+
+    /// Check that file descriptors can be passed over a socket.
+    #[test]
+    fn fd_passing() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (mut reader, writer) = UnixStream::pair().unwrap();
+
+        let () = send(&sender, &[writer.as_fd(), writer.as_fd()]).unwrap();
+        let fds = receive(&receiver).unwrap();
+        assert_eq!(fds.len(), 2);
+        drop(writer);
+
+        for fd in fds {
+            let () = File::from(fd).write_all(b"x").unwrap();
+        }
+        let mut data = Vec::new();
+        let _count = reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"xx");
+
+        let () = send(&sender, &[]).unwrap();
+        let fds = receive(&receiver).unwrap();
+        assert!(fds.is_empty());
+    }
+}
@@ -10,49 +10,447 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::env;
-use std::io::Read;
-use std::io::Write as _;
-use std::net::TcpListener;
-use std::net::TcpStream;
 use std::panic;
+use std::path::Path;
 use std::process;
-use std::process::Child;
-use std::process::Command;
 use std::process::ExitCode;
+use std::process::Output;
 use std::process::Stdio;
 use std::process::Termination;
+use std::result;
+use std::sync::OnceLock;
+use std::time::Duration;
 
+use crate::artifacts;
+use crate::artifacts::Artifacts;
+use crate::backend::CommandBackend;
+use crate::backend::ProcessBackend;
+use crate::backend::Retrying;
+use crate::backend::DEFAULT_SPAWN_BACKOFF;
+use crate::backend::DEFAULT_SPAWN_RETRIES;
+use crate::builder::Builder;
+#[cfg(target_os = "linux")]
+use crate::caps;
+use crate::child;
+use crate::child::ChildWrapper;
+use crate::child::POLL_INTERVAL;
 use crate::cmdline;
+use crate::cmdline::UnknownFlags;
+use crate::color;
+use crate::color::Color;
+use crate::debug;
+use crate::dry_run;
+use crate::entry;
 use crate::error::Result;
+use crate::exe;
+use crate::failure::ChildFailure;
+use crate::golden;
+use crate::heartbeat;
+use crate::heartbeat::Monitor;
+use crate::info;
+use crate::junit;
+use crate::leak;
+use crate::list;
+use crate::outcome::ForkOutcome;
+use crate::panic_hook;
+#[cfg(all(target_os = "linux", feature = "perf"))]
+use crate::perf;
+use crate::persist::Persisted;
+#[cfg(all(unix, feature = "pty"))]
+use crate::pty;
+#[cfg(all(unix, feature = "raw-fork"))]
+use crate::raw;
+use crate::resource;
+use crate::sanitizer;
+use crate::sched;
+use crate::seed::seed;
+use crate::seed::SEED_ENV;
+use crate::serial;
+use crate::stack;
+use crate::summary;
+#[cfg(feature = "tracing")]
+use crate::trace;
 
 
-const OCCURS_ENV: &str = "TEST_FORK_OCCURS";
-const OCCURS_TERM_LENGTH: usize = 17; /* ':' plus 16 hexits */
+pub(crate) const OCCURS_ENV: &str = "TEST_FORK_OCCURS";
+const MAX_DEPTH_ENV: &str = "TEST_FORK_MAX_DEPTH";
+const DEFAULT_MAX_DEPTH: usize = 16;
+/// The exit code of a child whose test panicked.
+pub(crate) const EX_SOFTWARE: i32 = 70;
+/// The environment variable used by LLVM's source based code coverage
+/// instrumentation to determine where to write profiling data to.
+pub(crate) const LLVM_PROFILE_FILE_ENV: &str = "LLVM_PROFILE_FILE";
+/// Whether the target supports spawning child processes.
+///
+/// On targets that don't (e.g., WebAssembly ones), tests are run
+/// in-process instead.
+pub(crate) const PROCESS_SUPPORT: bool = !cfg!(target_family = "wasm");
 
 
-fn supervise_child(child: Child) {
-    let output = child.wait_with_output().expect("failed to wait for child");
-    assert!(
-        output.status.success(),
-        "child exited unsuccessfully with {}",
-        output.status,
+thread_local! {
+    /// The number of times each fork point has been encountered on the
+    /// current thread, keyed by test name and fork ID.
+    static OCCURRENCES: RefCell<HashMap<(String, String), usize>> = RefCell::new(HashMap::new());
+}
+
+
+/// Split the value of the `TEST_FORK_OCCURS` environment variable into
+/// the individual fork IDs (without leading `:`) it is comprised of,
+/// along with the occurrence index of the respective fork point.
+///
+/// Each term has the form `:<id>` (for the first occurrence) or
+/// `:<id>@<occurrence>`.
+pub(crate) fn occurrences(occurs: &str) -> impl Iterator<Item = (&str, usize)> {
+    occurs
+        .split(':')
+        .skip(1)
+        .map(|term| match term.split_once('@') {
+            Some((id, occurrence)) => {
+                let occurrence = occurrence.parse().unwrap_or_else(|err| {
+                    panic!("test-fork: encountered invalid {OCCURS_ENV} term `{term}`: {err}")
+                });
+                (id, occurrence)
+            }
+            None => (term, 0),
+        })
+}
+
+/// Retrieve the command line arguments to pass on to child processes.
+///
+/// They are fixed for the lifetime of the process (for a given
+/// treatment of unknown flags) and only determined once, as they are
+/// needed for every child spawned.
+fn base_args(unknown_flags: UnknownFlags) -> Result<&'static [String]> {
+    static BASES: [OnceLock<Vec<String>>; 3] = [OnceLock::new(), OnceLock::new(), OnceLock::new()];
+
+    let base = BASES
+        .get(unknown_flags.index())
+        .expect("encountered unexpected unknown flag treatment");
+    if let Some(base) = base.get() {
+        return Ok(base)
+    }
+
+    let args = if entry::custom_main() {
+        // A custom `main` function interprets its arguments as it sees
+        // fit and the child has to see the same ones.
+        env::args().skip(1).collect()
+    } else {
+        cmdline::strip_cmdline(env::args(), unknown_flags)?
+    };
+    log!(
+        "derived child arguments {args:?} from {:?}",
+        env::args().skip(1).collect::<Vec<_>>()
     );
+    Ok(base.get_or_init(|| args))
+}
+
+/// Convert the [`ExitCode`] reported by a test into the exit code of
+/// the child process running it.
+fn exit_code(code: ExitCode) -> i32 {
+    // `ExitCode` provides no stable way of retrieving the value it
+    // represents, but we can compare it against all candidates.
+    (0..=u8::MAX)
+        .find(|candidate| ExitCode::from(*candidate) == code)
+        .map(i32::from)
+        .unwrap_or(EX_SOFTWARE)
+}
+
+/// Check whether the current process is a child process spawned for
+/// the fork point with the given ID (or one of its descendants).
+pub(crate) fn is_child(fork_id: &str) -> bool {
+    let occurs = env::var(OCCURS_ENV).unwrap_or_default();
+    let id = fork_id.strip_prefix(':').unwrap_or(fork_id);
+    let is_child = occurrences(&occurs).any(|(occur, _)| occur == id);
+    is_child
+}
+
+/// Retrieve the maximum fork nesting depth, as configured via the
+/// `TEST_FORK_MAX_DEPTH` environment variable.
+fn max_depth() -> usize {
+    env::var(MAX_DEPTH_ENV)
+        .map(|depth| {
+            depth.parse().unwrap_or_else(|err| {
+                panic!("test-fork: failed to parse {MAX_DEPTH_ENV} value `{depth}`: {err}")
+            })
+        })
+        .unwrap_or(DEFAULT_MAX_DEPTH)
+}
+
+/// Format the occurrence chain encoded in the value of the
+/// `TEST_FORK_OCCURS` environment variable in human readable form.
+fn format_chain(occurs: &str) -> String {
+    let chain = occurrences(occurs)
+        .map(|(id, occurrence)| format!(":{id} (occurrence {occurrence})"))
+        .collect::<Vec<_>>();
+
+    if chain.is_empty() {
+        "<empty>".to_string()
+    } else {
+        chain.join(" -> ")
+    }
+}
+
+/// Derive the `LLVM_PROFILE_FILE` value to use for a child process
+/// from the one `profile_file` used by the parent, if necessary.
+///
+/// Unless the pattern already contains the process ID (`%p`), all
+/// children would write their coverage data to the same file,
+/// clobbering each other's data. Hence, we insert the process ID into
+/// the file name.
+fn child_profile_file(profile_file: &str) -> Option<String> {
+    if profile_file.contains("%p") {
+        return None
+    }
+
+    let child_file = match profile_file.strip_suffix(".profraw") {
+        Some(stem) => format!("{stem}-%p.profraw"),
+        None => format!("{profile_file}-%p"),
+    };
+    Some(child_file)
+}
 
+/// Retrieve the (zero based) index of the current occurrence of the
+/// given fork point for the given test, counting it as encountered.
+fn next_occurrence(test_name: &str, id: &str) -> usize {
+    OCCURRENCES.with_borrow_mut(|occurrences| {
+        let count = occurrences
+            .entry((test_name.to_owned(), id.to_owned()))
+            .or_default();
+        let occurrence = *count;
+        *count += 1;
+        occurrence
+    })
+}
+
+
+/// Wait for the child to exit, declaring it hung if the heartbeat
+/// `monitor` says so.
+///
+/// If the child was found to be hung, its stacks are captured and it
+/// is killed. In that case the time since the last heartbeat is
+/// returned, along with the stacks, if they could be captured.
+fn wait_with_heartbeat(
+    child: &mut ChildWrapper,
+    mut monitor: Monitor,
+) -> Option<(Duration, Option<String>)> {
+    let () = monitor.start();
+    loop {
+        let status = child
+            .wait_timeout(POLL_INTERVAL)
+            .expect("failed to wait for child");
+        if status.is_some() {
+            break None
+        }
+
+        let hung = monitor
+            .check()
+            .expect("failed to check for child heartbeat");
+        if let Some(elapsed) = hung {
+            let pid = child.id();
+            let state = heartbeat::process_state(pid)
+                .map(|state| format!(", state: {state}"))
+                .unwrap_or_default();
+            eprintln!("test-fork: capturing stacks of hung child (pid {pid}{state})");
+            let stacks = stack::capture(pid);
+            eprintln!("test-fork: killing hung child (pid {pid})");
+            let () = child.report_timeout(elapsed);
+            let _result = child.kill_group();
+            let _status = child.wait().expect("failed to wait for child");
+            break Some((elapsed, stacks))
+        }
+    }
+}
+
+/// Prefix each line of `s` with `[<prefix>] `, if a prefix is provided.
+fn prefix_lines<'s>(s: Cow<'s, str>, prefix: Option<&str>) -> Cow<'s, str> {
+    match prefix {
+        Some(prefix) => s
+            .split_inclusive('\n')
+            .map(|line| format!("[{prefix}] {line}"))
+            .collect::<String>()
+            .into(),
+        None => s,
+    }
+}
+
+/// Forward the captured output of the child running the given test to
+/// our own output, as configured by `supervision`.
+pub(crate) fn forward_output(
+    stdout: &[u8],
+    stderr: &[u8],
+    test_name: &str,
+    supervision: Supervision,
+) {
     // Make sure to forward output we captured to our own output, using
     // print! and eprint! macros, which hook into the test output
     // capture mechanism, to mimic default behavior.
+    let prefix = supervision.prefix_output.then_some(test_name);
+    let relay = |output| {
+        let s = String::from_utf8_lossy(output);
+        let s = if supervision.color == Color::Never {
+            color::strip(s)
+        } else {
+            s
+        };
+        prefix_lines(s, prefix)
+    };
 
-    if !output.stdout.is_empty() {
-        let s = String::from_utf8_lossy(&output.stdout);
+    if !stdout.is_empty() {
+        let s = relay(stdout);
         print!("{s}");
     }
-    if !output.stderr.is_empty() {
-        let s = String::from_utf8_lossy(&output.stderr);
+    if !stderr.is_empty() {
+        let s = relay(stderr);
         eprint!("{s}");
     }
 }
 
+/// Options controlling the supervision of a child process.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Supervision {
+    /// Whether to fail if the child's stderr contains a sanitizer
+    /// report.
+    pub sanitizers: bool,
+    /// Whether to fail if the child left behind any processes.
+    pub leaks: bool,
+    /// Whether to capture the child's output instead of forwarding it.
+    pub capture_output: bool,
+    /// The maximum number of bytes of output to retain per stream.
+    pub output_limit: Option<usize>,
+    /// Whether to prefix forwarded output lines with the test's name.
+    pub prefix_output: bool,
+    /// The treatment of colors in the child's output.
+    pub color: Color,
+    /// Whether to persist the child's output to files.
+    pub persist_output: bool,
+    /// The treatment of the child's artifact directory.
+    pub artifacts: Artifacts,
+}
+
+
+/// Wait for the child to exit, classifying its failure, if any.
+///
+/// Unless output capturing is enabled, the child's output is forwarded
+/// to our own if it exited successfully.
+#[expect(clippy::unwrap_in_result)]
+pub(crate) fn supervise_child(
+    mut child: ChildWrapper,
+    monitor: Option<Monitor>,
+    supervision: Supervision,
+) -> result::Result<ForkOutcome, ChildFailure> {
+    let test_name = child.test_name().to_string();
+    let pid = child.id();
+    let start = child.start();
+    let persisted = if supervision.persist_output {
+        Persisted::create(&test_name).map(|(persisted, stdout, stderr)| {
+            let () = child.tee_output(stdout, stderr);
+            persisted
+        })
+    } else {
+        None
+    };
+    if let Some(limit) = supervision.output_limit {
+        let () = child.limit_output(limit);
+    }
+
+    let hung = match monitor {
+        Some(monitor) => wait_with_heartbeat(&mut child, monitor),
+        None => {
+            let _status = child.wait().expect("failed to wait for child");
+            None
+        }
+    };
+    let duration = start.elapsed();
+    // Counters prepared for the child, if any, have their final values
+    // now that it exited.
+    #[cfg(all(target_os = "linux", feature = "perf"))]
+    let counters = perf::Collector::take().map(|collector| collector.read());
+    // Leaked processes may hold on to the child's output pipes, so we
+    // have to take care of them before collecting the output.
+    let leaked = if supervision.leaks {
+        let leaked = leak::find_leaked(pid);
+        if !leaked.is_empty() {
+            log!("killing processes leaked by child {pid}: {leaked:?}");
+            let _result = child::kill_tree(pid);
+        }
+        leaked
+    } else {
+        Vec::new()
+    };
+    let mut output = child.wait_with_output().expect("failed to wait for child");
+    log!(
+        "child {pid} of `{test_name}` exited with {} after {duration:?}",
+        output.status
+    );
+    let (panics, stderr) = panic_hook::extract(&output.stderr);
+    #[cfg(feature = "tracing")]
+    let stderr = trace::forward(pid, &stderr);
+    output.stderr = stderr;
+
+    let mut failure = match hung {
+        Some((elapsed, stacks)) => Some(ChildFailure::TimedOut { elapsed, stacks }),
+        None => ChildFailure::classify(output.status, panics),
+    };
+    if supervision.sanitizers && matches!(failure, None | Some(ChildFailure::ExitCode { .. })) {
+        if let Some(report) = sanitizer::find_report(&output.stderr) {
+            failure = Some(ChildFailure::Sanitizer { report });
+        }
+    }
+    if failure.is_none() && !leaked.is_empty() {
+        failure = Some(ChildFailure::Leaked { processes: leaked });
+    }
+    let () = junit::report(&test_name, pid, duration, failure.as_ref(), &output);
+    let () = summary::record(&test_name, duration, failure.as_ref());
+    if let Some(persisted) = persisted {
+        let () = persisted.finish(failure.is_some());
+    }
+    let () = artifacts::finish(&test_name, supervision.artifacts, failure.is_some());
+    // The time reported by the test harness includes the overhead of
+    // spawning and supervising the child, so report the time the child
+    // itself ran for as well.
+    if cmdline::report_time() {
+        eprintln!(
+            "test-fork: child of `{test_name}` ran for <{:.3}s>",
+            duration.as_secs_f64()
+        );
+    }
+
+    if let Some(failure) = failure {
+        eprintln!(
+            "test-fork: child ran with seed {seed}; set {SEED_ENV}={seed} to reproduce",
+            seed = seed()
+        );
+        if matches!(failure, ChildFailure::TimedOut { .. }) {
+            let () = forward_output(&output.stdout, &output.stderr, &test_name, supervision);
+        }
+        return Err(failure)
+    }
+
+    let Output {
+        status,
+        stdout,
+        stderr,
+    } = output;
+    let mut outcome = ForkOutcome {
+        status,
+        duration,
+        #[cfg(all(target_os = "linux", feature = "perf"))]
+        perf_counters: counters,
+        ..Default::default()
+    };
+    if supervision.capture_output {
+        outcome.stdout = stdout;
+        outcome.stderr = stderr;
+    } else {
+        let () = forward_output(&stdout, &stderr, &test_name, supervision);
+    }
+    Ok(outcome)
+}
+
 
 /// Simulate a process fork.
 ///
@@ -60,16 +458,23 @@ fn supervise_child(child: Child) {
 /// to ensure that the child process, upon starting from the same entry point,
 /// also reaches this same `fork()` call. Recursive forks are supported; the
 /// child branch is taken from all child processes of the fork even if it is
-/// not directly the child of a particular branch. However, encountering the
-/// same fork point more than once in a single execution sequence of a child
-/// process is not (e.g., putting this call in a recursive function) and
-/// results in unspecified behaviour.
+/// not directly the child of a particular branch.
+///
+/// The same fork point may be encountered multiple times by the same
+/// thread (e.g., when calling `fork()` in a loop), in which case one
+/// child process is spawned for each occurrence. Each child re-executes
+/// the test from the start, skipping over all occurrences of the fork
+/// point preceding the one it was spawned for. However, encountering
+/// the same fork point again from *within* the child branch (e.g., by
+/// putting this call in a recursive function) is not supported and
+/// results in a panic.
 ///
 /// `fork_id` is a unique identifier identifying this particular fork location.
 /// This *must* be stable across processes of the same executable; pointers are
 /// not suitable stable, and string constants may not be suitably unique. The
 /// [`fork_id!()`] macro is the recommended way to supply this
-/// parameter.
+/// parameter. If a stable identifier across differently built
+/// executables is required, `fork_id!("my-id")` can be used instead.
 ///
 /// `test_name` must exactly match the full path of the test function being
 /// run.
@@ -77,13 +482,15 @@ fn supervise_child(child: Child) {
 /// If `test` panics, the child process exits with a failure code immediately
 /// rather than let the panic propagate out of the `fork()` call.
 ///
-/// ## Panics
+/// On targets without support for spawning processes (such as
+/// `wasm32-wasip1`), `test` is run in the current process instead,
+/// without any isolation. A panic then propagates out of `fork()` and
+/// a failure reported by `test` results in a panic.
 ///
-/// Panics if the environment indicates that there are already at least 16
-/// levels of fork nesting.
+/// ## Panics
 ///
-/// Panics if `std::env::current_exe()` fails to determine the path to
-/// the current executable.
+/// Panics if the environment indicates that there are already at least
+/// `TEST_FORK_MAX_DEPTH` (16 by default) levels of fork nesting.
 ///
 /// Panics if any argument to the current process is not valid UTF-8.
 pub fn fork<F, T>(fork_id: &str, test_name: &str, test: F) -> Result<()>
@@ -94,74 +501,125 @@ where
     F: Fn() -> T,
     T: Termination,
 {
-    fn no_configure_child(_child: &mut Command) {}
-
-    fork_int(
-        test_name,
-        fork_id,
-        no_configure_child,
-        supervise_child,
-        test,
-    )
+    Builder::new().fork(fork_id, test_name, test)
 }
 
 /// Simulate a process fork.
 ///
 /// This function is similar to [`fork`], except that it allows for data
-/// exchange with the child process.
-#[expect(clippy::panic_in_result_fn, clippy::unwrap_in_result)]
+/// exchange with the child process: `test` is invoked in the child with
+/// a copy of `data` and the data it leaves behind are conveyed back
+/// into `data`. Use [`fork_in_out_vec`] if the child is to return data
+/// of a different size.
 pub fn fork_in_out<F, T>(fork_id: &str, test_name: &str, test: F, data: &mut [u8]) -> Result<()>
 where
     F: Fn(&mut [u8]) -> T,
     T: Termination,
 {
-    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind TCP socket");
-    let addr = listener.local_addr().unwrap();
-    let data_len = data.len();
+    let mut buf = data.to_vec();
+    let () = fork_in_out_vec(fork_id, test_name, |buf| test(buf), &mut buf)?;
+    // The child can't change the length of a slice, so `buf` still has
+    // the size of `data`.
+    let () = data.copy_from_slice(&buf);
+    Ok(())
+}
 
-    fork_int(
+/// Simulate a process fork, exchanging variable length data with the
+/// child process.
+///
+/// This function is similar to [`fork_in_out`], except that the child
+/// is free to change the length of the data, e.g., to return a
+/// serialized result of a priori unknown size.
+pub fn fork_in_out_vec<F, T>(
+    fork_id: &str,
+    test_name: &str,
+    test: F,
+    data: &mut Vec<u8>,
+) -> Result<()>
+where
+    F: Fn(&mut Vec<u8>) -> T,
+    T: Termination,
+{
+    Builder::new().fork_in_out(fork_id, test_name, test, data)
+}
+
+/// Simulate a process fork, with full control over how the child
+/// process is spawned and supervised.
+///
+/// This function is the primitive that [`fork`] and friends are built
+/// on and is meant for building custom integrations (e.g., with other
+/// test frameworks) on top of `test-fork`. A new instance of the
+/// current executable is spawned as the child process, running just
+/// the test named `test_name`, which has to reach this same call again.
+/// The requirements on `fork_id` and `test_name` as well as the
+/// semantics with respect to repeated occurrences of the fork point are
+/// the same as for [`fork`].
+///
+/// In the parent:
+/// - `process_modifier` is invoked with the
+///   [`Command`][process::Command] used for spawning the child, right
+///   before it is spawned; it may be used to adjust the child's
+///   environment, for example
+/// - `in_parent` is invoked with the spawned child, and its result is
+///   returned; it is in charge of waiting for the child and retrieving
+///   its output (e.g., via [`ChildWrapper::wait_with_output`]), the
+///   child's process group gets killed if it is still running once the
+///   [`ChildWrapper`] is dropped
+///
+/// In the child, `in_child` is run and the process exits with the exit
+/// code derived from its result (or a failure code if it panics). The
+/// child's result is communicated to the parent only via its exit
+/// status and output. Neither `process_modifier` nor `in_parent` is
+/// invoked in the child, and occurrences of the fork point skipped by
+/// a child evaluate to `R::default()`.
+///
+/// On targets without support for spawning processes, `in_child` is
+/// run in the current process instead and `R::default()` is returned.
+///
+/// [`Builder::fork_supervised`] provides the same functionality for a
+/// configured [`Builder`], minus the process modifier.
+///
+/// ## Panics
+///
+/// Panics under the same conditions as [`fork`] does.
+pub fn fork_custom<M, P, C, R, T>(
+    fork_id: &str,
+    test_name: &str,
+    process_modifier: M,
+    in_parent: P,
+    in_child: C,
+) -> Result<R>
+where
+    M: FnOnce(&mut process::Command),
+    P: FnOnce(ChildWrapper) -> R,
+    R: Default,
+    T: Termination,
+    C: FnOnce() -> T,
+{
+    let options = LaunchOptions {
+        in_process: false,
+        raw: false,
+        unknown_flags: UnknownFlags::resolve(None),
+        exe: None,
+        backend: Retrying::new(
+            &CommandBackend,
+            DEFAULT_SPAWN_RETRIES,
+            DEFAULT_SPAWN_BACKOFF,
+        ),
+    };
+    fork_int_with(
         test_name,
         fork_id,
-        |cmd| {
-            cmd.env(fork_id, addr.to_string());
-        },
-        |child| {
-            let (mut stream, _addr) = listener
-                .accept()
-                .expect("failed to listen for child connection");
-            let () = stream
-                .write_all(data)
-                .expect("failed to send data to child");
-            let () = stream
-                .read_exact(data)
-                .expect("failed to receive data from child");
-            supervise_child(child)
-        },
-        || {
-            let addr = env::var(fork_id).unwrap_or_else(|err| {
-                panic!("failed to retrieve {fork_id} environment variable: {err}")
-            });
-            let mut stream =
-                TcpStream::connect(addr).expect("failed to establish connection with parent");
-
-            let mut data = Vec::with_capacity(data_len);
-            // SAFETY: The `Vec` contains `data_len` `u8` values, which
-            //         are valid for any bit pattern, so we can safely
-            //         adjust the length.
-            let () = unsafe { data.set_len(data_len) };
-
-            let () = stream
-                .read_exact(&mut data)
-                .expect("failed to receive data from parent");
-            let status = test(&mut data);
-            let () = stream
-                .write_all(&data)
-                .expect("failed to send data to parent");
-            status
-        },
+        &options,
+        process_modifier,
+        in_parent,
+        in_child,
     )
 }
 
+/// Simulate a process fork, spawning a new instance of the current
+/// executable as the child process.
+#[cfg(test)]
 pub(crate) fn fork_int<M, P, C, R, T>(
     test_name: &str,
     fork_id: &str,
@@ -171,7 +629,46 @@ pub(crate) fn fork_int<M, P, C, R, T>(
 ) -> Result<R>
 where
     M: FnOnce(&mut process::Command),
-    P: FnOnce(Child) -> R,
+    P: FnOnce(ChildWrapper) -> R,
+    R: Default,
+    T: Termination,
+    C: FnOnce() -> T,
+{
+    fork_custom(fork_id, test_name, process_modifier, in_parent, in_child)
+}
+
+/// The options controlling how a child process is launched.
+#[derive(Debug)]
+pub(crate) struct LaunchOptions<'a> {
+    /// Whether to run the test in the current process instead of
+    /// launching a child.
+    pub(crate) in_process: bool,
+    /// Whether to create the child via a plain `fork` instead of
+    /// launching a new instance of the executable.
+    pub(crate) raw: bool,
+    /// The treatment of flags of the current process unknown to us.
+    pub(crate) unknown_flags: UnknownFlags,
+    /// The executable to launch instead of the current one, if any.
+    pub(crate) exe: Option<&'a Path>,
+    /// The backend to launch the child with.
+    pub(crate) backend: Retrying<'a>,
+}
+
+
+/// Simulate a process fork, launching the child process as `options`
+/// say.
+pub(crate) fn fork_int_with<M, P, C, R, T>(
+    test_name: &str,
+    fork_id: &str,
+    options: &LaunchOptions<'_>,
+    process_modifier: M,
+    in_parent: P,
+    in_child: C,
+) -> Result<R>
+where
+    M: FnOnce(&mut process::Command),
+    P: FnOnce(ChildWrapper) -> R,
+    R: Default,
     T: Termination,
     C: FnOnce() -> T,
 {
@@ -184,61 +681,211 @@ where
     fork_impl(
         test_name,
         fork_id,
+        options,
         &mut |cmd| process_modifier.take().unwrap()(cmd),
         &mut |child| in_parent.take().unwrap()(child),
         &mut || in_child.take().unwrap()(),
     )
+    // Occurrences of the fork point skipped over by a child process
+    // don't have a result of their own.
+    .map(Option::unwrap_or_default)
+}
+
+/// Run the test in the current process, either because forking was
+/// disabled or on a target that does not support spawning child
+/// processes.
+///
+/// Panics of the test propagate to the caller and a failure reported
+/// by it is turned into one.
+fn run_in_process<T: Termination>(test_name: &str, in_child: &mut dyn FnMut() -> T) {
+    let code = exit_code(in_child().report());
+    assert!(
+        code == 0,
+        "test-fork: test `{test_name}` (run in-process) failed with exit code {code}"
+    );
+}
+
+/// Run the test in the current process, which is a child process,
+/// and exit.
+pub(crate) fn run_child<T: Termination>(in_child: &mut dyn FnMut() -> T) -> ! {
+    let () = panic_hook::install();
+    let () = sched::apply();
+    #[cfg(target_os = "linux")]
+    let () = caps::apply();
+    let () = debug::wait();
+    let () = heartbeat::start();
+    let () = golden::begin();
+
+    match panic::catch_unwind(panic::AssertUnwindSafe(in_child)) {
+        Ok(test_result) => {
+            let () = golden::end();
+            process::exit(exit_code(test_result.report()))
+        }
+        // Assume that the default panic handler already printed something
+        //
+        // We don't use process::abort() since it produces core dumps on
+        // some systems and isn't something more special than a normal
+        // panic. With `panic = "abort"` we never get here, but our
+        // panic hook exits the same way.
+        Err(_) => process::exit(EX_SOFTWARE),
+    }
 }
 
-#[expect(clippy::panic_in_result_fn, clippy::unwrap_in_result)]
+#[expect(clippy::panic_in_result_fn)]
 fn fork_impl<T: Termination, R>(
     test_name: &str,
     fork_id: &str,
+    options: &LaunchOptions<'_>,
     process_modifier: &mut dyn FnMut(&mut process::Command),
-    in_parent: &mut dyn FnMut(Child) -> R,
+    in_parent: &mut dyn FnMut(ChildWrapper) -> R,
     in_child: &mut dyn FnMut() -> T,
-) -> Result<R> {
+) -> Result<Option<R>> {
+    let &LaunchOptions {
+        in_process,
+        raw,
+        unknown_flags,
+        exe,
+        ref backend,
+    } = options;
+
+    if in_process || !PROCESS_SUPPORT {
+        let () = run_in_process(test_name, in_child);
+        return Ok(None)
+    }
+
     let mut occurs = env::var(OCCURS_ENV).unwrap_or_else(|_| String::new());
-    if occurs.contains(fork_id) {
-        match panic::catch_unwind(panic::AssertUnwindSafe(in_child)) {
-            Ok(test_result) => {
-                let rc = if test_result.report() == ExitCode::SUCCESS {
-                    0
-                } else {
-                    70
-                };
-                process::exit(rc)
-            }
-            // Assume that the default panic handler already printed something
-            //
-            // We don't use process::abort() since it produces core dumps on
-            // some systems and isn't something more special than a normal
-            // panic.
-            Err(_) => process::exit(70 /* EX_SOFTWARE */),
+    let id = fork_id.strip_prefix(':').unwrap_or(fork_id);
+    let occurrence = next_occurrence(test_name, id);
+    let expected = occurrences(&occurs)
+        .find(|(occur, _)| *occur == id)
+        .map(|(_, expected)| expected);
+
+    if let Some(expected) = expected {
+        if occurrence < expected {
+            // This occurrence of the fork point is handled by a
+            // different child process.
+            log!(
+                "skipping occurrence {occurrence} of fork point {fork_id} of `{test_name}` \
+                 (running as child for occurrence {expected})"
+            );
+            return Ok(None)
+        } else if occurrence > expected {
+            panic!(
+                "test-fork: fork point {fork_id} of test `{test_name}` encountered again \
+                 while running as its child (occurrence {occurrence}, expected {expected}); \
+                 recursing through the same fork point is not supported\n\
+                 occurrence chain: {}",
+                format_chain(&occurs)
+            );
         }
+
+        log!(
+            "running as child for occurrence {occurrence} of fork point {fork_id} of \
+             `{test_name}` (occurrence chain: {})",
+            format_chain(&occurs)
+        );
+        run_child(in_child)
     } else {
+        if list::requested() {
+            let () = list::emit(test_name, fork_id, occurrence);
+            return Ok(None)
+        }
+
         // Prevent misconfiguration creating a fork bomb
-        if occurs.len() > 16 * OCCURS_TERM_LENGTH {
-            panic!("test-fork: Not forking due to >=16 levels of recursion");
+        let max_depth = max_depth();
+        if occurrences(&occurs).count() >= max_depth {
+            panic!(
+                "test-fork: Not forking due to >={max_depth} levels of recursion \
+                 (adjust via {MAX_DEPTH_ENV})\n\
+                 occurrence chain: {}",
+                format_chain(&occurs)
+            );
         }
 
         occurs.push_str(fork_id);
-        let mut command =
-            process::Command::new(env::current_exe().expect("current_exe() failed, cannot fork"));
+        if occurrence > 0 {
+            occurs.push_str(&format!("@{occurrence}"));
+        }
+        log!(
+            "forking `{test_name}` at occurrence {occurrence} of fork point {fork_id} \
+             (occurrence chain: {})",
+            format_chain(&occurs)
+        );
+
+        let exe = exe::resolve(exe)?;
+        let args = base_args(unknown_flags)?;
+        let mut command = process::Command::new(&*exe);
+        command.args(args);
+        // A custom `main` function is not run by the test harness, so
+        // there is no test to select.
+        if !entry::custom_main() {
+            command.args(cmdline::RUN_TEST_ARGS).arg(test_name);
+        }
         command
-            .args(cmdline::strip_cmdline(env::args())?)
-            .args(cmdline::RUN_TEST_ARGS)
-            .arg(test_name)
             .env(OCCURS_ENV, &occurs)
             .stdin(Stdio::null())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
+        let () = heartbeat::unconfigure(&mut command);
+        let () = golden::unconfigure(&mut command);
+        let () = info::configure(&mut command, test_name);
+        let () = resource::configure(&mut command);
+        command.env(SEED_ENV, seed().to_string());
+        if let Some(profile_file) = env::var(LLVM_PROFILE_FILE_ENV)
+            .ok()
+            .and_then(|profile_file| child_profile_file(&profile_file))
+        {
+            command.env(LLVM_PROFILE_FILE_ENV, profile_file);
+        }
+        // Run the child in a process group of its own, so that we can
+        // easily kill it along with everything it spawned. Note that
+        // the standard library supports doing so using `posix_spawn`,
+        // which is considerably cheaper than `fork` & `exec`. We must
+        // not use `pre_exec` or similar, which would defeat that.
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt as _;
+            command.process_group(0);
+        }
         process_modifier(&mut command);
+        // The serialization locks acquired while configuring the child
+        // have to be held for as long as the child is alive.
+        let locks = serial::take();
+        let env_cleared = dry_run::take_env_cleared();
+        if dry_run::requested() {
+            let () = dry_run::print(test_name, &command, env_cleared);
+            return Ok(None)
+        }
+        log!("spawning child: {}", dry_run::format(&command, env_cleared));
+        let debug = debug::enabled(&command);
+
+        #[cfg(all(unix, feature = "raw-fork"))]
+        if raw {
+            match raw::fork(&command, test_name, fork_id)? {
+                Some(mut child) => {
+                    let () = child.hold_locks(locks);
+                    if debug {
+                        let () = debug::announce(test_name, child.id());
+                    }
+                    return Ok(Some(in_parent(child)))
+                }
+                None => run_child(in_child),
+            }
+        }
+        #[cfg(not(all(unix, feature = "raw-fork")))]
+        let _raw = raw;
 
-        let child = command.spawn()?;
+        let launched = backend.launch(&mut command)?;
+        #[cfg(all(unix, feature = "pty"))]
+        let launched = pty::attach(&mut command, launched);
+        let mut child = ChildWrapper::new(launched, test_name, fork_id);
+        let () = child.hold_locks(locks);
+        if debug {
+            let () = debug::announce(test_name, child.id());
+        }
         let result = in_parent(child);
 
-        Ok(result)
+        Ok(Some(result))
     }
 }
 
@@ -248,20 +895,77 @@ mod test {
     use super::*;
 
 
-    fn wait_for_child_output(child: Child) -> String {
+    fn supervise(child: ChildWrapper) {
+        if let Err(failure) = supervise_child(child, None, Supervision::default()) {
+            panic!("{failure}")
+        }
+    }
+
+    fn wait_for_child_output(child: ChildWrapper) -> String {
         let output = child.wait_with_output().expect("failed to wait for child");
         assert!(output.status.success());
         let output = String::from_utf8(output.stdout).unwrap();
         output
     }
 
+    /// Check that we derive unique coverage profile paths for child
+    /// processes.
+    #[test]
+    fn profile_file_rewriting() {
+        assert_eq!(child_profile_file("cov-%p-%m.profraw"), None);
+        assert_eq!(
+            child_profile_file("target/cov-%m.profraw"),
+            Some("target/cov-%m-%p.profraw".to_string())
+        );
+        assert_eq!(
+            child_profile_file("default"),
+            Some("default-%p".to_string())
+        );
+    }
+
+    /// Check that forwarded output lines get prefixed correctly.
+    #[test]
+    fn output_prefixing() {
+        let prefix = |s: &'static str| prefix_lines(Cow::Borrowed(s), Some("mod::test"));
+        assert_eq!(prefix(""), "");
+        assert_eq!(prefix("foo\n"), "[mod::test] foo\n");
+        assert_eq!(
+            prefix("foo\n\nbar"),
+            "[mod::test] foo\n[mod::test] \n[mod::test] bar"
+        );
+        assert_eq!(prefix_lines(Cow::Borrowed("foo\n"), None), "foo\n");
+    }
+
+    /// Check that the public forking primitive invokes the provided
+    /// functions as documented.
+    #[test]
+    fn custom_fork() {
+        let stdout = fork_custom(
+            fork_id!(),
+            fork_test_name!(custom_fork),
+            |cmd| {
+                cmd.env("TEST_FORK_CUSTOM", "modified");
+            },
+            |child| {
+                let output = child.wait_with_output().unwrap();
+                assert!(output.status.success());
+                String::from_utf8(output.stdout).unwrap()
+            },
+            || {
+                println!("custom: {}", env::var("TEST_FORK_CUSTOM").unwrap());
+            },
+        )
+        .unwrap();
+        assert!(stdout.contains("custom: modified\n"), "{stdout}");
+    }
+
     #[test]
     fn fork_basically_works() {
         fork_int(
             "fork::test::fork_basically_works",
             fork_id!(),
             |_| (),
-            supervise_child,
+            supervise,
             || println!("hello from child"),
         )
         .unwrap()
@@ -279,7 +983,7 @@ mod test {
                     "fork::test::child_output_captured_and_repeated",
                     fork_id!(),
                     |_| (),
-                    supervise_child,
+                    supervise,
                     || println!("hello from child"),
                 )
                 .unwrap()
@@ -289,6 +993,126 @@ mod test {
         assert!(output.contains("hello from child"));
     }
 
+    /// Check that forking works with a user provided fork ID.
+    #[test]
+    fn fork_with_named_id() {
+        fork_int(
+            "fork::test::fork_with_named_id",
+            fork_id!("fork-with-named-id"),
+            |_| (),
+            supervise,
+            || println!("hello from child"),
+        )
+        .unwrap()
+    }
+
+    /// Check that the same fork point can be encountered multiple
+    /// times, with each child running the correct occurrence.
+    #[test]
+    fn fork_in_loop() {
+        for i in 0..3 {
+            fork_int(
+                "fork::test::fork_in_loop",
+                fork_id!(),
+                |_| (),
+                |child| {
+                    let output = wait_for_child_output(child);
+                    assert!(output.contains(&format!("hello from child {i}")));
+                    let others = (0..3).filter(|j| *j != i);
+                    let () = others.for_each(|j| assert!(!output.contains(&format!("child {j}"))));
+                },
+                || println!("hello from child {i}"),
+            )
+            .unwrap();
+        }
+    }
+
+    /// Check that recursing through the same fork point in a child
+    /// results in a descriptive panic.
+    #[test]
+    fn fork_recursion_diagnosed() {
+        fn recurse(depth: usize) {
+            fork_int(
+                "fork::test::fork_recursion_diagnosed",
+                fork_id!("fork-recursion-diagnosed"),
+                |_| (),
+                |child| {
+                    let output = child.wait_with_output().unwrap();
+                    assert_eq!(output.status.code(), Some(70));
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    let expected = "fork point :fork-recursion-diagnosed of test \
+                                    `fork::test::fork_recursion_diagnosed` encountered again";
+                    assert!(stderr.contains(expected), "{stderr}");
+                    let expected = "occurrence chain: :fork-recursion-diagnosed (occurrence 0)";
+                    assert!(stderr.contains(expected), "{stderr}");
+                },
+                || {
+                    if depth == 0 {
+                        recurse(depth + 1)
+                    }
+                },
+            )
+            .unwrap()
+        }
+
+        recurse(0)
+    }
+
+    /// Check that the maximum fork nesting depth can be configured.
+    #[test]
+    fn max_depth_configurable() {
+        fork_int(
+            "fork::test::max_depth_configurable",
+            fork_id!(),
+            |cmd| {
+                cmd.env(MAX_DEPTH_ENV, "1");
+            },
+            |child| {
+                let output = child.wait_with_output().unwrap();
+                assert_eq!(output.status.code(), Some(70));
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                assert!(stderr.contains("Not forking due to >=1 levels"), "{stderr}");
+                assert!(stderr.contains("occurrence chain: :"), "{stderr}");
+            },
+            || {
+                fork_int(
+                    "fork::test::max_depth_configurable",
+                    fork_id!(),
+                    |_| (),
+                    supervise,
+                    || (),
+                )
+                .unwrap()
+            },
+        )
+        .unwrap()
+    }
+
+    /// Check that the exit code reported by the test is used as the
+    /// child's exit code.
+    #[test]
+    fn exit_code_propagated() {
+        let status = fork_int(
+            "fork::test::exit_code_propagated",
+            fork_id!(),
+            |_| (),
+            |mut child| child.wait().unwrap(),
+            || ExitCode::from(3),
+        )
+        .unwrap();
+        assert_eq!(status.code(), Some(3));
+
+        let status = fork_int(
+            "fork::test::exit_code_propagated",
+            fork_id!(),
+            |_| (),
+            |mut child| child.wait().unwrap(),
+            || Err::<(), _>("failure"),
+        )
+        .unwrap();
+        assert_eq!(status.code(), Some(1));
+    }
+
     #[test]
     fn child_aborted_if_panics() {
         let status = fork_int::<_, _, _, _, ()>(
@@ -302,6 +1126,31 @@ mod test {
         assert_eq!(70, status.code().unwrap());
     }
 
+    /// Check that panics in the child are attributed to the panicking
+    /// thread in the parent's failure message.
+    #[test]
+    #[should_panic(expected = "thread 'panicker' panicked at core/src/fork.rs")]
+    fn child_panic_reported() {
+        use std::thread;
+
+        fork_int(
+            "fork::test::child_panic_reported",
+            fork_id!(),
+            |_| (),
+            supervise,
+            || {
+                let result = thread::Builder::new()
+                    .name("panicker".to_string())
+                    .spawn(|| panic!("testing a panic, nothing to see here"))
+                    .unwrap()
+                    .join();
+                assert!(result.is_err());
+                panic!("panicker thread panicked")
+            },
+        )
+        .unwrap()
+    }
+
     /// Check that we can exchange data with the child process.
     #[test]
     fn data_exchange() {
@@ -320,4 +1169,34 @@ mod test {
 
         assert_eq!(data, [2, 3, 4, 5, 6]);
     }
+
+    /// Check that the child can return data of a different size than
+    /// it received.
+    #[test]
+    fn variable_length_data_exchange() {
+        let mut data = b"input".to_vec();
+
+        let () = fork_in_out_vec(
+            fork_id!(),
+            "fork::test::variable_length_data_exchange",
+            |data| {
+                assert_eq!(data, b"input");
+                *data = vec![42; 100_000];
+            },
+            &mut data,
+        )
+        .unwrap();
+
+        assert_eq!(data, vec![42; 100_000]);
+
+        let () = fork_in_out_vec(
+            fork_id!(),
+            "fork::test::variable_length_data_exchange",
+            Vec::clear,
+            &mut data,
+        )
+        .unwrap();
+
+        assert!(data.is_empty());
+    }
 }
@@ -11,19 +11,61 @@
 // except according to those terms.
 
 
+use std::str;
+
+
 /// Given the unqualified name of a `#[test]` function, produce a
 /// `&'static str` corresponding to the name of the test as filtered by the
 /// standard test harness.
+///
+/// The result is a constant expression and can be used in `const` and
+/// `static` contexts.
 #[macro_export]
 macro_rules! fork_test_name {
-    ($function_name:ident) => {
-        $crate::fix_module_path(concat!(module_path!(), "::", stringify!($function_name)))
-    };
+    ($function_name:ident) => {{
+        const NAME: &str =
+            $crate::fix_module_path(concat!(module_path!(), "::", stringify!($function_name)));
+        NAME
+    }};
 }
 
 /// Transform a string representing a qualified path as generated via
 /// `module_path!()` into a qualified path as expected by the standard Rust
 /// test harness.
-pub fn fix_module_path(path: &str) -> &str {
-    path.split_once("::").map(|(_, rest)| rest).unwrap_or(path)
+pub const fn fix_module_path(path: &str) -> &str {
+    let mut rest = path.as_bytes();
+    while let [first, tail @ ..] = rest {
+        if let (b':', [b':', after @ ..]) = (*first, tail) {
+            // SAFETY: `after` starts right after an ASCII character in
+            //         a valid UTF-8 string and, hence, is valid UTF-8
+            //         itself.
+            return unsafe { str::from_utf8_unchecked(after) }
+        }
+        rest = tail;
+    }
+    path
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that we can fix up module paths as expected.
+    #[test]
+    fn module_path_fixing() {
+        assert_eq!(fix_module_path("foo"), "foo");
+        assert_eq!(fix_module_path("foo::bar"), "bar");
+        assert_eq!(fix_module_path("foo::bar::baz"), "bar::baz");
+        assert_eq!(fix_module_path("foo:bar::baz"), "baz");
+        assert_eq!(fix_module_path("föö::bär"), "bär");
+    }
+
+    /// Check that `fork_test_name!` can be used in constant contexts.
+    #[test]
+    fn const_test_name() {
+        const NAME: &str = fork_test_name!(const_test_name);
+        assert_eq!(NAME, "fork_test::test::const_test_name");
+    }
 }
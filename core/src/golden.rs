@@ -0,0 +1,174 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Comparison of a child's output against a golden file.
+//!
+//! Besides the output of the test itself, the child's stdout contains
+//! output of the test harness. To be able to tell the two apart, the
+//! child brackets the test's output with marker lines if requested to
+//! do so by the parent.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::io::Write as _;
+use std::path::Path;
+use std::process::Command;
+
+use crate::failure::ChildFailure;
+
+
+/// The environment variable instructing the child to mark the test's
+/// output.
+const MARK_ENV: &str = "TEST_FORK_MARK_OUTPUT";
+/// The environment variable enabling update mode, in which golden files
+/// are rewritten instead of compared against.
+const UPDATE_ENV: &str = "TEST_FORK_UPDATE_GOLDEN";
+/// The line marking the beginning of the test's output.
+const BEGIN_MARKER: &str = "<<test-fork-output-begin>>\n";
+/// The line marking the end of the test's output.
+const END_MARKER: &str = "<<test-fork-output-end>>\n";
+
+
+/// Instruct the child process spawned by `command` to mark the test's
+/// output.
+pub(crate) fn configure(command: &mut Command) {
+    command.env(MARK_ENV, "1");
+}
+
+/// Make sure that the marking configuration is not inherited by the
+/// child process.
+pub(crate) fn unconfigure(command: &mut Command) {
+    command.env_remove(MARK_ENV);
+}
+
+fn write_marker(marker: &str) {
+    if env::var_os(MARK_ENV).is_some() {
+        let mut stdout = io::stdout().lock();
+        // Make sure that everything written so far ends up before the
+        // marker.
+        let _result = stdout
+            .flush()
+            .and_then(|()| stdout.write_all(marker.as_bytes()))
+            .and_then(|()| stdout.flush());
+    }
+}
+
+/// Mark the beginning of the test's output, if requested.
+pub(crate) fn begin() {
+    write_marker(BEGIN_MARKER)
+}
+
+/// Mark the end of the test's output, if requested.
+pub(crate) fn end() {
+    write_marker(END_MARKER)
+}
+
+
+/// Extract the test's output from the child's marked stdout.
+fn extract(stdout: &[u8]) -> &[u8] {
+    let find = |haystack: &[u8], needle: &str| {
+        haystack
+            .windows(needle.len())
+            .position(|window| window == needle.as_bytes())
+    };
+
+    let begin = find(stdout, BEGIN_MARKER)
+        .and_then(|idx| stdout.get(idx + BEGIN_MARKER.len()..))
+        .unwrap_or_default();
+    let output = find(begin, END_MARKER)
+        .and_then(|idx| begin.get(..idx))
+        .unwrap_or(begin);
+    output
+}
+
+/// Describe the first difference between `expected` and `actual`.
+fn describe_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(expected), Some(actual)) if expected == actual => line += 1,
+            (expected, actual) => {
+                break format!(
+                    "first difference in line {line}:\n  expected: {}\n  actual:   {}",
+                    expected
+                        .map(|s| format!("{s:?}"))
+                        .unwrap_or_else(|| "<EOF>".into()),
+                    actual
+                        .map(|s| format!("{s:?}"))
+                        .unwrap_or_else(|| "<EOF>".into()),
+                )
+            }
+        }
+    }
+}
+
+/// Compare the test's output contained in the child's marked `stdout`
+/// against the golden file at `path`.
+///
+/// In update mode, the golden file is rewritten instead.
+pub(crate) fn check(path: &Path, stdout: &[u8]) -> Result<(), ChildFailure> {
+    let actual = extract(stdout);
+
+    if env::var_os(UPDATE_ENV).is_some_and(|update| update != "0") {
+        let result = path
+            .parent()
+            .map(fs::create_dir_all)
+            .transpose()
+            .and_then(|_| fs::write(path, actual));
+        if let Err(err) = result {
+            eprintln!(
+                "test-fork: failed to update golden file {}: {err}",
+                path.display()
+            );
+        }
+        return Ok(())
+    }
+
+    let expected = fs::read(path).unwrap_or_default();
+    if expected != actual {
+        let difference = describe_difference(
+            &String::from_utf8_lossy(&expected),
+            &String::from_utf8_lossy(actual),
+        );
+        return Err(ChildFailure::OutputMismatch {
+            path: path.to_path_buf(),
+            difference,
+        })
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that we can extract marked output.
+    #[test]
+    fn output_extraction() {
+        let stdout = format!("\nrunning 1 test\n{BEGIN_MARKER}hello\nworld\n{END_MARKER}.\n");
+        assert_eq!(extract(stdout.as_bytes()), b"hello\nworld\n");
+        assert_eq!(extract(b"no markers"), b"");
+    }
+
+    /// Check that differences are described as expected.
+    #[test]
+    fn difference_description() {
+        let difference = describe_difference("a\nb\nc\n", "a\nx\nc\n");
+        assert_eq!(
+            difference,
+            "first difference in line 2:\n  expected: \"b\"\n  actual:   \"x\""
+        );
+
+        let difference = describe_difference("a\n", "a\nb\n");
+        assert_eq!(
+            difference,
+            "first difference in line 2:\n  expected: <EOF>\n  actual:   \"b\""
+        );
+    }
+}
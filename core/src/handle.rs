@@ -0,0 +1,215 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+use std::io;
+use std::result;
+
+use crate::builder::Builder;
+use crate::child::ChildWrapper;
+use crate::failure::ChildFailure;
+use crate::fork::supervise_child;
+use crate::heartbeat::Monitor;
+use crate::outcome::ForkOutcome;
+#[cfg(all(target_os = "linux", feature = "perf"))]
+use crate::perf;
+
+
+/// A handle to a child process started via [`Builder::spawn_fork`].
+///
+/// The child runs concurrently with the parent until it is joined.
+/// Dropping the handle without joining it kills the child along with
+/// all processes it spawned, unless [`Builder::kill_on_drop`] is
+/// disabled.
+///
+/// A handle obtained in a child process skipping over the fork point,
+/// or for a test that got run in-process, does not represent any
+/// child. Joining it succeeds right away with a default
+/// [`ForkOutcome`].
+#[derive(Debug, Default)]
+pub struct ForkHandle {
+    /// The builder the child was spawned with.
+    builder: Builder,
+    /// The name of the test the child runs.
+    test_name: String,
+    /// The child process, if any and not yet joined.
+    child: Option<ChildWrapper>,
+    /// The monitor of the child's heartbeat, if enabled.
+    monitor: Option<Monitor>,
+    /// The performance counters collecting the child's events.
+    #[cfg(all(target_os = "linux", feature = "perf"))]
+    counters: Option<perf::Collector>,
+}
+
+impl ForkHandle {
+    /// Create a handle for a spawned child.
+    ///
+    /// Performance counters prepared for the child by the calling
+    /// thread, if any, are taken over by the handle.
+    pub(crate) fn new(
+        builder: Builder,
+        test_name: &str,
+        child: Option<ChildWrapper>,
+        monitor: Option<Monitor>,
+    ) -> Self {
+        Self {
+            builder,
+            test_name: test_name.to_string(),
+            #[cfg(all(target_os = "linux", feature = "perf"))]
+            counters: child.as_ref().and_then(|_| perf::Collector::take()),
+            child,
+            monitor,
+        }
+    }
+
+    /// Retrieve the OS-assigned process identifier of the child, if
+    /// the handle represents one that has not been joined yet.
+    #[inline]
+    pub fn id(&self) -> Option<u32> {
+        self.child.as_ref().map(ChildWrapper::id)
+    }
+
+    /// Wait for the child to exit, reporting its outcome just like
+    /// [`Builder::try_fork`] does.
+    pub fn join(mut self) -> result::Result<ForkOutcome, ChildFailure> {
+        self.complete()
+    }
+
+    /// Check whether the child has exited, without blocking.
+    ///
+    /// `None` is returned if the child is still running. Otherwise its
+    /// outcome is reported just like [`ForkHandle::join`] does, after
+    /// which the handle no longer represents a child.
+    pub fn try_join(&mut self) -> Option<result::Result<ForkOutcome, ChildFailure>> {
+        if let Some(child) = &mut self.child {
+            // An error to check the child's status will surface once we
+            // wait for it properly.
+            if let Ok(None) = child.try_wait() {
+                return None
+            }
+        }
+        Some(self.complete())
+    }
+
+    /// Kill the child along with all processes it spawned.
+    ///
+    /// The child still has to be joined to retrieve its outcome, which
+    /// will report a failure.
+    pub fn kill(&mut self) -> io::Result<()> {
+        match &mut self.child {
+            Some(child) => child.kill_group(),
+            None => Ok(()),
+        }
+    }
+
+    /// Supervise the child until it exited and check its outcome.
+    fn complete(&mut self) -> result::Result<ForkOutcome, ChildFailure> {
+        let outcome = self.child.take().map(|child| {
+            #[cfg(all(target_os = "linux", feature = "perf"))]
+            if let Some(counters) = self.counters.take() {
+                let () = counters.install();
+            }
+            supervise_child(child, self.monitor.take(), self.builder.supervision())
+        });
+        self.builder.finish(&self.test_name, outcome)
+    }
+}
+
+impl Drop for ForkHandle {
+    fn drop(&mut self) {
+        if !self.builder.kills_on_drop() {
+            if let Some(child) = self.child.take() {
+                let () = child.detach();
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::thread::sleep;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use crate::child::reap_detached;
+
+
+    /// Check that the parent can carry on while the child runs and
+    /// join it afterwards.
+    #[test]
+    fn spawn_and_join() {
+        let mut handle = Builder::new()
+            .capture_output(true)
+            .spawn_fork(fork_id!(), fork_test_name!(spawn_and_join), || {
+                let () = sleep(Duration::from_millis(200));
+                println!("joined");
+            })
+            .unwrap();
+
+        assert!(handle.id().is_some());
+        assert!(handle.try_join().is_none());
+
+        let outcome = handle.join().unwrap();
+        let stdout = String::from_utf8_lossy(&outcome.stdout);
+        assert!(stdout.contains("joined"), "{stdout}");
+    }
+
+    /// Check that `ForkHandle::try_join` eventually reports the
+    /// child's outcome.
+    #[test]
+    fn try_join() {
+        let mut handle = Builder::new()
+            .spawn_fork(fork_id!(), fork_test_name!(try_join), || {})
+            .unwrap();
+
+        let result = loop {
+            match handle.try_join() {
+                Some(result) => break result,
+                None => sleep(Duration::from_millis(10)),
+            }
+        };
+        let _outcome = result.unwrap();
+        assert_eq!(handle.id(), None);
+    }
+
+    /// Check that we can kill a spawned child.
+    #[test]
+    fn kill() {
+        let mut handle = Builder::new()
+            .spawn_fork(fork_id!(), fork_test_name!(kill), || {
+                let () = sleep(Duration::from_secs(60));
+            })
+            .unwrap();
+
+        let () = handle.kill().unwrap();
+        let _failure = handle.join().unwrap_err();
+    }
+
+    /// Check that a child can be left running when its handle is
+    /// dropped, to be reaped explicitly.
+    #[test]
+    fn detach_on_drop() {
+        let handle = Builder::new()
+            .kill_on_drop(false)
+            .spawn_fork(fork_id!(), fork_test_name!(detach_on_drop), || {
+                let () = sleep(Duration::from_millis(200));
+            })
+            .unwrap();
+        let pid = handle.id().unwrap();
+        let () = drop(handle);
+
+        let start = Instant::now();
+        let status = loop {
+            let reaped = reap_detached().unwrap();
+            if let Some((_, status)) = reaped.into_iter().find(|(id, _)| *id == pid) {
+                break status
+            }
+            assert!(start.elapsed() < Duration::from_secs(30));
+            let () = sleep(Duration::from_millis(10));
+        };
+        // The child was not killed but exited on its own.
+        assert!(status.success());
+    }
+}
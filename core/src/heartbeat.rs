@@ -0,0 +1,242 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Heartbeat based detection of hung child processes.
+//!
+//! When enabled, the child periodically sends a single byte to the
+//! parent over a TCP connection, provided that the test reported
+//! progress via [`heartbeat`] since the last time. The parent keeps
+//! track of the time the last heartbeat was received and declares the
+//! child hung if none arrived for the configured interval.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::io::Read as _;
+use std::io::Write as _;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::process::Command;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Once;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+
+/// The environment variable used for conveying the heartbeat
+/// configuration to the child, in the form `<interval-ms>@<address>`.
+const HEARTBEAT_ENV: &str = "TEST_FORK_HEARTBEAT";
+/// The time we grant the child for connecting to the parent before
+/// considering it hung, unless the heartbeat timeout is even longer.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The number of times the test reported progress.
+static PROGRESS: AtomicUsize = AtomicUsize::new(0);
+
+
+/// The parent side of the heartbeat mechanism.
+#[derive(Debug)]
+pub(crate) struct Monitor {
+    /// The listener the child connects to.
+    listener: TcpListener,
+    /// The address `listener` is bound to.
+    addr: SocketAddr,
+    /// The connection to the child, once established.
+    stream: Option<TcpStream>,
+    /// Whether the child connected to us already.
+    connected: bool,
+    /// The time after which we consider the child hung if no heartbeat
+    /// was received.
+    timeout: Duration,
+    /// The time after which we consider the child hung if it did not
+    /// connect to us.
+    connect_timeout: Duration,
+    /// The time we last received a heartbeat, or, before the child
+    /// connected, the time supervision started.
+    last: Instant,
+}
+
+impl Monitor {
+    pub(crate) fn new(timeout: Duration) -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let () = listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+
+        let slf = Self {
+            listener,
+            addr,
+            stream: None,
+            connected: false,
+            timeout,
+            connect_timeout: timeout.max(CONNECT_TIMEOUT),
+            last: Instant::now(),
+        };
+        Ok(slf)
+    }
+
+    /// Retrieve the environment variable (name and value) instructing
+    /// the child process to send heartbeats to this monitor.
+    pub(crate) fn child_env(&self) -> (&'static str, String) {
+        // Ping a couple of times per timeout interval, so that a single
+        // delayed heartbeat does not cause a false positive.
+        let interval = (self.timeout / 4).max(Duration::from_millis(1));
+        let value = format!("{}@{}", interval.as_millis(), self.addr);
+        (HEARTBEAT_ENV, value)
+    }
+
+    /// Start the clock, as the child got spawned.
+    pub(crate) fn start(&mut self) {
+        self.last = Instant::now();
+    }
+
+    /// Process all heartbeats received since the last call.
+    fn poll(&mut self) -> io::Result<()> {
+        if !self.connected {
+            match self.listener.accept() {
+                Ok((stream, _addr)) => {
+                    let () = stream.set_nonblocking(true)?;
+                    self.stream = Some(stream);
+                    self.connected = true;
+                    self.last = Instant::now();
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err),
+            }
+        }
+
+        if let Some(stream) = &mut self.stream {
+            let mut buf = [0; 64];
+            loop {
+                match stream.read(&mut buf) {
+                    // The child closed the connection (e.g., because it
+                    // is exiting). There won't be any more heartbeats,
+                    // but the child isn't hung either.
+                    Ok(0) => {
+                        self.stream = None;
+                        self.timeout = Duration::MAX;
+                        break
+                    }
+                    Ok(_) => self.last = Instant::now(),
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check whether the child is to be considered hung, returning the
+    /// time since the last heartbeat if that is the case.
+    pub(crate) fn check(&mut self) -> io::Result<Option<Duration>> {
+        let () = self.poll()?;
+        let timeout = if self.connected {
+            self.timeout
+        } else {
+            self.connect_timeout
+        };
+        let elapsed = self.last.elapsed();
+        Ok((elapsed > timeout).then_some(elapsed))
+    }
+}
+
+
+/// Make sure that the heartbeat configuration is not inherited by
+/// the child process.
+pub(crate) fn unconfigure(command: &mut Command) {
+    command.env_remove(HEARTBEAT_ENV);
+}
+
+
+/// Retrieve a human readable description of the state of the process
+/// with the given PID, if possible.
+pub(crate) fn process_state(pid: u32) -> Option<String> {
+    if cfg!(target_os = "linux") {
+        let status = fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+        let state = status
+            .lines()
+            .find_map(|line| line.strip_prefix("State:"))?
+            .trim()
+            .to_string();
+        Some(state)
+    } else {
+        None
+    }
+}
+
+
+/// Report that the test run by the current process is making progress.
+///
+/// A test with heartbeat based hang detection enabled (see
+/// [`Builder::heartbeat`][crate::Builder::heartbeat]) that runs longer
+/// than the configured timeout has to call this function regularly,
+/// or it is considered hung. Outside of such a test, the function has
+/// no effect.
+pub fn heartbeat() {
+    let _progress = PROGRESS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Start sending heartbeats to the parent, if requested by it.
+pub(crate) fn start() {
+    static START: Once = Once::new();
+
+    let () = START.call_once(|| {
+        let config = match env::var(HEARTBEAT_ENV) {
+            Ok(config) => config,
+            Err(_) => return,
+        };
+        let (interval, addr) = config
+            .split_once('@')
+            .and_then(|(interval, addr)| Some((interval.parse().ok()?, addr)))
+            .unwrap_or_else(|| panic!("test-fork: invalid {HEARTBEAT_ENV} value `{config}`"));
+        let interval = Duration::from_millis(interval);
+        let mut stream = TcpStream::connect(addr)
+            .unwrap_or_else(|err| panic!("test-fork: failed to connect to {addr}: {err}"));
+
+        let _handle = thread::Builder::new()
+            .name("test-fork-heartbeat".to_string())
+            .spawn(move || {
+                // Connecting counts as the first heartbeat.
+                let mut reported = PROGRESS.load(Ordering::Relaxed);
+                loop {
+                    let () = thread::sleep(interval);
+                    let progress = PROGRESS.load(Ordering::Relaxed);
+                    if progress != reported {
+                        reported = progress;
+                        // If sending fails the parent is gone and there
+                        // is nobody left to care.
+                        if stream.write_all(&[0]).is_err() {
+                            break
+                        }
+                    }
+                }
+            })
+            .expect("failed to spawn heartbeat thread");
+    });
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that a child never connecting to the monitor is
+    /// eventually considered hung.
+    #[test]
+    fn unconnected_child_hung() {
+        let mut monitor = Monitor::new(Duration::from_millis(10)).unwrap();
+        monitor.connect_timeout = Duration::from_millis(100);
+        let () = monitor.start();
+        assert_eq!(monitor.check().unwrap(), None);
+
+        let () = thread::sleep(Duration::from_millis(50));
+        assert_eq!(monitor.check().unwrap(), None);
+
+        let () = thread::sleep(Duration::from_millis(100));
+        assert!(monitor.check().unwrap().is_some());
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Information about the current child process, as conveyed by the
+//! parent via the environment.
+
+use std::env;
+use std::process;
+use std::process::Command;
+
+use crate::fork::occurrences;
+use crate::fork::OCCURS_ENV;
+
+
+/// The environment variable conveying the name of the test the child
+/// runs.
+const TEST_NAME_ENV: &str = "TEST_FORK_TEST_NAME";
+/// The environment variable conveying the process ID of the parent.
+const PARENT_PID_ENV: &str = "TEST_FORK_PARENT_PID";
+
+
+/// Information about the forked test run by the current (child)
+/// process.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ForkInfo {
+    /// The name of the test.
+    test_name: String,
+    /// The ID of the fork point that spawned the process.
+    fork_id: String,
+    /// The occurrence index of said fork point.
+    occurrence: usize,
+    /// The process ID of the parent.
+    parent_pid: u32,
+    /// The fork nesting depth.
+    depth: usize,
+}
+
+impl ForkInfo {
+    /// Retrieve the full path of the test the child process runs.
+    #[inline]
+    pub fn test_name(&self) -> &str {
+        &self.test_name
+    }
+
+    /// Retrieve the ID of the fork point that spawned the child
+    /// process (without leading `:`).
+    #[inline]
+    pub fn fork_id(&self) -> &str {
+        &self.fork_id
+    }
+
+    /// Retrieve the (zero based) index of the occurrence of the fork
+    /// point that spawned the child process.
+    ///
+    /// The index is only non-zero if the same fork point was
+    /// encountered multiple times by the test, e.g., inside a loop.
+    #[inline]
+    pub fn occurrence(&self) -> usize {
+        self.occurrence
+    }
+
+    /// Retrieve the process ID of the parent that spawned the child
+    /// process.
+    #[inline]
+    pub fn parent_pid(&self) -> u32 {
+        self.parent_pid
+    }
+
+    /// Retrieve the fork nesting depth of the child process.
+    ///
+    /// A child spawned by a process that is not a child itself has a
+    /// depth of one.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+
+/// Retrieve information about the forked test run by the current
+/// process.
+///
+/// `None` is returned if the current process is not a child process
+/// spawned by `test-fork`.
+pub fn fork_info() -> Option<ForkInfo> {
+    let occurs = env::var(OCCURS_ENV).ok()?;
+    let test_name = env::var(TEST_NAME_ENV).ok()?;
+    let parent_pid = env::var(PARENT_PID_ENV).ok()?.parse().ok()?;
+    let (fork_id, occurrence) = occurrences(&occurs).last()?;
+
+    let info = ForkInfo {
+        test_name,
+        fork_id: fork_id.to_string(),
+        occurrence,
+        parent_pid,
+        depth: occurrences(&occurs).count(),
+    };
+    Some(info)
+}
+
+/// Convey information about the child process spawned by `command` for
+/// running the given test to it.
+pub(crate) fn configure(command: &mut Command, test_name: &str) {
+    command
+        .env(TEST_NAME_ENV, test_name)
+        .env(PARENT_PID_ENV, process::id().to_string());
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(unix)]
+    use std::os::unix::process::parent_id;
+
+    use crate::fork::fork_int;
+
+
+    /// Check that the child can retrieve information about itself.
+    #[test]
+    fn child_info() {
+        let test_name = fork_test_name!(child_info);
+        fork_int(
+            test_name,
+            fork_id!("child-info"),
+            |_| (),
+            |mut child| {
+                assert_eq!(fork_info(), None);
+                assert!(child.wait().unwrap().success())
+            },
+            || {
+                let info = fork_info().unwrap();
+                assert_eq!(info.test_name(), test_name);
+                assert_eq!(info.fork_id(), "child-info");
+                assert_eq!(info.occurrence(), 0);
+                assert_ne!(info.parent_pid(), process::id());
+                #[cfg(unix)]
+                assert_eq!(info.parent_pid(), parent_id());
+                assert_eq!(info.depth(), 1);
+            },
+        )
+        .unwrap()
+    }
+}
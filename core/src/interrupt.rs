@@ -0,0 +1,274 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Termination of child processes when the parent gets interrupted.
+//!
+//! On Unix systems, children run in process groups of their own and so
+//! don't receive the `SIGINT` the terminal sends to the foreground
+//! process group on Ctrl-C. Hence, the parent keeps track of all its
+//! running children and kills their process groups when it receives a
+//! termination signal, before handing the signal on. On Windows, the
+//! process trees of all children are terminated from a console control
+//! handler.
+
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::Ordering;
+use std::sync::Once;
+
+
+/// The maximum number of children tracked at any one time. Children
+/// beyond that are not terminated on interrupt.
+const MAX_TRACKED: usize = 256;
+
+
+/// The process identifiers of all tracked children, with zero denoting
+/// an unused slot.
+///
+/// We can't use any locks here, as the slots are accessed from a signal
+/// handler on Unix systems.
+static TRACKED: [AtomicU32; MAX_TRACKED] = [const { AtomicU32::new(0) }; MAX_TRACKED];
+
+
+/// Start tracking the child with the given process identifier,
+/// installing the interrupt handler if that has not happened yet.
+pub(crate) fn track(pid: u32) {
+    static INSTALL: Once = Once::new();
+
+    let () = INSTALL.call_once(imp::install);
+    let _slot = TRACKED.iter().find(|slot| {
+        slot.compare_exchange(0, pid, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+    });
+}
+
+/// Stop tracking the child with the given process identifier.
+pub(crate) fn untrack(pid: u32) {
+    let _slot = TRACKED.iter().find(|slot| {
+        slot.compare_exchange(pid, 0, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+    });
+}
+
+
+#[cfg(unix)]
+mod imp {
+    use std::ffi::c_int;
+    use std::mem;
+    use std::ptr;
+    use std::sync::atomic::AtomicI32;
+    use std::sync::atomic::Ordering;
+    use std::sync::OnceLock;
+
+    use super::TRACKED;
+
+
+    /// The signals upon which we terminate all children.
+    const SIGNALS: [c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+
+    /// The process that installed the signal handler. A process created
+    /// via a plain `fork` inherits the handler, but not the children.
+    static OWNER: AtomicI32 = AtomicI32::new(0);
+    /// The actions in place for each of [`SIGNALS`] before we installed
+    /// our handler.
+    static PREVIOUS: OnceLock<Vec<(c_int, Action)>> = OnceLock::new();
+
+
+    /// A `sigaction` that can be shared with the signal handler.
+    struct Action(libc::sigaction);
+
+    // SAFETY: A `sigaction` is plain data, merely referencing a signal
+    //         handler, which is callable from any thread.
+    unsafe impl Send for Action {}
+    // SAFETY: See above.
+    unsafe impl Sync for Action {}
+
+
+    /// Kill the process groups of all tracked children and hand the
+    /// signal on to whatever action was in place before.
+    extern "C" fn handle(signal: c_int) {
+        // SAFETY: `getpid` is always safe to call.
+        if unsafe { libc::getpid() } == OWNER.load(Ordering::Acquire) {
+            for slot in &TRACKED {
+                if let Ok(pid @ 1..) = libc::pid_t::try_from(slot.load(Ordering::Acquire)) {
+                    // SAFETY: `kill` is always safe to call.
+                    let _rc = unsafe { libc::kill(-pid, libc::SIGKILL) };
+                }
+            }
+        }
+
+        let previous = PREVIOUS
+            .get()
+            .and_then(|previous| previous.iter().find(|(sig, _)| *sig == signal));
+        match previous {
+            // SAFETY: `action` was retrieved from `sigaction` and so is
+            //         valid.
+            Some((_, Action(action))) => unsafe {
+                let _rc = libc::sigaction(signal, action, ptr::null_mut());
+            },
+            // SAFETY: `signal` is always safe to call.
+            None => unsafe {
+                let _handler = libc::signal(signal, libc::SIG_DFL);
+            },
+        }
+        // The signal is blocked while we are handling it, so it will be
+        // delivered once we return.
+        // SAFETY: `raise` is always safe to call.
+        let _rc = unsafe { libc::raise(signal) };
+    }
+
+    /// Install the signal handler for all of [`SIGNALS`] that are not
+    /// ignored.
+    pub(super) fn install() {
+        // SAFETY: `getpid` is always safe to call.
+        let () = OWNER.store(unsafe { libc::getpid() }, Ordering::Release);
+
+        // SAFETY: `sigaction` is plain data, for which all zeroes is a
+        //         valid value.
+        let mut action = unsafe { mem::zeroed::<libc::sigaction>() };
+        action.sa_sigaction = handle as extern "C" fn(c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+
+        let previous = SIGNALS
+            .iter()
+            .filter_map(|signal| {
+                // SAFETY: `sigaction` is plain data, for which all zeroes
+                //         is a valid value.
+                let mut previous = unsafe { mem::zeroed::<libc::sigaction>() };
+                // SAFETY: Both `action` and `previous` are valid
+                //         `sigaction` objects.
+                let rc = unsafe { libc::sigaction(*signal, &action, &mut previous) };
+                if rc != 0 {
+                    return None
+                }
+                // Leave ignored signals ignored.
+                if previous.sa_sigaction == libc::SIG_IGN {
+                    // SAFETY: `previous` was just retrieved from
+                    //         `sigaction` and so is valid.
+                    let _rc = unsafe { libc::sigaction(*signal, &previous, ptr::null_mut()) };
+                    return None
+                }
+                Some((*signal, Action(previous)))
+            })
+            .collect();
+        let _result = PREVIOUS.set(previous);
+    }
+}
+
+
+#[cfg(windows)]
+mod imp {
+    use std::sync::atomic::Ordering;
+
+    use super::TRACKED;
+    use crate::child::kill_tree;
+
+
+    /// Terminate the process trees of all tracked children.
+    extern "system" fn handle(_ctrl_type: u32) -> i32 {
+        for slot in &TRACKED {
+            let pid = slot.load(Ordering::Acquire);
+            if pid != 0 {
+                let _result = kill_tree(pid);
+            }
+        }
+        // Let the next handler (ultimately the default one, terminating
+        // the process) take care of the event.
+        0
+    }
+
+    /// Install the console control handler.
+    pub(super) fn install() {
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn SetConsoleCtrlHandler(
+                handler: Option<extern "system" fn(u32) -> i32>,
+                add: i32,
+            ) -> i32;
+        }
+
+        // SAFETY: `handle` is a valid handler routine.
+        let _rc = unsafe { SetConsoleCtrlHandler(Some(handle), 1) };
+    }
+}
+
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    pub(super) fn install() {}
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::fork::fork_int;
+
+
+    /// Check that children are tracked and untracked correctly.
+    #[test]
+    fn tracking() {
+        let pid = u32::MAX - 1;
+        let () = track(pid);
+        assert!(TRACKED
+            .iter()
+            .any(|slot| slot.load(Ordering::Relaxed) == pid));
+
+        let () = untrack(pid);
+        assert!(!TRACKED
+            .iter()
+            .any(|slot| slot.load(Ordering::Relaxed) == pid));
+    }
+
+    /// Check that tracked children get killed when the parent receives
+    /// a termination signal.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn kill_on_interrupt() {
+        use std::fs;
+        use std::os::unix::process::CommandExt as _;
+        use std::os::unix::process::ExitStatusExt as _;
+        use std::process::Command;
+        use std::thread;
+        use std::time::Duration;
+        use std::time::Instant;
+
+        fork_int(
+            fork_test_name!(kill_on_interrupt),
+            fork_id!(),
+            |_| (),
+            |child| {
+                let output = child.wait_with_output().unwrap();
+                assert_eq!(output.status.signal(), Some(libc::SIGTERM));
+
+                let stdout = String::from_utf8(output.stdout).unwrap();
+                let pid = stdout.trim().rsplit('\n').next().unwrap();
+                // The orphaned process may linger as a zombie for a
+                // while, until it gets reaped.
+                let start = Instant::now();
+                while let Ok(stat) = fs::read_to_string(format!("/proc/{pid}/stat")) {
+                    let (_, rest) = stat.rsplit_once(')').unwrap();
+                    if rest.trim_start().starts_with('Z') {
+                        break
+                    }
+                    assert!(start.elapsed() < Duration::from_secs(30), "{stat}");
+                    let () = thread::sleep(Duration::from_millis(10));
+                }
+            },
+            || {
+                let mut sleep = Command::new("sleep")
+                    .arg("60")
+                    .process_group(0)
+                    .spawn()
+                    .unwrap();
+                let () = track(sleep.id());
+                println!("{}", sleep.id());
+                // SAFETY: `raise` is always safe to call.
+                let _rc = unsafe { libc::raise(libc::SIGTERM) };
+                let _status = sleep.wait();
+            },
+        )
+        .unwrap()
+    }
+}
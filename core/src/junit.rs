@@ -0,0 +1,161 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Emission of `JUnit` XML reports for child processes.
+//!
+//! If the `TEST_FORK_JUNIT_DIR` environment variable is set, a
+//! `JUnit` XML file describing the outcome of each supervised child
+//! process is written into the directory it names.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Output;
+use std::time::Duration;
+
+use crate::failure::ChildFailure;
+
+
+/// The environment variable specifying the directory to write reports
+/// to.
+const JUNIT_DIR_ENV: &str = "TEST_FORK_JUNIT_DIR";
+
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Most control characters are not allowed in XML 1.0.
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => escaped.push('\u{FFFD}'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Derive a file name from the name of a test.
+pub(crate) fn file_name(test_name: &str) -> String {
+    test_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// Format a `JUnit` XML report for a single test.
+fn format_report(
+    test_name: &str,
+    duration: Duration,
+    failure: Option<&ChildFailure>,
+    stdout: &[u8],
+    stderr: &[u8],
+) -> String {
+    let (classname, name) = test_name.rsplit_once("::").unwrap_or(("", test_name));
+    let time = duration.as_secs_f64();
+    let failures = usize::from(failure.is_some());
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml += &format!(
+        "<testsuite name=\"test-fork\" tests=\"1\" failures=\"{failures}\" time=\"{time:.3}\">\n"
+    );
+    xml += &format!(
+        "  <testcase classname=\"{}\" name=\"{}\" time=\"{time:.3}\">\n",
+        escape(classname),
+        escape(name)
+    );
+    if let Some(failure) = failure {
+        let message = escape(&failure.to_string());
+        xml += &format!("    <failure message=\"{message}\">{message}</failure>\n");
+    }
+    xml += &format!(
+        "    <system-out>{}</system-out>\n",
+        escape(&String::from_utf8_lossy(stdout))
+    );
+    xml += &format!(
+        "    <system-err>{}</system-err>\n",
+        escape(&String::from_utf8_lossy(stderr))
+    );
+    xml += "  </testcase>\n";
+    xml += "</testsuite>\n";
+    xml
+}
+
+
+/// Write a `JUnit` XML report for the child process with the given
+/// `pid`, if reporting is enabled.
+pub(crate) fn report(
+    test_name: &str,
+    pid: u32,
+    duration: Duration,
+    failure: Option<&ChildFailure>,
+    output: &Output,
+) {
+    let dir = match env::var_os(JUNIT_DIR_ENV) {
+        Some(dir) => PathBuf::from(dir),
+        None => return,
+    };
+
+    // The same test may fork multiple times, so include the PID of the
+    // child to keep reports apart.
+    let file_name = file_name(test_name);
+    let path = dir.join(format!("{file_name}-{pid}.xml"));
+    let xml = format_report(test_name, duration, failure, &output.stdout, &output.stderr);
+
+    // Reports are purely informational. Failure to write one should
+    // not fail the test.
+    if let Err(err) = fs::create_dir_all(&dir).and_then(|()| fs::write(&path, xml)) {
+        eprintln!(
+            "test-fork: failed to write JUnit report {}: {err}",
+            path.display()
+        );
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that we derive file names from test names as expected.
+    #[test]
+    fn file_naming() {
+        assert_eq!(file_name("foo::bar_baz"), "foo--bar_baz");
+        assert_eq!(file_name("a/b c"), "a-b-c");
+    }
+
+    /// Check that `JUnit` reports are formatted as expected.
+    #[test]
+    fn report_formatting() {
+        let failure = ChildFailure::ExitCode { code: 3 };
+        let xml = format_report(
+            "foo::bar::baz",
+            Duration::from_millis(1500),
+            Some(&failure),
+            b"hello <world>\n",
+            b"",
+        );
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuite name="test-fork" tests="1" failures="1" time="1.500">
+  <testcase classname="foo::bar" name="baz" time="1.500">
+    <failure message="child exited unsuccessfully with exit code 3">child exited unsuccessfully with exit code 3</failure>
+    <system-out>hello &lt;world&gt;
+</system-out>
+    <system-err></system-err>
+  </testcase>
+</testsuite>
+"#;
+        assert_eq!(xml, expected);
+    }
+}
@@ -0,0 +1,69 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Detection of processes leaked by a child.
+//!
+//! On Unix systems, each child runs in a process group of its own, led
+//! by the child itself. Any process remaining in this group after the
+//! child exited was spawned (directly or indirectly) by it and leaked.
+
+
+/// Find all (non-zombie) processes in the process group with the given
+/// ID, returning a human readable description of each.
+#[cfg(target_os = "linux")]
+pub(crate) fn find_leaked(pgid: u32) -> Vec<String> {
+    use std::fs;
+
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut leaked = entries
+        .filter_map(|entry| {
+            let pid = entry.ok()?.file_name().to_str()?.parse::<u32>().ok()?;
+            // The format is `<pid> (<comm>) <state> <ppid> <pgrp> ...`,
+            // where `comm` may contain spaces and parentheses.
+            let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+            let (head, tail) = stat.rsplit_once(')')?;
+            let (_, comm) = head.split_once('(')?;
+            let mut fields = tail.split_whitespace();
+            let state = fields.next()?;
+            let _ppid = fields.next()?;
+            let pgrp = fields.next()?.parse::<u32>().ok()?;
+
+            (pgrp == pgid && state != "Z").then(|| (pid, format!("pid {pid} ({comm})")))
+        })
+        .collect::<Vec<_>>();
+    let () = leaked.sort_unstable();
+    leaked.into_iter().map(|(_pid, desc)| desc).collect()
+}
+
+/// Find all processes in the process group with the given ID,
+/// returning a human readable description of each.
+#[cfg(all(unix, not(target_os = "linux")))]
+pub(crate) fn find_leaked(pgid: u32) -> Vec<String> {
+    let pgid = match libc::pid_t::try_from(pgid) {
+        Ok(pgid) => pgid,
+        Err(_) => return Vec::new(),
+    };
+
+    // We have no portable way of enumerating the members of a process
+    // group, but we can check whether there are any.
+    // SAFETY: `kill` is always safe to call.
+    let rc = unsafe { libc::kill(-pgid, 0) };
+    if rc == 0 {
+        vec![format!("unknown processes in process group {pgid}")]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Find all processes in the process group with the given ID.
+///
+/// Process groups are not supported on this platform, so no leaked
+/// processes are ever reported.
+#[cfg(not(unix))]
+pub(crate) fn find_leaked(_pgid: u32) -> Vec<String> {
+    Vec::new()
+}
@@ -16,17 +16,111 @@
 mod sugar;
 #[macro_use]
 mod fork_test;
+#[macro_use]
+mod log;
+mod artifacts;
+mod backend;
+mod bench;
+mod builder;
+#[cfg(target_os = "linux")]
+mod caps;
+mod child;
 mod cmdline;
+mod color;
+#[cfg(feature = "container")]
+mod container;
+mod debug;
+mod dry_run;
+mod entry;
 mod error;
+mod events;
+mod exe;
+mod failure;
+#[cfg(target_os = "linux")]
+mod faketime;
+#[cfg(unix)]
+mod fds;
 mod fork;
+mod golden;
+mod handle;
+mod heartbeat;
+mod info;
+mod interrupt;
+mod junit;
+mod leak;
+mod list;
+#[cfg(target_os = "linux")]
+mod namespace;
+mod outcome;
+mod panic_hook;
+#[cfg(all(target_os = "linux", feature = "perf"))]
+mod perf;
+mod persist;
 mod procmac;
+#[cfg(all(unix, feature = "pty"))]
+mod pty;
+#[cfg(all(unix, feature = "raw-fork"))]
+mod raw;
+mod record;
+mod resource;
+mod sanitizer;
+mod sched;
+mod seed;
+mod serial;
+#[cfg(unix)]
+mod shared;
+mod stack;
+mod summary;
+#[cfg(unix)]
+mod sys;
+#[cfg(feature = "tracing")]
+pub mod trace;
 
+pub use crate::artifacts::artifact_dir;
+pub use crate::artifacts::Artifacts;
+pub use crate::backend::ChildProcess;
+pub use crate::backend::CommandBackend;
+pub use crate::backend::Launched;
+pub use crate::backend::ProcessBackend;
+pub use crate::bench::BenchSummary;
+pub use crate::bench::Bencher;
+pub use crate::bench::Measure;
+pub use crate::builder::Builder;
+pub use crate::child::reap_detached;
+pub use crate::child::ChildWrapper;
+pub use crate::cmdline::UnknownFlags;
+pub use crate::color::Color;
+#[cfg(feature = "container")]
+pub use crate::container::ContainerBackend;
+pub use crate::entry::enter_main;
+pub use crate::error::Error;
+pub use crate::error::Result;
+pub use crate::failure::ChildFailure;
+pub use crate::failure::OpaquePayload;
 pub use crate::fork::fork;
+pub use crate::fork::fork_custom;
 pub use crate::fork::fork_in_out;
+pub use crate::fork::fork_in_out_vec;
 #[doc(hidden)]
 pub use crate::fork_test::fix_module_path;
+pub use crate::handle::ForkHandle;
+pub use crate::heartbeat::heartbeat;
+pub use crate::info::fork_info;
+pub use crate::info::ForkInfo;
+pub use crate::outcome::ForkOutcome;
+pub use crate::panic_hook::PayloadType;
+#[cfg(all(target_os = "linux", feature = "perf"))]
+pub use crate::perf::PerfCounters;
+pub use crate::resource::reserved_ports;
+pub use crate::resource::unique_token;
+pub use crate::seed::seed;
+#[cfg(unix)]
+pub use crate::shared::SharedBuffer;
 pub use crate::sugar::ForkId;
 
 pub use crate::procmac::try_bench;
+pub use crate::procmac::try_bench_stable;
+pub use crate::procmac::try_config;
 pub use crate::procmac::try_fork;
+pub use crate::procmac::try_main;
 pub use crate::procmac::try_test;
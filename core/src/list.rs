@@ -0,0 +1,89 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Listing of forked tests instead of running them.
+//!
+//! If the `TEST_FORK_LIST` environment variable is set, no child
+//! processes are spawned. Instead, each fork point encountered emits a
+//! single line record on stderr:
+//! `test-fork-list:\t<test-name>\t<fork-id>\t<occurrence>`, allowing
+//! tooling to discover which tests of a binary fork (and how often).
+//! Records are written to stderr directly, bypassing any output
+//! capturing by the test harness.
+
+use std::env;
+use std::io;
+use std::io::Write as _;
+
+use crate::record::escape;
+
+
+/// The environment variable requesting the listing of fork points.
+const LIST_ENV: &str = "TEST_FORK_LIST";
+/// The prefix identifying a listing record line.
+const RECORD_PREFIX: &str = "test-fork-list:";
+
+
+/// Check whether listing fork points instead of spawning children is
+/// requested.
+pub(crate) fn requested() -> bool {
+    env::var_os(LIST_ENV).is_some()
+}
+
+/// Format the record for the given occurrence of a fork point,
+/// including the trailing newline.
+fn to_line(test_name: &str, fork_id: &str, occurrence: usize) -> String {
+    format!(
+        "{RECORD_PREFIX}\t{}\t{}\t{occurrence}\n",
+        escape(test_name),
+        escape(fork_id)
+    )
+}
+
+/// Emit the record for the given occurrence of a fork point.
+pub(crate) fn emit(test_name: &str, fork_id: &str, occurrence: usize) {
+    // Write the record in one go, to prevent interleaving with the
+    // output of other threads.
+    let _result = io::stderr().write_all(to_line(test_name, fork_id, occurrence).as_bytes());
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::fork::fork_int;
+    use crate::Builder;
+
+
+    /// Check that fork points are listed instead of spawning children
+    /// if requested.
+    #[test]
+    fn fork_point_listing() {
+        fork_int(
+            fork_test_name!(fork_point_listing),
+            fork_id!(),
+            |cmd| {
+                cmd.env(LIST_ENV, "1");
+            },
+            |child| {
+                let output = child.wait_with_output().unwrap();
+                assert!(output.status.success());
+                let stderr = String::from_utf8(output.stderr).unwrap();
+                let expected = to_line("list::test::listed", ":listed", 0)
+                    + &to_line("list::test::listed", ":listed", 1);
+                assert!(stderr.contains(&expected), "{stderr}");
+            },
+            || {
+                for _ in 0..2 {
+                    let () = Builder::new()
+                        .fork(fork_id!("listed"), "list::test::listed", || {
+                            panic!("test should not run")
+                        })
+                        .unwrap();
+                }
+            },
+        )
+        .unwrap()
+    }
+}
@@ -0,0 +1,90 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Debug logging of the fork machinery itself.
+//!
+//! If the `TEST_FORK_LOG` environment variable is set to `debug`, the
+//! decisions taken while forking (e.g., based on the occurrence chain),
+//! the command line children are spawned with, their exit statuses,
+//! and the killing of processes are logged to stderr, bypassing any
+//! output capturing by the test harness. Each line is prefixed with the
+//! process identifier of the logging process, as parent and children
+//! log to the same stream.
+
+use std::env;
+use std::fmt::Arguments;
+use std::io;
+use std::io::Write as _;
+use std::process;
+use std::sync::OnceLock;
+
+
+/// The environment variable controlling debug logging.
+const LOG_ENV: &str = "TEST_FORK_LOG";
+
+
+/// Log a message, if debug logging is enabled.
+macro_rules! log {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled() {
+            let () = $crate::log::write(format_args!($($arg)*));
+        }
+    };
+}
+
+
+/// Check whether debug logging is enabled.
+pub(crate) fn enabled() -> bool {
+    static ENABLED: OnceLock<bool> = OnceLock::new();
+
+    *ENABLED.get_or_init(|| env::var(LOG_ENV).is_ok_and(|level| level == "debug"))
+}
+
+/// Write a log message to stderr.
+pub(crate) fn write(args: Arguments<'_>) {
+    // Write the line in one go, to prevent interleaving with the
+    // output of other threads.
+    let line = format!("test-fork[{}]: {args}\n", process::id());
+    let _result = io::stderr().write_all(line.as_bytes());
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::fork::fork_int;
+    use crate::Builder;
+
+
+    /// Check that the fork machinery logs its decisions if requested.
+    #[test]
+    fn debug_logging() {
+        fork_int(
+            fork_test_name!(debug_logging),
+            fork_id!(),
+            |cmd| {
+                cmd.env(LOG_ENV, "debug");
+            },
+            |child| {
+                let output = child.wait_with_output().unwrap();
+                assert!(output.status.success());
+                let stderr = String::from_utf8(output.stderr).unwrap();
+                assert!(
+                    stderr.contains("forking `log::test::debug_logging`"),
+                    "{stderr}"
+                );
+                assert!(stderr.contains("spawning child:"), "{stderr}");
+                assert!(stderr.contains("running as child"), "{stderr}");
+                assert!(stderr.contains("exited with"), "{stderr}");
+            },
+            || {
+                assert!(enabled());
+                let () = Builder::new()
+                    .fork(fork_id!("logged"), fork_test_name!(debug_logging), || ())
+                    .unwrap();
+            },
+        )
+        .unwrap()
+    }
+}
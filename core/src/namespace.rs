@@ -0,0 +1,394 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Isolation of the child via Linux namespaces.
+//!
+//! The child is moved into a new user namespace just before it
+//! executes, which allows for creating further namespaces without
+//! special privileges:
+//! - To isolate file system modifications, a new mount namespace is
+//!   created. There, a `tmpfs` is mounted and used as the upper layer
+//!   of an overlay mount on top of each of the selected directories.
+//!   All modifications the child makes to these directories end up in
+//!   the `tmpfs` and vanish along with the namespace once the child
+//!   exits.
+//! - To guarantee the cleanup of all processes spawned by the child, a
+//!   new PID namespace is created. Because only processes created
+//!   after entering it become members, the child forks once more. The
+//!   resulting process runs the test as PID 1 of the namespace, while
+//!   the original one merely waits for it and relays its exit status.
+//!   When PID 1 exits, the kernel kills all other processes in the
+//!   namespace.
+
+use std::env;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt as _;
+use std::os::unix::process::CommandExt as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::ptr;
+
+use crate::fork::EX_SOFTWARE;
+use crate::sys::check;
+
+
+/// Convert `value` into a [`CString`].
+fn c_string(value: impl Into<Vec<u8>>) -> io::Result<CString> {
+    CString::new(value).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+/// Write `data` to the file at `path`.
+///
+/// This function is async-signal-safe.
+fn write_file(path: &CString, data: &CString) -> io::Result<()> {
+    // SAFETY: `path` is a valid NUL terminated string.
+    let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC) };
+    let _rc = check(fd)?;
+    let data = data.as_bytes();
+    // SAFETY: `data` is valid for reads of `data.len()` bytes.
+    let rc = unsafe { libc::write(fd, data.as_ptr().cast(), data.len()) };
+    // SAFETY: `fd` is a valid file descriptor owned by us.
+    let _rc = unsafe { libc::close(fd) };
+    if rc == -1 {
+        return Err(io::Error::last_os_error())
+    }
+    Ok(())
+}
+
+/// Mount a file system.
+///
+/// This function is async-signal-safe.
+fn mount(
+    source: Option<&CString>,
+    target: &CString,
+    fstype: Option<&CString>,
+    flags: libc::c_ulong,
+    data: Option<&CString>,
+) -> io::Result<()> {
+    let ptr = |s: Option<&CString>| s.map(|s| s.as_ptr()).unwrap_or(ptr::null());
+    // SAFETY: All pointers are either NULL or point to valid NUL
+    //         terminated strings.
+    let rc = unsafe {
+        libc::mount(
+            ptr(source),
+            target.as_ptr(),
+            ptr(fstype),
+            flags,
+            ptr(data).cast(),
+        )
+    };
+    let _rc = check(rc)?;
+    Ok(())
+}
+
+
+/// Everything required for setting up the namespaces of the child,
+/// prepared in advance so that no allocations are necessary after
+/// forking.
+#[derive(Debug)]
+struct Setup {
+    setgroups: CString,
+    deny: CString,
+    uid_map: CString,
+    uid_mapping: CString,
+    gid_map: CString,
+    gid_mapping: CString,
+    root: CString,
+    tmpfs: CString,
+    overlay: CString,
+    scratch: CString,
+    /// The directories to create in the scratch `tmpfs`.
+    dirs: Vec<CString>,
+    /// The directories to overlay, along with the mount options for
+    /// doing so.
+    overlays: Vec<(CString, CString)>,
+    /// Whether to run the child in a PID namespace of its own.
+    pid_namespace: bool,
+}
+
+impl Setup {
+    fn new(scratch: &Path, dirs: &[PathBuf], pid_namespace: bool) -> io::Result<Self> {
+        // SAFETY: `getuid` is always safe to call.
+        let uid = unsafe { libc::getuid() };
+        // SAFETY: `getgid` is always safe to call.
+        let gid = unsafe { libc::getgid() };
+        let mut setup = Self {
+            setgroups: c_string("/proc/self/setgroups")?,
+            deny: c_string("deny")?,
+            uid_map: c_string("/proc/self/uid_map")?,
+            uid_mapping: c_string(format!("{uid} {uid} 1"))?,
+            gid_map: c_string("/proc/self/gid_map")?,
+            gid_mapping: c_string(format!("{gid} {gid} 1"))?,
+            root: c_string("/")?,
+            tmpfs: c_string("tmpfs")?,
+            overlay: c_string("overlay")?,
+            scratch: c_string(scratch.as_os_str().as_bytes())?,
+            dirs: Vec::new(),
+            overlays: Vec::new(),
+            pid_namespace,
+        };
+
+        for (idx, dir) in dirs.iter().enumerate() {
+            let dir = dir.canonicalize()?;
+            let upper = scratch.join(format!("upper{idx}"));
+            let work = scratch.join(format!("work{idx}"));
+            let mut options = b"lowerdir=".to_vec();
+            let () = options.extend_from_slice(dir.as_os_str().as_bytes());
+            let () = options.extend_from_slice(b",upperdir=");
+            let () = options.extend_from_slice(upper.as_os_str().as_bytes());
+            let () = options.extend_from_slice(b",workdir=");
+            let () = options.extend_from_slice(work.as_os_str().as_bytes());
+
+            let () = setup.dirs.push(c_string(upper.as_os_str().as_bytes())?);
+            let () = setup.dirs.push(c_string(work.as_os_str().as_bytes())?);
+            let () = setup
+                .overlays
+                .push((c_string(dir.as_os_str().as_bytes())?, c_string(options)?));
+        }
+        Ok(setup)
+    }
+
+    /// Set up the namespaces of the current process.
+    ///
+    /// This function is async-signal-safe.
+    fn apply(&self) -> io::Result<()> {
+        let mut flags = libc::CLONE_NEWUSER;
+        if !self.overlays.is_empty() {
+            flags |= libc::CLONE_NEWNS;
+        }
+        if self.pid_namespace {
+            flags |= libc::CLONE_NEWPID;
+        }
+
+        // SAFETY: `unshare` is always safe to call.
+        let _rc = check(unsafe { libc::unshare(flags) })?;
+        // Map our user and group onto themselves, so that files keep
+        // their ownership.
+        let () = write_file(&self.setgroups, &self.deny)?;
+        let () = write_file(&self.uid_map, &self.uid_mapping)?;
+        let () = write_file(&self.gid_map, &self.gid_mapping)?;
+
+        if !self.overlays.is_empty() {
+            let () = self.mount_overlays()?;
+        }
+        if self.pid_namespace {
+            let () = enter_pid_namespace()?;
+        }
+        Ok(())
+    }
+
+    /// Mount the overlays.
+    ///
+    /// This function is async-signal-safe.
+    fn mount_overlays(&self) -> io::Result<()> {
+        // Make sure that none of our mounts propagate to the parent's
+        // namespace.
+        let () = mount(
+            None,
+            &self.root,
+            None,
+            libc::MS_REC | libc::MS_PRIVATE,
+            None,
+        )?;
+        let () = mount(Some(&self.tmpfs), &self.scratch, Some(&self.tmpfs), 0, None)?;
+
+        for dir in &self.dirs {
+            // SAFETY: `dir` is a valid NUL terminated string.
+            let _rc = check(unsafe { libc::mkdir(dir.as_ptr(), 0o700) })?;
+        }
+        for (dir, options) in &self.overlays {
+            let () = mount(
+                Some(&self.overlay),
+                dir,
+                Some(&self.overlay),
+                0,
+                Some(options),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Fork into the PID namespace created previously.
+///
+/// Returns in the new process, which is PID 1 of the namespace. The
+/// current process waits for it to exit and then exits the same way.
+///
+/// This function is async-signal-safe.
+fn enter_pid_namespace() -> io::Result<()> {
+    // SAFETY: We are running in a freshly forked child, which only
+    //         contains a single thread.
+    let pid = unsafe { libc::fork() };
+    let _rc = check(pid)?;
+    if pid == 0 {
+        // Make sure that we don't outlive the process relaying our
+        // exit status, e.g., in case it got killed.
+        // SAFETY: `prctl` is always safe to call.
+        let _rc = check(unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) })?;
+        return Ok(())
+    }
+
+    let mut status = 0;
+    loop {
+        // SAFETY: `status` is valid for writes.
+        let rc = unsafe { libc::waitpid(pid, &mut status, 0) };
+        if rc != -1 {
+            break
+        }
+        if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            // SAFETY: `_exit` is always safe to call.
+            unsafe { libc::_exit(EX_SOFTWARE) }
+        }
+    }
+
+    if libc::WIFSIGNALED(status) {
+        let signal = libc::WTERMSIG(status);
+        // SAFETY: `signal` is always safe to call.
+        let _handler = unsafe { libc::signal(signal, libc::SIG_DFL) };
+        // SAFETY: `raise` is always safe to call.
+        let _rc = unsafe { libc::raise(signal) };
+    }
+    // SAFETY: `_exit` is always safe to call.
+    unsafe { libc::_exit(libc::WEXITSTATUS(status)) }
+}
+
+
+/// Configure the child process spawned by `command` to run in
+/// namespaces of its own.
+///
+/// The child sees a throwaway overlay on top of each of the directories
+/// in `dirs` and, if `pid_namespace` is `true`, runs as PID 1 of a new
+/// PID namespace.
+pub(crate) fn configure(
+    command: &mut Command,
+    dirs: &[PathBuf],
+    pid_namespace: bool,
+) -> io::Result<()> {
+    // The mount point for the scratch `tmpfs`. It is only ever mounted
+    // over in the child's mount namespace, so it can be shared by all
+    // children.
+    let scratch = env::temp_dir().join("test-fork-overlay");
+    if !dirs.is_empty() {
+        let () = fs::create_dir_all(&scratch)?;
+    }
+    let setup = Setup::new(&scratch, dirs, pid_namespace)?;
+
+    // SAFETY: `Setup::apply` is async-signal-safe.
+    let _command = unsafe { command.pre_exec(move || setup.apply()) };
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::mem;
+    use std::process;
+    use std::slice;
+    use std::thread;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use crate::fork::fork_int;
+
+
+    /// Check that modifications to an overlaid directory are not
+    /// visible outside of the child.
+    #[test]
+    fn modification_isolation() {
+        // Parent and child need to agree on the directory, so we can't
+        // use a randomized name.
+        let dir = env::temp_dir().join("test-fork-overlay-test");
+        let () = fs::create_dir_all(&dir).unwrap();
+        let () = fs::write(dir.join("existing"), "original").unwrap();
+
+        fork_int(
+            fork_test_name!(modification_isolation),
+            fork_id!(),
+            |cmd| configure(cmd, slice::from_ref(&dir), false).unwrap(),
+            |mut child| assert!(child.wait().unwrap().success()),
+            || {
+                assert_eq!(
+                    fs::read_to_string(dir.join("existing")).unwrap(),
+                    "original"
+                );
+                let () = fs::write(dir.join("existing"), "modified").unwrap();
+                let () = fs::write(dir.join("new"), "new").unwrap();
+                assert_eq!(
+                    fs::read_to_string(dir.join("existing")).unwrap(),
+                    "modified"
+                );
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("existing")).unwrap(),
+            "original"
+        );
+        assert!(!dir.join("new").exists());
+        let () = fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Check that processes spawned by a child running in a PID
+    /// namespace of its own are killed when it exits.
+    #[test]
+    fn pid_namespace_cleanup() {
+        // A (hopefully) unique argument identifying the process we
+        // spawn.
+        const MARKER: &str = "3600.7305";
+
+        fn is_running() -> bool {
+            fs::read_dir("/proc").unwrap().flatten().any(|entry| {
+                fs::read(entry.path().join("cmdline")).is_ok_and(|cmdline| {
+                    cmdline
+                        .split(|b| *b == 0)
+                        .any(|arg| arg == MARKER.as_bytes())
+                })
+            })
+        }
+
+        fork_int(
+            fork_test_name!(pid_namespace_cleanup),
+            fork_id!(),
+            |cmd| configure(cmd, &[], true).unwrap(),
+            |child| {
+                let output = child.wait_with_output().unwrap();
+                assert!(
+                    output.status.success(),
+                    "{}",
+                    String::from_utf8_lossy(&output.stderr)
+                );
+
+                let start = Instant::now();
+                while is_running() {
+                    assert!(start.elapsed() < Duration::from_secs(30));
+                    let () = thread::sleep(Duration::from_millis(10));
+                }
+            },
+            || {
+                assert_eq!(process::id(), 1);
+                // Move the process out of our process group, so that
+                // only the PID namespace takes care of it.
+                let grandchild = Command::new("sleep")
+                    .arg(MARKER)
+                    .process_group(0)
+                    .spawn()
+                    .unwrap();
+                let () = mem::forget(grandchild);
+
+                let start = Instant::now();
+                while !is_running() {
+                    assert!(start.elapsed() < Duration::from_secs(30));
+                    let () = thread::sleep(Duration::from_millis(10));
+                }
+            },
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,36 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+use std::process::ExitStatus;
+use std::time::Duration;
+
+#[cfg(all(target_os = "linux", feature = "perf"))]
+use crate::perf::PerfCounters;
+
+
+/// The outcome of a successfully run child process.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct ForkOutcome {
+    /// The child's exit status.
+    pub status: ExitStatus,
+    /// The time the child ran for.
+    pub duration: Duration,
+    /// The data the child wrote to its standard output.
+    ///
+    /// Output is only captured if requested via
+    /// [`Builder::capture_output`][crate::Builder::capture_output].
+    /// Otherwise it is forwarded to the parent's output and this
+    /// buffer is empty.
+    pub stdout: Vec<u8>,
+    /// The data the child wrote to its standard error.
+    ///
+    /// See [`ForkOutcome::stdout`] for when output is captured.
+    pub stderr: Vec<u8>,
+    /// The performance counters collected for the child.
+    ///
+    /// Counters are only collected if requested via
+    /// [`Builder::perf_counters`][crate::Builder::perf_counters].
+    #[cfg(all(target_os = "linux", feature = "perf"))]
+    pub perf_counters: Option<PerfCounters>,
+}
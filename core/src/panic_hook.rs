@@ -0,0 +1,258 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Structured reporting of panics happening in the child process.
+//!
+//! The child installs a panic hook that, in addition to invoking the
+//! previously installed hook, writes a single line record describing
+//! the panic to stderr. Because the record is written in one go, it
+//! does not get interleaved with output of other threads and the parent
+//! can reliably pick it out of the child's stderr.
+//!
+//! A record has the form
+//! `test-fork-panic:\t<thread>\t<file>\t<line>\t<column>\t<payload>\t<message>`,
+//! with `<payload>` being the type of the panic payload (`str`,
+//! `string`, or `other`) and tabs, newlines, and backslashes in the
+//! individual fields escaped.
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::io;
+use std::io::Write as _;
+use std::panic;
+use std::panic::PanicHookInfo;
+use std::process;
+use std::sync::Once;
+use std::thread;
+
+use crate::fork::EX_SOFTWARE;
+use crate::record;
+use crate::record::escape;
+use crate::record::unescape;
+
+
+/// The prefix identifying a panic record line.
+const RECORD_PREFIX: &str = "test-fork-panic:";
+
+/// Guard for the one-time installation of the panic hook.
+static INSTALL: Once = Once::new();
+
+
+/// The type of the payload of a panic.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PayloadType {
+    /// A `&'static str`, as produced by `panic!` with a literal.
+    Str,
+    /// A `String`, as produced by `panic!` with format arguments.
+    #[default]
+    String,
+    /// Any other type, e.g., as produced by
+    /// [`panic_any`][std::panic::panic_any].
+    Other,
+}
+
+impl PayloadType {
+    /// Retrieve the name of the type, as used in records.
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Str => "str",
+            Self::String => "string",
+            Self::Other => "other",
+        }
+    }
+
+    /// Parse a type name as produced by [`PayloadType::as_str`].
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "str" => Some(Self::Str),
+            "string" => Some(Self::String),
+            "other" => Some(Self::Other),
+            _ => None,
+        }
+    }
+}
+
+
+/// Information about a panic that happened in the child process.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PanicRecord {
+    /// The name of the panicking thread, if any.
+    pub thread: Option<String>,
+    /// The source location of the panic, as `<file>:<line>:<column>`.
+    pub location: Option<String>,
+    /// The panic message.
+    pub message: String,
+    /// The type of the panic payload.
+    pub payload: PayloadType,
+}
+
+impl PanicRecord {
+    fn from_info(info: &PanicHookInfo<'_>) -> Self {
+        let payload = info.payload();
+        let (message, payload) = if let Some(s) = payload.downcast_ref::<&str>() {
+            ((*s).to_string(), PayloadType::Str)
+        } else if let Some(s) = payload.downcast_ref::<String>() {
+            (s.clone(), PayloadType::String)
+        } else {
+            ("Box<dyn Any>".to_string(), PayloadType::Other)
+        };
+
+        Self {
+            thread: thread::current().name().map(str::to_string),
+            location: info
+                .location()
+                .map(|loc| format!("{}:{}:{}", loc.file(), loc.line(), loc.column())),
+            message,
+            payload,
+        }
+    }
+
+    /// Serialize the record into its line representation, including
+    /// the trailing newline.
+    fn to_line(&self) -> String {
+        let (file, line, column) = self
+            .location
+            .as_deref()
+            .and_then(|loc| {
+                let (rest, column) = loc.rsplit_once(':')?;
+                let (file, line) = rest.rsplit_once(':')?;
+                Some((file, line, column))
+            })
+            .unwrap_or_default();
+
+        format!(
+            "{RECORD_PREFIX}\t{}\t{}\t{line}\t{column}\t{}\t{}\n",
+            escape(self.thread.as_deref().unwrap_or_default()),
+            escape(file),
+            self.payload.as_str(),
+            escape(&self.message),
+        )
+    }
+
+    /// Parse a single line (without trailing newline) into a record.
+    fn from_line(line: &str) -> Option<Self> {
+        let rest = line.strip_prefix(RECORD_PREFIX)?.strip_prefix('\t')?;
+        let mut fields = rest.splitn(6, '\t');
+        let thread = unescape(fields.next()?);
+        let file = unescape(fields.next()?);
+        let line = fields.next()?;
+        let column = fields.next()?;
+        let payload = PayloadType::parse(fields.next()?)?;
+        let message = unescape(fields.next()?);
+
+        let record = Self {
+            thread: (!thread.is_empty()).then_some(thread),
+            location: (!file.is_empty()).then(|| format!("{file}:{line}:{column}")),
+            message,
+            payload,
+        };
+        Some(record)
+    }
+}
+
+impl Display for PanicRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let thread = self.thread.as_deref().unwrap_or("<unnamed>");
+        write!(f, "thread '{thread}' panicked")?;
+        if let Some(location) = &self.location {
+            write!(f, " at {location}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+
+/// Install the panic hook writing panic records to stderr.
+///
+/// The previously installed hook is still invoked, so that the usual
+/// human readable panic output is unaffected.
+///
+/// When built with `panic = "abort"`, the panic can't be caught, so the
+/// hook exits the child right away, with the same exit code as used
+/// for caught panics. That way, children behave the same irrespective
+/// of the panic strategy and no core dumps are produced.
+pub(crate) fn install() {
+    let () = INSTALL.call_once(|| {
+        let prev = panic::take_hook();
+        let () = panic::set_hook(Box::new(move |info| {
+            let () = write_record(&PanicRecord::from_info(info));
+            let () = prev(info);
+
+            if cfg!(panic = "abort") {
+                process::exit(EX_SOFTWARE)
+            }
+        }));
+    });
+}
+
+/// Write the given record to stderr.
+fn write_record(record: &PanicRecord) {
+    let line = record.to_line();
+    // We are already panicking. If stderr is unusable there is nothing
+    // sensible left to do about it.
+    let _result = io::stderr().lock().write_all(line.as_bytes());
+}
+
+/// Report a panic that is about to be raised without invoking the
+/// panic hook, as [`panic::resume_unwind`] does, the way the hook would
+/// have.
+///
+/// That only has an effect in a child, where the hook is installed.
+pub(crate) fn report_unhooked(record: &PanicRecord) {
+    if INSTALL.is_completed() {
+        let () = write_record(record);
+
+        if cfg!(panic = "abort") {
+            process::exit(EX_SOFTWARE)
+        }
+    }
+}
+
+
+/// Extract all panic records from a child's stderr, returning them
+/// along with the remaining output.
+pub(crate) fn extract(stderr: &[u8]) -> (Vec<PanicRecord>, Vec<u8>) {
+    record::extract(stderr, PanicRecord::from_line)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that panic records can be round tripped through their
+    /// line representation.
+    #[test]
+    fn record_round_trip() {
+        let record = PanicRecord {
+            thread: Some("worker\t1".to_string()),
+            location: Some("src/lib.rs:42:7".to_string()),
+            message: "assertion failed\nleft: 1\\2".to_string(),
+            payload: PayloadType::String,
+        };
+        let line = record.to_line();
+        assert_eq!(line.lines().count(), 1);
+
+        let stderr = format!("some output\n{line}more output\n");
+        let (records, remainder) = extract(stderr.as_bytes());
+        assert_eq!(records, vec![record]);
+        assert_eq!(remainder, b"some output\nmore output\n");
+    }
+
+    /// Check that records lacking optional data are handled correctly.
+    #[test]
+    fn record_without_thread_and_location() {
+        let record = PanicRecord {
+            thread: None,
+            location: None,
+            message: "boom".to_string(),
+            payload: PayloadType::Other,
+        };
+        let (records, remainder) = extract(record.to_line().as_bytes());
+        assert_eq!(records, vec![record.clone()]);
+        assert!(remainder.is_empty());
+        assert_eq!(record.to_string(), "thread '<unnamed>' panicked: boom");
+    }
+}
@@ -0,0 +1,261 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Collection of performance counters for child processes via
+//! `perf_event_open(2)`.
+//!
+//! Just before spawning the child, the parent opens disabled counters
+//! for the spawning thread, which are inherited by the child and
+//! enabled only once it `exec`s. That way, the parent's own activity is
+//! not counted, but everything happening in the child (and any threads
+//! and processes it creates) from the start of the test executable on
+//! is. The values of the child's counters are folded into the parent's
+//! once the child exited. Only events happening in user space are
+//! counted, so that counters are usable with the default
+//! `perf_event_paranoid` setting.
+
+use std::cell::RefCell;
+use std::ffi::c_int;
+use std::fs::File;
+use std::io::Read as _;
+use std::mem::size_of;
+use std::os::fd::FromRawFd as _;
+use std::time::Duration;
+
+
+/// `PERF_TYPE_HARDWARE`
+const TYPE_HARDWARE: u32 = 0;
+/// `PERF_TYPE_SOFTWARE`
+const TYPE_SOFTWARE: u32 = 1;
+/// `PERF_COUNT_HW_CPU_CYCLES`
+const COUNT_HW_CPU_CYCLES: u64 = 0;
+/// `PERF_COUNT_HW_INSTRUCTIONS`
+const COUNT_HW_INSTRUCTIONS: u64 = 1;
+/// `PERF_COUNT_HW_CACHE_MISSES`
+const COUNT_HW_CACHE_MISSES: u64 = 3;
+/// `PERF_COUNT_SW_TASK_CLOCK`
+const COUNT_SW_TASK_CLOCK: u64 = 1;
+/// The `disabled` bit of `perf_event_attr`'s flags.
+const FLAG_DISABLED: u64 = 1 << 0;
+/// The `inherit` bit of `perf_event_attr`'s flags.
+const FLAG_INHERIT: u64 = 1 << 1;
+/// The `exclude_kernel` bit of `perf_event_attr`'s flags.
+const FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+/// The `exclude_hv` bit of `perf_event_attr`'s flags.
+const FLAG_EXCLUDE_HV: u64 = 1 << 6;
+/// The `enable_on_exec` bit of `perf_event_attr`'s flags.
+const FLAG_ENABLE_ON_EXEC: u64 = 1 << 12;
+/// `PERF_FORMAT_TOTAL_TIME_ENABLED`
+const FORMAT_TOTAL_TIME_ENABLED: u64 = 1 << 0;
+/// `PERF_FORMAT_TOTAL_TIME_RUNNING`
+const FORMAT_TOTAL_TIME_RUNNING: u64 = 1 << 1;
+/// `PERF_FLAG_FD_CLOEXEC`
+const FLAG_FD_CLOEXEC: u64 = 1 << 3;
+
+
+/// The first version of `struct perf_event_attr`, which is all we
+/// need and which is understood by all kernels.
+#[derive(Debug, Default)]
+#[repr(C)]
+struct PerfEventAttr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+}
+
+
+/// The values of performance counters collected for a child process.
+///
+/// Counters that are not supported by the system (e.g., hardware
+/// counters inside of a virtual machine) or that could not be opened
+/// for lack of privileges are `None`. Values are scaled to compensate
+/// for times at which the kernel had to multiplex counters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PerfCounters {
+    /// The number of CPU cycles.
+    pub cycles: Option<u64>,
+    /// The number of retired instructions.
+    pub instructions: Option<u64>,
+    /// The number of cache misses.
+    pub cache_misses: Option<u64>,
+    /// The CPU time consumed.
+    pub task_clock: Option<Duration>,
+}
+
+
+thread_local! {
+    /// The counters prepared for the next child spawned by the current
+    /// thread.
+    static PREPARED: RefCell<Option<Collector>> = const { RefCell::new(None) };
+}
+
+
+/// Open a counter for the given event for the calling thread, to be
+/// inherited by children and enabled once they `exec`.
+fn open(type_: u32, config: u64) -> Option<File> {
+    let attr = PerfEventAttr {
+        type_,
+        size: size_of::<PerfEventAttr>() as u32,
+        config,
+        read_format: FORMAT_TOTAL_TIME_ENABLED | FORMAT_TOTAL_TIME_RUNNING,
+        flags: FLAG_DISABLED
+            | FLAG_INHERIT
+            | FLAG_EXCLUDE_KERNEL
+            | FLAG_EXCLUDE_HV
+            | FLAG_ENABLE_ON_EXEC,
+        ..Default::default()
+    };
+    // Count for the calling thread on any CPU, without any group
+    // leader.
+    let pid: libc::pid_t = 0;
+    let cpu: c_int = -1;
+    let group_fd: c_int = -1;
+    // SAFETY: `attr` is a valid `perf_event_attr` of the size it
+    //         advertises and all other arguments are plain values.
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_perf_event_open,
+            &attr,
+            pid,
+            cpu,
+            group_fd,
+            FLAG_FD_CLOEXEC,
+        )
+    };
+    let fd = c_int::try_from(fd).ok().filter(|fd| *fd >= 0)?;
+    // SAFETY: `fd` is a freshly opened file descriptor owned by nobody
+    //         else.
+    let file = unsafe { File::from_raw_fd(fd) };
+    Some(file)
+}
+
+/// Scale a counter value to compensate for multiplexing, given the
+/// times the counter was enabled and actually running.
+fn scale(value: u64, enabled: u64, running: u64) -> u64 {
+    if running == 0 || running >= enabled {
+        value
+    } else {
+        let scaled = u128::from(value) * u128::from(enabled) / u128::from(running);
+        u64::try_from(scaled).unwrap_or(u64::MAX)
+    }
+}
+
+/// Read the (scaled) value of a counter.
+fn read(mut file: &File) -> Option<u64> {
+    let mut buf = [0; 3 * size_of::<u64>()];
+    let () = file.read_exact(&mut buf).ok()?;
+
+    let mut values = buf
+        .chunks_exact(size_of::<u64>())
+        .map(|chunk| u64::from_ne_bytes(<[u8; 8]>::try_from(chunk).unwrap_or_default()));
+    let mut next = || values.next().unwrap_or_default();
+    let (value, enabled, running) = (next(), next(), next());
+    Some(scale(value, enabled, running))
+}
+
+
+/// The set of counters collecting events of a child process.
+#[derive(Debug)]
+pub(crate) struct Collector {
+    cycles: Option<File>,
+    instructions: Option<File>,
+    cache_misses: Option<File>,
+    task_clock: Option<File>,
+}
+
+impl Collector {
+    /// Prepare counters for the next child spawned by the calling
+    /// thread, to be retrieved via [`Collector::take`] afterwards.
+    pub fn prepare() {
+        let collector = Self {
+            cycles: open(TYPE_HARDWARE, COUNT_HW_CPU_CYCLES),
+            instructions: open(TYPE_HARDWARE, COUNT_HW_INSTRUCTIONS),
+            cache_misses: open(TYPE_HARDWARE, COUNT_HW_CACHE_MISSES),
+            task_clock: open(TYPE_SOFTWARE, COUNT_SW_TASK_CLOCK),
+        };
+        let () = PREPARED.with(|prepared| *prepared.borrow_mut() = Some(collector));
+    }
+
+    /// Retrieve the counters previously prepared by the calling thread,
+    /// if any.
+    ///
+    /// The counters should be read and dropped as soon as the child
+    /// exited, lest they accumulate events of other processes spawned
+    /// by the thread.
+    pub fn take() -> Option<Self> {
+        PREPARED.with(RefCell::take)
+    }
+
+    /// Make the counters available to a subsequent
+    /// [`Collector::take`] by the calling thread.
+    pub fn install(self) {
+        let () = PREPARED.with(|prepared| *prepared.borrow_mut() = Some(self));
+    }
+
+    /// Read the values of all counters.
+    ///
+    /// Values of the child are only available once it exited, at which
+    /// point they include the events of all its descendants that exited
+    /// as well.
+    pub fn read(&self) -> PerfCounters {
+        PerfCounters {
+            cycles: self.cycles.as_ref().and_then(read),
+            instructions: self.instructions.as_ref().and_then(read),
+            cache_misses: self.cache_misses.as_ref().and_then(read),
+            task_clock: self
+                .task_clock
+                .as_ref()
+                .and_then(read)
+                .map(Duration::from_nanos),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Instant;
+
+    use crate::Builder;
+
+
+    /// Check that we scale multiplexed counter values correctly.
+    #[test]
+    fn value_scaling() {
+        assert_eq!(scale(100, 10, 10), 100);
+        assert_eq!(scale(100, 10, 5), 200);
+        assert_eq!(scale(100, 10, 0), 100);
+        assert_eq!(scale(u64::MAX, 2, 1), u64::MAX);
+    }
+
+    /// Check that counters get collected for a child, to the extent
+    /// supported by the system.
+    #[test]
+    fn counter_collection() {
+        let outcome = Builder::new()
+            .perf_counters(true)
+            .try_fork(fork_id!(), fork_test_name!(counter_collection), || {
+                let start = Instant::now();
+                while start.elapsed() < Duration::from_millis(20) {}
+            })
+            .unwrap();
+
+        let counters = outcome.perf_counters.unwrap();
+        if let Some(task_clock) = counters.task_clock {
+            assert!(task_clock >= Duration::from_millis(10), "{task_clock:?}");
+        }
+        if let Some(instructions) = counters.instructions {
+            assert!(instructions > 0);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Persisting of the output of child processes to files.
+//!
+//! If enabled, everything a child writes to its standard output and
+//! error is written to `<test-name>.out` and `<test-name>.err` files as
+//! it arrives, regardless of any output limit. The files are kept only
+//! if the child failed. They are written into the directory named by
+//! the `TEST_FORK_OUTPUT_DIR` environment variable, if set, and into
+//! `test-fork/` inside Cargo's target directory otherwise.
+
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::junit::file_name;
+
+
+/// The environment variable specifying the directory to write output
+/// files to.
+const OUTPUT_DIR_ENV: &str = "TEST_FORK_OUTPUT_DIR";
+/// The file created by Cargo in the root of its target directory.
+const CACHEDIR_TAG: &str = "CACHEDIR.TAG";
+
+
+/// Check whether persisting output is requested via the environment.
+pub(crate) fn requested() -> bool {
+    env::var_os(OUTPUT_DIR_ENV).is_some()
+}
+
+/// Find the directory to write output files to.
+pub(crate) fn output_dir() -> io::Result<PathBuf> {
+    if let Some(dir) = env::var_os(OUTPUT_DIR_ENV) {
+        return Ok(PathBuf::from(dir))
+    }
+
+    // Test executables reside somewhere below Cargo's target
+    // directory (e.g., in `target/debug/deps/`).
+    let exe = env::current_exe()?;
+    let target = exe
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.join(CACHEDIR_TAG).is_file())
+        .or_else(|| exe.parent())
+        .unwrap_or_else(|| Path::new("."));
+    Ok(target.join("test-fork"))
+}
+
+
+/// The files the output of a child gets persisted to.
+#[derive(Debug)]
+pub(crate) struct Persisted {
+    /// The path to the file containing the child's standard output.
+    stdout: PathBuf,
+    /// The path to the file containing the child's standard error.
+    stderr: PathBuf,
+}
+
+impl Persisted {
+    /// Create the files for persisting the output of a child running
+    /// the given test, returning them for writing.
+    ///
+    /// Output files are purely informational. Failure to create them is
+    /// reported, but does not fail the test.
+    pub fn create(test_name: &str) -> Option<(Self, File, File)> {
+        let create = || {
+            let dir = output_dir()?;
+            let () = fs::create_dir_all(&dir)?;
+            let name = file_name(test_name);
+            let slf = Self {
+                stdout: dir.join(format!("{name}.out")),
+                stderr: dir.join(format!("{name}.err")),
+            };
+            let stdout = File::create(&slf.stdout)?;
+            let stderr = File::create(&slf.stderr)?;
+            io::Result::Ok((slf, stdout, stderr))
+        };
+
+        match create() {
+            Ok(persisted) => Some(persisted),
+            Err(err) => {
+                eprintln!("test-fork: failed to create output files for `{test_name}`: {err}");
+                None
+            }
+        }
+    }
+
+    /// Keep the files if the child failed and remove them otherwise.
+    pub fn finish(self, failed: bool) {
+        if failed {
+            eprintln!(
+                "test-fork: output of child persisted to {} and {}",
+                self.stdout.display(),
+                self.stderr.display()
+            );
+        } else {
+            let _result = fs::remove_file(&self.stdout);
+            let _result = fs::remove_file(&self.stderr);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::process::ExitCode;
+
+    use crate::Builder;
+    use crate::ChildFailure;
+
+
+    /// Check that the output of a successful child is not persisted.
+    #[test]
+    fn output_removal() {
+        let test_name = fork_test_name!(output_removal);
+        let dir = output_dir().unwrap();
+        let stdout = dir.join(format!("{}.out", file_name(test_name)));
+        let stderr = dir.join(format!("{}.err", file_name(test_name)));
+
+        let _outcome = Builder::new()
+            .persist_output(true)
+            .capture_output(true)
+            .try_fork(fork_id!(), test_name, || println!("succeeded"))
+            .unwrap();
+        assert!(!stdout.exists());
+        assert!(!stderr.exists());
+    }
+
+    /// Check that the output of a failed child is persisted in its
+    /// entirety.
+    #[test]
+    fn output_persistence() {
+        let test_name = fork_test_name!(output_persistence);
+        let dir = output_dir().unwrap();
+        let stdout = dir.join(format!("{}.out", file_name(test_name)));
+        let stderr = dir.join(format!("{}.err", file_name(test_name)));
+
+        let failure = Builder::new()
+            .persist_output(true)
+            .output_limit(4)
+            .try_fork(fork_id!(), test_name, || {
+                println!("failed");
+                eprintln!("with an error");
+                ExitCode::FAILURE
+            })
+            .unwrap_err();
+        assert!(
+            matches!(failure, ChildFailure::ExitCode { .. }),
+            "{failure}"
+        );
+
+        let out = fs::read_to_string(&stdout).unwrap();
+        assert!(out.contains("failed\n"), "{out}");
+        let err = fs::read_to_string(&stderr).unwrap();
+        assert!(err.contains("with an error\n"), "{err}");
+
+        let () = fs::remove_file(&stdout).unwrap();
+        let () = fs::remove_file(&stderr).unwrap();
+    }
+}
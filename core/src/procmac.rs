@@ -1,6 +1,7 @@
 // Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
 // SPDX-License-Identifier: (Apache-2.0 OR MIT)
 
+use std::collections::HashSet;
 use std::ops::Deref as _;
 
 use proc_macro2::Ident;
@@ -10,15 +11,34 @@ use proc_macro2::TokenStream as Tokens;
 use quote::quote;
 use quote::ToTokens as _;
 
+use syn::meta;
+use syn::meta::ParseNestedMeta;
+use syn::parenthesized;
+use syn::parse::Parser as _;
+use syn::parse_quote;
+use syn::punctuated::Punctuated;
 use syn::Attribute;
+use syn::Block;
 use syn::Error;
+use syn::Expr;
+use syn::ExprArray;
+use syn::ExprLit;
 use syn::FnArg;
+use syn::Item;
 use syn::ItemFn;
+use syn::ItemMod;
+use syn::Lit;
+use syn::LitInt;
+use syn::LitStr;
+use syn::Meta;
 use syn::Pat;
+use syn::Path;
 use syn::Result;
 use syn::ReturnType;
 use syn::Signature;
+use syn::Token;
 use syn::Type;
+use syn::Visibility;
 
 
 #[derive(Debug)]
@@ -38,12 +58,553 @@ impl Kind {
 }
 
 
+/// The configuration of the Tokio runtime to run an async test on.
+#[derive(Debug, Default)]
+struct TokioArgs {
+    /// Whether to use the multi-threaded runtime flavor.
+    multi_thread: bool,
+    /// The number of worker threads of the multi-threaded runtime.
+    worker_threads: Option<usize>,
+}
+
+impl TokioArgs {
+    fn parse(meta: ParseNestedMeta<'_>) -> Result<Self> {
+        let mut args = Self::default();
+        if !meta.input.is_empty() && !meta.input.peek(Token![,]) {
+            let () = meta.parse_nested_meta(|meta| {
+                if meta.path.is_ident("flavor") {
+                    let lit = meta.value()?.parse::<LitStr>()?;
+                    args.multi_thread =
+                        match lit.value().as_str() {
+                            "current_thread" => false,
+                            "multi_thread" => true,
+                            _ => return Err(Error::new_spanned(
+                                lit,
+                                "unsupported flavor (expected `current_thread` or `multi_thread`)",
+                            )),
+                        };
+                    Ok(())
+                } else if meta.path.is_ident("worker_threads") {
+                    let lit = meta.value()?.parse::<LitInt>()?;
+                    args.worker_threads = Some(lit.base10_parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported tokio argument"))
+                }
+            })?;
+        }
+
+        if args.worker_threads.is_some() && !args.multi_thread {
+            return Err(meta.error("`worker_threads` requires the `multi_thread` flavor"))
+        }
+        Ok(args)
+    }
+
+    /// Generate an expression running `block` to completion on a newly
+    /// created runtime.
+    fn block_on(&self, block: &Block) -> Tokens {
+        let builder = if self.multi_thread {
+            let worker_threads = self
+                .worker_threads
+                .map(|count| quote! { .worker_threads(#count) });
+            quote! { ::tokio::runtime::Builder::new_multi_thread() #worker_threads }
+        } else {
+            quote! { ::tokio::runtime::Builder::new_current_thread() }
+        };
+
+        quote! {
+            #builder
+                .enable_all()
+                .build()
+                .expect("failed to build Tokio runtime")
+                .block_on(async #block)
+        }
+    }
+}
+
+
+/// The async runtime to run an async test on.
+#[derive(Debug)]
+enum Runtime {
+    /// A Tokio runtime with the given configuration.
+    Tokio(TokioArgs),
+    /// A runtime represented by the path of a `block_on` style function.
+    BlockOn(Tokens),
+}
+
+impl Runtime {
+    /// Parse the value of the `runtime` argument, which is either the
+    /// name of a well-known runtime or the path of a `block_on` style
+    /// function.
+    fn parse_path(path: Path) -> Self {
+        let block_on = if path.is_ident("async_std") {
+            quote! { ::async_std::task::block_on }
+        } else if path.is_ident("smol") {
+            quote! { ::smol::block_on }
+        } else if path.is_ident("tokio") {
+            return Self::Tokio(TokioArgs::default())
+        } else {
+            path.into_token_stream()
+        };
+        Self::BlockOn(block_on)
+    }
+
+    /// Generate an expression running `block` to completion on the
+    /// runtime.
+    fn block_on(&self, block: &Block) -> Tokens {
+        match self {
+            Self::Tokio(tokio) => tokio.block_on(block),
+            Self::BlockOn(block_on) => quote! { #block_on(async #block) },
+        }
+    }
+}
+
+
+/// Arguments accepted by our attributes.
+#[derive(Debug, Default)]
+struct Args {
+    /// The heartbeat timeout, in milliseconds.
+    heartbeat_ms: Option<u64>,
+    /// The maximum number of bytes of output to retain per stream.
+    output_limit: Option<usize>,
+    /// Whether to prefix forwarded output lines with the test's name.
+    prefix_output: bool,
+    /// Whether to persist the child's output to files.
+    persist_output: bool,
+    /// The `Artifacts` variant naming the treatment of the child's
+    /// artifact directory.
+    artifacts: Option<Ident>,
+    /// The `Color` variant describing the treatment of colors in the
+    /// child's output.
+    color: Option<Ident>,
+    /// Whether to fail on sanitizer reports.
+    sanitizer_check: bool,
+    /// Whether to fail on leaked processes.
+    leak_check: bool,
+    /// The environment variables to inherit, if the child's environment
+    /// is to be cleared.
+    clean_env: Option<Vec<String>>,
+    /// The path to the golden file to compare the test's output
+    /// against, relative to the crate's manifest directory.
+    expected_output: Option<String>,
+    /// The data to feed to the child's standard input.
+    stdin: Option<String>,
+    /// The path to a file to feed to the child's standard input,
+    /// relative to the crate's manifest directory.
+    stdin_file: Option<String>,
+    /// The path of the function to run in the parent before the child
+    /// is spawned.
+    before: Option<Tokens>,
+    /// The path of the function to run in the parent after the child
+    /// exited.
+    after: Option<Tokens>,
+    /// The path of the function producing the fixture to pass to the
+    /// test.
+    fixture: Option<Tokens>,
+    /// The runtime to run an async test on in the child, if any.
+    runtime: Option<Runtime>,
+    /// Whether to create the child via a plain `fork`.
+    raw_fork: bool,
+    /// The `cfg` predicate that has to hold for the test to be run in
+    /// a child process, if any.
+    fork_if: Option<Tokens>,
+    /// The name of the environment variable that has to be set for the
+    /// test to be run in a child process, if any.
+    opt_in_env: Option<String>,
+    /// Whether the child is to wait for a debugger.
+    debug: bool,
+    /// The niceness of the child.
+    nice: Option<i32>,
+    /// The indices of the CPUs the child may run on.
+    cpu_affinity: Option<Vec<usize>>,
+    /// The name of the serialization lock to hold for the child.
+    serial: Option<String>,
+    /// The number of TCP ports to reserve for the child.
+    reserve_ports: Option<usize>,
+    /// The number of benchmark iterations to run before measuring.
+    warmup_iters: Option<u64>,
+    /// The minimum number of benchmark iterations to measure.
+    min_iters: Option<u64>,
+    /// The `Measure` variant naming the clock to measure benchmark
+    /// iterations with.
+    measure: Option<Ident>,
+    /// The environment variables to set for each of the children to
+    /// run the test in, if a configuration matrix is used.
+    matrix: Option<Vec<Vec<(String, String)>>>,
+    /// The number of instances of the child to run concurrently.
+    instances: Option<usize>,
+}
+
+impl Args {
+    fn parse(attr: Tokens) -> Result<Self> {
+        let mut args = Self::default();
+        let parser = meta::parser(|meta| {
+            if meta.path.is_ident("heartbeat_ms") {
+                let lit = meta.value()?.parse::<LitInt>()?;
+                args.heartbeat_ms = Some(lit.base10_parse()?);
+                Ok(())
+            } else if meta.path.is_ident("output_limit") {
+                let lit = meta.value()?.parse::<LitInt>()?;
+                args.output_limit = Some(lit.base10_parse()?);
+                Ok(())
+            } else if meta.path.is_ident("prefix_output") {
+                args.prefix_output = true;
+                Ok(())
+            } else if meta.path.is_ident("persist_output") {
+                args.persist_output = true;
+                Ok(())
+            } else if meta.path.is_ident("artifacts") {
+                let lit = meta.value()?.parse::<LitStr>()?;
+                let variant = match lit.value().as_str() {
+                    "keep" => "Keep",
+                    "on_failure" => "KeepOnFailure",
+                    _ => {
+                        return Err(Error::new_spanned(
+                            lit,
+                            "unsupported artifacts treatment (expected `keep` or `on_failure`)",
+                        ))
+                    }
+                };
+                args.artifacts = Some(Ident::new(variant, lit.span()));
+                Ok(())
+            } else if meta.path.is_ident("color") {
+                let lit = meta.value()?.parse::<LitStr>()?;
+                let variant = match lit.value().as_str() {
+                    "auto" => "Auto",
+                    "always" => "Always",
+                    "never" => "Never",
+                    _ => {
+                        return Err(Error::new_spanned(
+                            lit,
+                            "unsupported color (expected `auto`, `always`, or `never`)",
+                        ))
+                    }
+                };
+                args.color = Some(Ident::new(variant, lit.span()));
+                Ok(())
+            } else if meta.path.is_ident("sanitizer_check") {
+                args.sanitizer_check = true;
+                Ok(())
+            } else if meta.path.is_ident("leak_check") {
+                args.leak_check = true;
+                Ok(())
+            } else if meta.path.is_ident("clean_env") {
+                let mut allow = Vec::new();
+                if !meta.input.is_empty() && !meta.input.peek(Token![,]) {
+                    let () = meta.parse_nested_meta(|meta| {
+                        if meta.path.is_ident("allow") {
+                            let array = meta.value()?.parse::<ExprArray>()?;
+                            for elem in array.elems {
+                                match elem {
+                                    Expr::Lit(ExprLit {
+                                        lit: Lit::Str(lit), ..
+                                    }) => allow.push(lit.value()),
+                                    elem => {
+                                        return Err(Error::new_spanned(
+                                            elem,
+                                            "expected string literal",
+                                        ))
+                                    }
+                                }
+                            }
+                            Ok(())
+                        } else {
+                            Err(meta.error("unsupported clean_env argument"))
+                        }
+                    })?;
+                }
+                args.clean_env = Some(allow);
+                Ok(())
+            } else if meta.path.is_ident("expected_output") {
+                let lit = meta.value()?.parse::<LitStr>()?;
+                args.expected_output = Some(lit.value());
+                Ok(())
+            } else if meta.path.is_ident("stdin") {
+                let lit = meta.value()?.parse::<LitStr>()?;
+                args.stdin = Some(lit.value());
+                Ok(())
+            } else if meta.path.is_ident("stdin_file") {
+                let lit = meta.value()?.parse::<LitStr>()?;
+                args.stdin_file = Some(lit.value());
+                Ok(())
+            } else if meta.path.is_ident("before") {
+                let path = meta.value()?.parse::<Path>()?;
+                args.before = Some(path.into_token_stream());
+                Ok(())
+            } else if meta.path.is_ident("after") {
+                let path = meta.value()?.parse::<Path>()?;
+                args.after = Some(path.into_token_stream());
+                Ok(())
+            } else if meta.path.is_ident("fixture") {
+                let path = meta.value()?.parse::<Path>()?;
+                args.fixture = Some(path.into_token_stream());
+                Ok(())
+            } else if meta.path.is_ident("raw_fork") {
+                args.raw_fork = true;
+                Ok(())
+            } else if meta.path.is_ident("fork_if") {
+                let content;
+                let _paren = parenthesized!(content in meta.input);
+                args.fork_if = Some(content.parse::<Tokens>()?);
+                Ok(())
+            } else if meta.path.is_ident("opt_in_env") {
+                let lit = meta.value()?.parse::<LitStr>()?;
+                args.opt_in_env = Some(lit.value());
+                Ok(())
+            } else if meta.path.is_ident("debug") {
+                args.debug = true;
+                Ok(())
+            } else if meta.path.is_ident("nice") {
+                let value = meta.value()?;
+                let negative = value.parse::<Option<Token![-]>>()?.is_some();
+                let lit = value.parse::<LitInt>()?;
+                let nice = lit.base10_parse::<i32>()?;
+                args.nice = Some(if negative { -nice } else { nice });
+                Ok(())
+            } else if meta.path.is_ident("cpu_affinity") {
+                let array = meta.value()?.parse::<ExprArray>()?;
+                let cpus = array
+                    .elems
+                    .iter()
+                    .map(|elem| match elem {
+                        Expr::Lit(ExprLit {
+                            lit: Lit::Int(lit), ..
+                        }) => lit.base10_parse::<usize>(),
+                        elem => Err(Error::new_spanned(elem, "expected integer literal")),
+                    })
+                    .collect::<Result<_>>()?;
+                args.cpu_affinity = Some(cpus);
+                Ok(())
+            } else if meta.path.is_ident("serial") {
+                let lit = meta.value()?.parse::<LitStr>()?;
+                args.serial = Some(lit.value());
+                Ok(())
+            } else if meta.path.is_ident("reserve_ports") {
+                let lit = meta.value()?.parse::<LitInt>()?;
+                args.reserve_ports = Some(lit.base10_parse()?);
+                Ok(())
+            } else if meta.path.is_ident("warmup_iters") {
+                let lit = meta.value()?.parse::<LitInt>()?;
+                args.warmup_iters = Some(lit.base10_parse()?);
+                Ok(())
+            } else if meta.path.is_ident("min_iters") {
+                let lit = meta.value()?.parse::<LitInt>()?;
+                args.min_iters = Some(lit.base10_parse()?);
+                Ok(())
+            } else if meta.path.is_ident("measure") {
+                let lit = meta.value()?.parse::<LitStr>()?;
+                let variant = match lit.value().as_str() {
+                    "wall" => "Wall",
+                    "cpu" => "Cpu",
+                    _ => {
+                        return Err(Error::new_spanned(
+                            lit,
+                            "unsupported measure (expected `wall` or `cpu`)",
+                        ))
+                    }
+                };
+                args.measure = Some(Ident::new(variant, lit.span()));
+                Ok(())
+            } else if meta.path.is_ident("matrix") {
+                let mut matrix = Vec::new();
+                let () = meta.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("env") {
+                        let content;
+                        let _paren = parenthesized!(content in meta.input);
+                        let mut envs = Vec::new();
+                        while !content.is_empty() {
+                            let key = content.parse::<LitStr>()?;
+                            let _eq = content.parse::<Token![=]>()?;
+                            let value = content.parse::<LitStr>()?;
+                            let () = envs.push((key.value(), value.value()));
+                            if !content.is_empty() {
+                                let _comma = content.parse::<Token![,]>()?;
+                            }
+                        }
+                        let () = matrix.push(envs);
+                        Ok(())
+                    } else {
+                        Err(meta.error("unsupported matrix configuration (expected `env`)"))
+                    }
+                })?;
+                if matrix.is_empty() {
+                    return Err(meta.error("matrix requires at least one configuration"))
+                }
+                args.matrix = Some(matrix);
+                Ok(())
+            } else if meta.path.is_ident("instances") {
+                let lit = meta.value()?.parse::<LitInt>()?;
+                let instances = lit.base10_parse()?;
+                if instances == 0 {
+                    return Err(Error::new_spanned(lit, "at least one instance is required"))
+                }
+                args.instances = Some(instances);
+                Ok(())
+            } else if meta.path.is_ident("tokio") || meta.path.is_ident("runtime") {
+                if args.runtime.is_some() {
+                    return Err(meta.error("only a single runtime may be specified"))
+                }
+                let runtime = if meta.path.is_ident("tokio") {
+                    Runtime::Tokio(TokioArgs::parse(meta)?)
+                } else {
+                    Runtime::parse_path(meta.value()?.parse::<Path>()?)
+                };
+                args.runtime = Some(runtime);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported attribute argument"))
+            }
+        });
+        let () = parser.parse2(attr)?;
+        Ok(args)
+    }
+
+    /// Check whether any of the arguments configuring the benchmark
+    /// measurement loop are set.
+    fn configures_bench(&self) -> bool {
+        self.warmup_iters.is_some() || self.min_iters.is_some() || self.measure.is_some()
+    }
+
+    /// Generate an expression creating a `Builder` configured according
+    /// to the arguments.
+    fn builder(&self) -> Tokens {
+        let heartbeat = self.heartbeat_ms.map(|ms| {
+            quote! { .heartbeat(::std::time::Duration::from_millis(#ms)) }
+        });
+        let output_limit = self
+            .output_limit
+            .map(|limit| quote! { .output_limit(#limit) });
+        let prefix_output = self.prefix_output.then(|| quote! { .prefix_output(true) });
+        let persist_output = self
+            .persist_output
+            .then(|| quote! { .persist_output(true) });
+        let artifacts = self.artifacts.as_ref().map(|artifacts| {
+            quote! { .artifacts(::test_fork::test_fork_core::Artifacts::#artifacts) }
+        });
+        let color = self.color.as_ref().map(|color| {
+            quote! { .color(::test_fork::test_fork_core::Color::#color) }
+        });
+        let sanitizer_check = self
+            .sanitizer_check
+            .then(|| quote! { .sanitizer_check(true) });
+        let leak_check = self.leak_check.then(|| quote! { .leak_check(true) });
+        let clean_env = self.clean_env.as_ref().map(|allow| {
+            quote! { .env_clear_except(&[#(#allow),*] as &[&str]) }
+        });
+        let expected_output = self.expected_output.as_ref().map(|path| {
+            quote! {
+                .expected_output(::core::concat!(::core::env!("CARGO_MANIFEST_DIR"), "/", #path))
+            }
+        });
+        let stdin = self.stdin.as_ref().map(|data| quote! { .stdin(#data) });
+        let stdin_file = self.stdin_file.as_ref().map(|path| {
+            quote! {
+                .stdin_file(::core::concat!(::core::env!("CARGO_MANIFEST_DIR"), "/", #path))
+            }
+        });
+        let raw_fork = self.raw_fork.then(|| quote! { .raw_fork(true) });
+        let fork_if = self.fork_if.as_ref().map(|predicate| {
+            quote! { .in_process(!::core::cfg!(#predicate)) }
+        });
+        let opt_in_env = self
+            .opt_in_env
+            .as_ref()
+            .map(|name| quote! { .opt_in_env(#name) });
+        let debug = self.debug.then(|| quote! { .debug(true) });
+        let nice = self.nice.map(|nice| quote! { .nice(#nice) });
+        let cpu_affinity = self
+            .cpu_affinity
+            .as_ref()
+            .map(|cpus| quote! { .cpu_affinity([#(#cpus),*]) });
+        let serial = self.serial.as_ref().map(|name| quote! { .serial(#name) });
+        let reserve_ports = self
+            .reserve_ports
+            .map(|count| quote! { .reserve_ports(#count) });
+        let warmup_iters = self
+            .warmup_iters
+            .map(|iters| quote! { .warmup_iters(#iters) });
+        let min_iters = self.min_iters.map(|iters| quote! { .min_iters(#iters) });
+        let measure = self.measure.as_ref().map(|measure| {
+            quote! { .measure(::test_fork::test_fork_core::Measure::#measure) }
+        });
+        let before = self.before.as_ref().map(|hook| quote! { .before(#hook) });
+        let after = self.after.as_ref().map(|hook| quote! { .after(#hook) });
+
+        quote! {
+            ::test_fork::test_fork_core::Builder::new()
+                #heartbeat
+                #output_limit
+                #prefix_output
+                #persist_output
+                #artifacts
+                #color
+                #sanitizer_check
+                #leak_check
+                #clean_env
+                #expected_output
+                #stdin
+                #stdin_file
+                #raw_fork
+                #fork_if
+                #opt_in_env
+                #debug
+                #nice
+                #cpu_affinity
+                #serial
+                #reserve_ports
+                #warmup_iters
+                #min_iters
+                #measure
+                #before
+                #after
+        }
+    }
+}
+
+
+/// The paths of well-known third-party attributes turning a function
+/// into a test.
+const THIRD_PARTY_TESTS: [&[&str]; 9] = [
+    &["actix_rt", "test"],
+    &["actix_web", "test"],
+    &["async_std", "test"],
+    &["rstest"],
+    &["rstest", "rstest"],
+    &["test_case"],
+    &["test_case", "test_case"],
+    &["test_log", "test"],
+    &["tokio", "test"],
+];
+
+
+/// Check whether given attribute is a well-known third-party test
+/// attribute, such as `#[tokio::test]` or `#[rstest]`, with or without
+/// arguments.
+fn is_third_party_test(attr: &Attribute) -> bool {
+    let path = attr.path();
+    THIRD_PARTY_TESTS.iter().any(|candidate| {
+        path.segments.len() == candidate.len()
+            && path
+                .segments
+                .iter()
+                .zip(candidate.iter())
+                .all(|(segment, ident)| segment.arguments.is_none() && segment.ident == ident)
+    })
+}
+
 /// Check whether given attribute is a test or bench attribute of the
 /// form:
 /// - `#[<kind>]`
 /// - `#[core::prelude::*::<kind>]` or `#[::core::prelude::*::<kind>]`
 /// - `#[std::prelude::*::<kind>]` or `#[::std::prelude::*::<kind>]`
+///
+/// Well-known third-party test attributes (see
+/// [`is_third_party_test`]) are recognized as test attributes as well.
 fn is_attribute_kind(kind: Kind, attr: &Attribute) -> bool {
+    if matches!(kind, Kind::Test) && is_third_party_test(attr) {
+        return true
+    }
+
     let path = match &attr.meta {
         syn::Meta::Path(path) => path,
         _ => return false,
@@ -71,8 +632,45 @@ fn is_attribute_kind(kind: Kind, attr: &Attribute) -> bool {
 }
 
 
+/// Re-emit `input_fn` with the attribute `path`, carrying `attr` as
+/// arguments, as its inner-most attribute, if it carries any
+/// third-party test attribute.
+///
+/// Such attributes may rewrite the function (e.g., to run an async body
+/// on a runtime or to generate one test per case), so we can only wrap
+/// it after they got expanded. Once they are, they emit a regular
+/// `#[test]` attribute for us to recognize.
+fn defer_to_third_party(path: Tokens, attr: &Tokens, input_fn: &ItemFn) -> Option<Tokens> {
+    if !input_fn.attrs.iter().any(is_third_party_test) {
+        return None
+    }
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = input_fn;
+    let attr = if attr.is_empty() {
+        quote! { #[#path] }
+    } else {
+        quote! { #[#path(#attr)] }
+    };
+
+    let deferred = quote! {
+        #(#attrs)*
+        #attr
+        #vis #sig #block
+    };
+    Some(deferred)
+}
+
 /// Testable implementation of the `#[test]` attribute's core logic.
 pub fn try_test(attr: Tokens, input_fn: ItemFn) -> Result<Tokens> {
+    if let Some(deferred) = defer_to_third_party(quote! { ::test_fork::test }, &attr, &input_fn) {
+        return Ok(deferred)
+    }
+
     let has_test = input_fn
         .attrs
         .iter()
@@ -87,12 +685,8 @@ pub fn try_test(attr: Tokens, input_fn: ItemFn) -> Result<Tokens> {
 }
 
 fn try_test_inner(attr: Tokens, input_fn: ItemFn, inner_test: Tokens) -> Result<Tokens> {
-    if !attr.is_empty() {
-        return Err(Error::new_spanned(
-            attr,
-            "the attribute does not currently accept arguments",
-        ))
-    }
+    let args = Args::parse(attr)?;
+    let builder = args.builder();
 
     let ItemFn {
         attrs,
@@ -101,14 +695,141 @@ fn try_test_inner(attr: Tokens, input_fn: ItemFn, inner_test: Tokens) -> Result<
         block,
     } = input_fn;
 
+    if args.configures_bench() {
+        return Err(Error::new_spanned(
+            sig.fn_token,
+            "benchmark attribute arguments require a benchmark function",
+        ))
+    }
+
+    // If so requested, run an async test on a runtime that we create
+    // only in the child.
+    let block = match (&args.runtime, sig.asyncness) {
+        (Some(runtime), Some(_)) => {
+            sig.asyncness = None;
+            let block_on = runtime.block_on(&block);
+            quote! { { #block_on } }
+        }
+        (Some(_), None) => {
+            return Err(Error::new_spanned(
+                sig.fn_token,
+                "async runtime attribute arguments require an async test function",
+            ))
+        }
+        (None, _) => block.into_token_stream(),
+    };
+
+    // A crashing child must not satisfy a test expected to panic.
+    let should_panic = attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("should_panic"));
+    let builder = if should_panic {
+        quote! { #builder.should_panic(true) }
+    } else {
+        builder
+    };
+
     let test_name = sig.ident.clone();
     let mut body_fn_sig = sig.clone();
     body_fn_sig.ident = Ident::new("body_fn", Span::call_site());
     // Our tests currently basically have to return (), because we don't
     // have a good way of conveying the result back from the child
-    // process.
+    // process. A test expected to panic may still report its failure
+    // through the returned value, though, which we turn into a panic in
+    // the child, similar to what `libtest` does.
+    let report = |call| {
+        quote! {{
+            let code = ::std::process::Termination::report(#call);
+            ::core::assert!(
+                code == ::std::process::ExitCode::SUCCESS,
+                "the test returned a termination value with a non-zero status code",
+            );
+        }}
+    };
+    let (body_fn, body_fn_fixture) = if should_panic && matches!(sig.output, ReturnType::Type(..)) {
+        let call = report(quote! { body_fn() });
+        let call_fixture = report(quote! { body_fn(fixture) });
+        (quote! { || #call }, quote! { |fixture| #call_fixture })
+    } else {
+        (
+            quote! { body_fn as fn() -> _ },
+            quote! { body_fn as fn(_) -> _ },
+        )
+    };
     sig.output = ReturnType::Default;
 
+    if args.matrix.is_some() && args.instances.is_some() {
+        return Err(Error::new_spanned(
+            sig.fn_token,
+            "`matrix` attribute argument cannot be combined with `instances`",
+        ))
+    }
+
+    let fork = match (&args.fixture, sig.inputs.len()) {
+        (None, 0) if args.instances.is_some() => {
+            let instances = args.instances;
+            quote! {
+                #builder.fork_instances(
+                    ::test_fork::test_fork_core::fork_id!(),
+                    ::test_fork::test_fork_core::fork_test_name!(#test_name),
+                    #instances,
+                    #body_fn,
+                )
+            }
+        }
+        (None, 0) if args.matrix.is_some() => {
+            let configurations = args.matrix.iter().flatten().map(|envs| {
+                let envs = envs.iter().map(|(key, value)| quote! { (#key, #value) });
+                quote! { &[#(#envs),*] as &[(&str, &str)] }
+            });
+            quote! {
+                #builder.fork_matrix(
+                    ::test_fork::test_fork_core::fork_id!(),
+                    ::test_fork::test_fork_core::fork_test_name!(#test_name),
+                    &[#(#configurations),*],
+                    #body_fn,
+                )
+            }
+        }
+        (None, 0) => quote! {
+            #builder.fork(
+                ::test_fork::test_fork_core::fork_id!(),
+                ::test_fork::test_fork_core::fork_test_name!(#test_name),
+                #body_fn,
+            )
+        },
+        (Some(_), _) if args.matrix.is_some() || args.instances.is_some() => {
+            return Err(Error::new_spanned(
+                sig.fn_token,
+                "`matrix` and `instances` attribute arguments cannot be combined with `fixture`",
+            ))
+        }
+        (Some(fixture), 1) => {
+            // The fixture is provided by us, not the test harness.
+            sig.inputs.clear();
+            quote! {
+                #builder.fork_with_fixture(
+                    ::test_fork::test_fork_core::fork_id!(),
+                    ::test_fork::test_fork_core::fork_test_name!(#test_name),
+                    #fixture,
+                    #body_fn_fixture,
+                )
+            }
+        }
+        (None, _) => {
+            return Err(Error::new_spanned(
+                sig.inputs,
+                "test function with arguments requires `fixture` attribute argument",
+            ))
+        }
+        (Some(_), _) => {
+            return Err(Error::new_spanned(
+                sig.to_token_stream(),
+                "test function with `fixture` attribute argument has unexpected signature (expected single fixture argument)",
+            ))
+        }
+    };
+
     let augmented_test = quote! {
         #inner_test
         #(#attrs)*
@@ -116,11 +837,7 @@ fn try_test_inner(attr: Tokens, input_fn: ItemFn, inner_test: Tokens) -> Result<
             #body_fn_sig
             #block
 
-            ::test_fork::test_fork_core::fork(
-                ::test_fork::test_fork_core::fork_id!(),
-                ::test_fork::test_fork_core::fork_test_name!(#test_name),
-                body_fn as fn() -> _,
-            ).expect("forking test failed")
+            #fork.expect("forking test failed")
         }
     };
 
@@ -143,6 +860,38 @@ fn parse_bench_sig(sig: &Signature) -> Option<(Pat, Type)> {
     }
 }
 
+/// Expand a benchmark into a function running it with our own
+/// `Bencher` via `Builder::fork_bench`.
+fn own_bencher_bench(
+    builder: &Tokens,
+    inner_attr: Tokens,
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    sig: Signature,
+    mut body_fn_sig: Signature,
+    block: Box<Block>,
+) -> Tokens {
+    let test_name = &sig.ident;
+    if let Some(FnArg::Typed(pat_type)) = body_fn_sig.inputs.first_mut() {
+        *pat_type.ty = parse_quote! { &mut ::test_fork::test_fork_core::Bencher };
+    }
+
+    quote! {
+        #inner_attr
+        #(#attrs)*
+        #vis #sig {
+            #body_fn_sig
+            #block
+
+            let _summary = #builder.fork_bench(
+                ::test_fork::test_fork_core::fork_id!(),
+                ::test_fork::test_fork_core::fork_test_name!(#test_name),
+                body_fn as fn(&mut ::test_fork::test_fork_core::Bencher) -> _,
+            ).expect("forking test failed");
+        }
+    }
+}
+
 /// Testable implementation of the `#[bench]` attribute's core logic.
 pub fn try_bench(attr: Tokens, input_fn: ItemFn) -> Result<Tokens> {
     let has_bench = input_fn
@@ -159,12 +908,8 @@ pub fn try_bench(attr: Tokens, input_fn: ItemFn) -> Result<Tokens> {
 }
 
 fn try_bench_inner(attr: Tokens, input_fn: ItemFn, inner_bench: Tokens) -> Result<Tokens> {
-    if !attr.is_empty() {
-        return Err(Error::new_spanned(
-            attr,
-            "the attribute does not currently accept arguments",
-        ))
-    }
+    let args = Args::parse(attr)?;
+    let builder = args.builder();
 
     let ItemFn {
         attrs,
@@ -173,6 +918,13 @@ fn try_bench_inner(attr: Tokens, input_fn: ItemFn, inner_bench: Tokens) -> Resul
         block,
     } = input_fn;
 
+    if args.matrix.is_some() || args.instances.is_some() {
+        return Err(Error::new_spanned(
+            sig.fn_token,
+            "`matrix` and `instances` attribute arguments require a test function",
+        ))
+    }
+
     let (bencher_name, bencher_ty) = parse_bench_sig(&sig).ok_or_else(|| {
         Error::new_spanned(
             sig.to_token_stream(),
@@ -185,6 +937,17 @@ fn try_bench_inner(attr: Tokens, input_fn: ItemFn, inner_bench: Tokens) -> Resul
     body_fn_sig.ident = Ident::new("body_fn", Span::call_site());
     sig.output = ReturnType::Default;
 
+    if args.configures_bench() {
+        // The measurement loop is configured by us, so the benchmark
+        // is run with our own `Bencher` in lieu of the harness'.
+        if let Some(FnArg::Typed(pat_type)) = sig.inputs.first_mut() {
+            *pat_type.pat = parse_quote! { _ };
+        }
+        let augmented_bench =
+            own_bencher_bench(&builder, inner_bench, attrs, vis, sig, body_fn_sig, block);
+        return Ok(augmented_bench)
+    }
+
     let augmented_bench = quote! {
         #inner_bench
         #(#attrs)*
@@ -205,8 +968,10 @@ fn try_bench_inner(attr: Tokens, input_fn: ItemFn, inner_bench: Tokens) -> Resul
                 transmute::<&mut #bencher_ty, &mut BencherBuf>(#bencher_name)
             };
 
-            fn wrapper_fn(buf_ref: &mut [u8]) {
-                let buf_ref = <&mut BencherBuf>::try_from(buf_ref).unwrap();
+            let mut buf = buf_ref.to_vec();
+
+            fn wrapper_fn(buf: &mut ::std::vec::Vec<u8>) {
+                let buf_ref = <&mut BencherBuf>::try_from(buf.as_mut_slice()).unwrap();
                 // SAFETY: See above.
                 let bench_ref = unsafe {
                     transmute::<&mut BencherBuf, &mut #bencher_ty>(buf_ref)
@@ -214,20 +979,75 @@ fn try_bench_inner(attr: Tokens, input_fn: ItemFn, inner_bench: Tokens) -> Resul
                 let () = body_fn(bench_ref);
             }
 
-            ::test_fork::test_fork_core::fork_in_out(
+            let () = #builder.fork_in_out(
                 ::test_fork::test_fork_core::fork_id!(),
                 ::test_fork::test_fork_core::fork_test_name!(#test_name),
-                wrapper_fn as fn(&mut [u8]) -> _,
-                buf_ref,
-            ).expect("forking test failed")
+                wrapper_fn as fn(&mut ::std::vec::Vec<u8>) -> _,
+                &mut buf,
+            ).expect("forking test failed");
+            let () = buf_ref.copy_from_slice(&buf);
         }
     };
 
     Ok(augmented_bench)
 }
 
+/// Testable implementation of the `#[bench]` attribute's core logic for
+/// stable toolchains, on which `libtest` benchmarks are unavailable.
+///
+/// The benchmark is turned into a regular test that runs it with our
+/// own `Bencher`.
+pub fn try_bench_stable(attr: Tokens, input_fn: ItemFn) -> Result<Tokens> {
+    let has_test = input_fn
+        .attrs
+        .iter()
+        .any(|attr| is_attribute_kind(Kind::Test, attr));
+    let inner_test = if has_test {
+        quote! {}
+    } else {
+        quote! { #[::core::prelude::v1::test] }
+    };
+
+    let args = Args::parse(attr)?;
+    let builder = args.builder();
+
+    let ItemFn {
+        attrs,
+        vis,
+        mut sig,
+        block,
+    } = input_fn;
+
+    if args.matrix.is_some() || args.instances.is_some() {
+        return Err(Error::new_spanned(
+            sig.fn_token,
+            "`matrix` and `instances` attribute arguments require a test function",
+        ))
+    }
+
+    let _bencher = parse_bench_sig(&sig).ok_or_else(|| {
+        Error::new_spanned(
+            sig.to_token_stream(),
+            "benchmark function has unexpected signature (expected single `&mut Bencher` argument)",
+        )
+    })?;
+
+    let mut body_fn_sig = sig.clone();
+    body_fn_sig.ident = Ident::new("body_fn", Span::call_site());
+    sig.inputs.clear();
+    sig.output = ReturnType::Default;
+
+    let augmented_bench =
+        own_bencher_bench(&builder, inner_test, attrs, vis, sig, body_fn_sig, block);
+    Ok(augmented_bench)
+}
+
 /// Testable implementation of the `#[fork]` attribute's core logic.
 pub fn try_fork(attr: Tokens, input_fn: ItemFn, supports_bench: bool) -> Result<Tokens> {
+    if let Some(deferred) = defer_to_third_party(quote! { ::test_fork::fork }, &attr, &input_fn) {
+        return Ok(deferred)
+    }
+
     let has_test = input_fn
         .attrs
         .iter()
@@ -256,3 +1076,173 @@ pub fn try_fork(attr: Tokens, input_fn: ItemFn, supports_bench: bool) -> Result<
         ))
     }
 }
+
+/// Testable implementation of the `#[main]` attribute's core logic.
+pub fn try_main(attr: Tokens, input_fn: ItemFn) -> Result<Tokens> {
+    let mut bodies = Vec::new();
+    let parser = meta::parser(|meta| {
+        if meta.path.is_ident("bodies") {
+            meta.parse_nested_meta(|meta| {
+                let name = meta
+                    .path
+                    .segments
+                    .last()
+                    .map(|segment| segment.ident.to_string())
+                    .unwrap_or_default();
+                bodies.push((name, meta.path));
+                Ok(())
+            })
+        } else {
+            Err(meta.error("unsupported attribute argument"))
+        }
+    });
+    let () = parser.parse2(attr)?;
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = input_fn;
+
+    if let Some(asyncness) = sig.asyncness {
+        return Err(Error::new_spanned(
+            asyncness,
+            "test_fork::main does not support async functions",
+        ))
+    }
+    if !sig.inputs.is_empty() {
+        return Err(Error::new_spanned(
+            sig.inputs,
+            "test_fork::main requires a function without arguments",
+        ))
+    }
+
+    let bodies = bodies.iter().map(|(name, path)| {
+        quote! {
+            (
+                #name,
+                (|| ::std::process::Termination::report(#path()))
+                    as fn() -> ::std::process::ExitCode,
+            )
+        }
+    });
+
+    let augmented_main = quote! {
+        #(#attrs)*
+        #vis #sig {
+            let () = ::test_fork::test_fork_core::enter_main(&[#(#bodies),*]);
+            #block
+        }
+    };
+    Ok(augmented_main)
+}
+
+
+/// Check whether given attribute is `#[test_fork::<name>]` for any of
+/// the given names.
+fn is_own_attribute(attr: &Attribute, names: &[&str]) -> bool {
+    let mut segments = attr.path().segments.iter();
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some(krate), Some(name), None) => {
+            krate.arguments.is_none()
+                && name.arguments.is_none()
+                && krate.ident == "test_fork"
+                && names.iter().any(|candidate| name.ident == candidate)
+        }
+        _ => false,
+    }
+}
+
+/// Retrieve the key identifying an attribute argument for the purpose
+/// of overriding it.
+fn arg_key(arg: &Meta) -> String {
+    let key = arg.path().to_token_stream().to_string();
+    // Only a single runtime may be specified, irrespective of how.
+    if key == "tokio" {
+        "runtime".to_string()
+    } else {
+        key
+    }
+}
+
+/// Merge the `defaults` into the arguments of `attr`, with the latter
+/// taking precedence. A default runtime is only applicable to `async`
+/// functions and only merged if `runtime` is `true`.
+fn merge_defaults(
+    defaults: &Punctuated<Meta, Token![,]>,
+    attr: &mut Attribute,
+    runtime: bool,
+) -> Result<()> {
+    let args = match &attr.meta {
+        Meta::Path(_) => Punctuated::new(),
+        Meta::List(list) => {
+            list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?
+        }
+        Meta::NameValue(_) => return Err(Error::new_spanned(&attr, "unexpected attribute form")),
+    };
+    let keys = args.iter().map(arg_key).collect::<HashSet<_>>();
+    let merged = defaults
+        .iter()
+        .filter(|arg| {
+            let key = arg_key(arg);
+            !keys.contains(&key) && (runtime || key != "runtime")
+        })
+        .chain(args.iter());
+
+    let path = attr.path().clone();
+    *attr = parse_quote! { #[#path(#(#merged),*)] };
+    Ok(())
+}
+
+/// Apply the `defaults` to all our attributes on the given items,
+/// recursing into inline modules.
+fn apply_defaults(defaults: &Punctuated<Meta, Token![,]>, items: &mut [Item]) -> Result<()> {
+    for item in items {
+        match item {
+            Item::Fn(item_fn) => {
+                let runtime = item_fn.sig.asyncness.is_some();
+                for attr in &mut item_fn.attrs {
+                    if is_own_attribute(attr, &["test", "fork", "bench"]) {
+                        let () = merge_defaults(defaults, attr, runtime)?;
+                    }
+                }
+            }
+            Item::Mod(item_mod) => {
+                // A nested configuration is expanded after ours, so we
+                // pass on our defaults to it instead.
+                if let Some(attr) = item_mod
+                    .attrs
+                    .iter_mut()
+                    .find(|attr| is_own_attribute(attr, &["config"]))
+                {
+                    let () = merge_defaults(defaults, attr, true)?;
+                } else if let Some((_brace, items)) = &mut item_mod.content {
+                    let () = apply_defaults(defaults, items)?;
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Testable implementation of the `#[config]` attribute's core logic.
+pub fn try_config(attr: Tokens, mut input_mod: ItemMod) -> Result<Tokens> {
+    // Check the arguments up front, to report errors against the
+    // attribute providing them.
+    let _args = Args::parse(attr.clone())?;
+    let defaults = Punctuated::<Meta, Token![,]>::parse_terminated.parse2(attr)?;
+
+    let items = match &mut input_mod.content {
+        Some((_brace, items)) => items,
+        None => {
+            return Err(Error::new_spanned(
+                &input_mod.ident,
+                "test_fork::config requires an inline module",
+            ))
+        }
+    };
+    let () = apply_defaults(&defaults, items)?;
+    Ok(input_mod.into_token_stream())
+}
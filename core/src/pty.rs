@@ -0,0 +1,185 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Connection of the child's standard output to a pseudo-terminal.
+//!
+//! Just before spawning the child, the parent opens a pseudo-terminal
+//! and hands the child its subsidiary end as standard output. The
+//! controlling end is retained and, once the child got spawned, read
+//! from in lieu of the usual output pipe.
+
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::mem;
+use std::os::fd::AsRawFd as _;
+use std::os::fd::FromRawFd as _;
+use std::os::fd::OwnedFd;
+use std::os::unix::ffi::OsStrExt as _;
+use std::os::unix::fs::OpenOptionsExt as _;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use crate::backend::Launched;
+use crate::sys::check;
+
+
+/// The number of columns of the terminal.
+const COLUMNS: u16 = 80;
+/// The number of rows of the terminal.
+const ROWS: u16 = 24;
+
+
+thread_local! {
+    /// The controlling end of the pseudo-terminal prepared for the next
+    /// child spawned by the current thread.
+    static PREPARED: RefCell<Option<File>> = const { RefCell::new(None) };
+}
+
+
+/// Open the controlling end of a new pseudo-terminal.
+///
+/// The file descriptor is marked close-on-exec, so that it doesn't leak
+/// into processes spawned concurrently.
+#[cfg(not(target_vendor = "apple"))]
+fn open_controller() -> io::Result<File> {
+    // SAFETY: `posix_openpt` is always safe to call.
+    let fd = check(unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC) })?;
+    // SAFETY: `posix_openpt` returned a freshly opened file descriptor
+    //         owned by nobody else.
+    let controller = unsafe { File::from_raw_fd(fd) };
+    Ok(controller)
+}
+
+/// Open the controlling end of a new pseudo-terminal.
+///
+/// The file descriptor is marked close-on-exec. The system does not
+/// support doing so atomically, though, so processes spawned
+/// concurrently may still inherit it.
+#[cfg(target_vendor = "apple")]
+fn open_controller() -> io::Result<File> {
+    // SAFETY: `posix_openpt` is always safe to call.
+    let fd = check(unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY) })?;
+    // SAFETY: `posix_openpt` returned a freshly opened file descriptor
+    //         owned by nobody else.
+    let controller = unsafe { File::from_raw_fd(fd) };
+    // SAFETY: `controller` is a valid file descriptor.
+    let _rc =
+        check(unsafe { libc::fcntl(controller.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) })?;
+    Ok(controller)
+}
+
+/// Retrieve the path to the subsidiary end of the pseudo-terminal with
+/// the given controlling end.
+fn subsidiary_path(controller: &File) -> io::Result<PathBuf> {
+    // `ptsname` returns a pointer to static storage, so calls have to
+    // be serialized.
+    static PTSNAME: Mutex<()> = Mutex::new(());
+
+    let _guard = PTSNAME.lock().unwrap_or_else(PoisonError::into_inner);
+    // SAFETY: `controller` is a valid pseudo-terminal file descriptor.
+    let name = unsafe { libc::ptsname(controller.as_raw_fd()) };
+    if name.is_null() {
+        return Err(io::Error::last_os_error())
+    }
+    // SAFETY: `ptsname` returned a valid NUL terminated string, which
+    //         stays valid until the next call.
+    let name = unsafe { CStr::from_ptr(name) };
+    Ok(PathBuf::from(OsStr::from_bytes(name.to_bytes())))
+}
+
+/// Open a pseudo-terminal, returning its controlling and its
+/// subsidiary end.
+///
+/// Both ends are marked close-on-exec, so that only the child we hand
+/// the subsidiary end to ends up holding it.
+fn open() -> io::Result<(File, OwnedFd)> {
+    let controller = open_controller()?;
+    // SAFETY: `controller` is a valid pseudo-terminal file descriptor.
+    let _rc = check(unsafe { libc::grantpt(controller.as_raw_fd()) })?;
+    // SAFETY: `controller` is a valid pseudo-terminal file descriptor.
+    let _rc = check(unsafe { libc::unlockpt(controller.as_raw_fd()) })?;
+    // The standard library opens files with `O_CLOEXEC`.
+    let subsidiary = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(subsidiary_path(&controller)?)?;
+    let subsidiary = OwnedFd::from(subsidiary);
+
+    let size = libc::winsize {
+        ws_row: ROWS,
+        ws_col: COLUMNS,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: `subsidiary` is a valid terminal file descriptor and
+    //         `size` is a valid `winsize` object.
+    let _rc = check(unsafe { libc::ioctl(subsidiary.as_raw_fd(), libc::TIOCSWINSZ, &size) })?;
+
+    // SAFETY: `termios` is plain data, for which all zeroes is a valid
+    //         value.
+    let mut termios = unsafe { mem::zeroed::<libc::termios>() };
+    // SAFETY: `subsidiary` is a valid terminal file descriptor and
+    //         `termios` is a valid `termios` object.
+    let _rc = check(unsafe { libc::tcgetattr(subsidiary.as_raw_fd(), &mut termios) })?;
+    // Don't translate line feeds into carriage return & line feed
+    // pairs, to keep the output comparable to that of a pipe.
+    termios.c_oflag &= !libc::ONLCR;
+    // SAFETY: `subsidiary` is a valid terminal file descriptor and
+    //         `termios` is a valid `termios` object.
+    let _rc = check(unsafe { libc::tcsetattr(subsidiary.as_raw_fd(), libc::TCSANOW, &termios) })?;
+
+    Ok((controller, subsidiary))
+}
+
+
+/// Connect the standard output of the child process spawned by
+/// `command` to a newly opened pseudo-terminal.
+///
+/// The controlling end of the terminal has to be attached to the
+/// launched child via [`attach`] afterwards.
+pub(crate) fn configure(command: &mut Command) -> io::Result<()> {
+    let (controller, subsidiary) = open()?;
+    command.stdout(Stdio::from(subsidiary));
+    let () = PREPARED.with(|prepared| *prepared.borrow_mut() = Some(controller));
+    Ok(())
+}
+
+/// Read the output of the `launched` child from the pseudo-terminal
+/// prepared by the calling thread, if any.
+///
+/// The subsidiary end of the terminal held by `command` is closed, so
+/// that reading reaches the end once the child exited.
+pub(crate) fn attach(command: &mut Command, mut launched: Launched) -> Launched {
+    if let Some(controller) = PREPARED.with(RefCell::take) {
+        command.stdout(Stdio::null());
+        launched.stdout = Some(Box::new(controller));
+    }
+    launched
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that both ends of a pseudo-terminal are marked
+    /// close-on-exec.
+    #[test]
+    fn close_on_exec() {
+        let (controller, subsidiary) = open().unwrap();
+        for fd in [controller.as_raw_fd(), subsidiary.as_raw_fd()] {
+            // SAFETY: `fd` is a valid file descriptor.
+            let flags = check(unsafe { libc::fcntl(fd, libc::F_GETFD) }).unwrap();
+            assert_ne!(flags & libc::FD_CLOEXEC, 0);
+        }
+    }
+}
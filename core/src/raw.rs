@@ -0,0 +1,304 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Creation of child processes via a plain `fork`, without `exec`.
+//!
+//! Compared to spawning a new instance of the test binary, forking
+//! avoids process startup and re-initialization of the test harness
+//! altogether. The child is a copy of the parent at the point of the
+//! fork, though, containing only the forking thread. Locks held by
+//! other threads at that time stay locked forever in the child, which
+//! can cause it to deadlock.
+//!
+//! The child also inherits all file descriptors of the parent. Those
+//! backing serialization locks are closed right away, lest the child
+//! keeps holding the locks of unrelated tests for as long as it is
+//! alive. Raw forks are serialized among each other, so that no child
+//! inherits the pipes set up for capturing the output of another one.
+
+use std::env;
+use std::fs::File;
+use std::io;
+use std::io::Write as _;
+use std::os::fd::AsRawFd as _;
+use std::os::fd::FromRawFd as _;
+use std::os::fd::OwnedFd;
+use std::os::fd::RawFd;
+use std::os::unix::process::ExitStatusExt as _;
+use std::process;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use crate::backend::ChildProcess;
+use crate::backend::Launched;
+use crate::child::ChildWrapper;
+use crate::fork::EX_SOFTWARE;
+use crate::serial;
+use crate::sys::check;
+
+
+/// The lock serializing raw forks within the process.
+static FORK_LOCK: Mutex<()> = Mutex::new(());
+
+
+/// Create a pipe, returning its read and write ends.
+///
+/// Both ends are marked close-on-exec, so that they don't leak into
+/// processes spawned concurrently.
+#[cfg(not(target_vendor = "apple"))]
+fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    // SAFETY: `fds` is valid for writing two file descriptors.
+    let _rc = check(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) })?;
+    let [read, write] = fds;
+    // SAFETY: `pipe2` succeeded, so the file descriptor is valid and
+    //         owned by nobody else.
+    let read = unsafe { OwnedFd::from_raw_fd(read) };
+    // SAFETY: See above.
+    let write = unsafe { OwnedFd::from_raw_fd(write) };
+    Ok((read, write))
+}
+
+/// Create a pipe, returning its read and write ends.
+///
+/// Both ends are marked close-on-exec. Lacking `pipe2`, that happens
+/// only after the pipe got created, so processes spawned concurrently
+/// may still inherit them.
+#[cfg(target_vendor = "apple")]
+fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    // SAFETY: `fds` is valid for writing two file descriptors.
+    let _rc = check(unsafe { libc::pipe(fds.as_mut_ptr()) })?;
+    let [read, write] = fds;
+    // SAFETY: `pipe` succeeded, so the file descriptor is valid and
+    //         owned by nobody else.
+    let read = unsafe { OwnedFd::from_raw_fd(read) };
+    // SAFETY: See above.
+    let write = unsafe { OwnedFd::from_raw_fd(write) };
+
+    for fd in [&read, &write] {
+        // SAFETY: `fd` is a valid file descriptor.
+        let _rc = check(unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) })?;
+    }
+    Ok((read, write))
+}
+
+/// Set up the current process, just after it got forked, as a child
+/// configured according to `command`.
+fn set_up_child(
+    command: &Command,
+    stdin: File,
+    stdout: OwnedFd,
+    stderr: OwnedFd,
+    locks: &mut Vec<RawFd>,
+) -> io::Result<()> {
+    // The `SerialLock` objects owning these file descriptors are never
+    // dropped in this process, as we never return to their owners.
+    for fd in locks.drain(..) {
+        // SAFETY: `fd` is a valid file descriptor and not used by
+        //         anybody else (see above).
+        let _rc = unsafe { libc::close(fd) };
+    }
+
+    // Run the child in a process group of its own, just as we do for
+    // spawned ones.
+    // SAFETY: `setpgid` is always safe to call.
+    let _rc = check(unsafe { libc::setpgid(0, 0) })?;
+
+    for (fd, target) in [
+        (stdin.as_raw_fd(), libc::STDIN_FILENO),
+        (stdout.as_raw_fd(), libc::STDOUT_FILENO),
+        (stderr.as_raw_fd(), libc::STDERR_FILENO),
+    ] {
+        // SAFETY: Both file descriptors are valid.
+        let _rc = check(unsafe { libc::dup2(fd, target) })?;
+    }
+
+    for (name, value) in command.get_envs() {
+        match value {
+            // SAFETY: The forked process contains only a single thread.
+            Some(value) => unsafe { env::set_var(name, value) },
+            // SAFETY: The forked process contains only a single thread.
+            None => unsafe { env::remove_var(name) },
+        }
+    }
+    Ok(())
+}
+
+/// Fork the current process, configuring the child according to
+/// `command`, except for its program and arguments.
+///
+/// Only the environment variables set (or removed) explicitly on
+/// `command` are honored. The child's standard input is connected to
+/// the null device.
+///
+/// Returns the wrapped child in the parent and `None` in the child.
+pub(crate) fn fork(
+    command: &Command,
+    test_name: &str,
+    fork_id: &str,
+) -> io::Result<Option<ChildWrapper>> {
+    let _guard = FORK_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let stdin = File::open("/dev/null")?;
+    let (stdout_read, stdout_write) = pipe()?;
+    let (stderr_read, stderr_write) = pipe()?;
+    // Make sure that buffered output does not end up being written by
+    // both processes.
+    let _result = io::stdout().flush();
+    // Keep the set of held serialization locks stable while forking,
+    // so that the child knows exactly which ones to close.
+    let mut locks = serial::held();
+
+    // SAFETY: The child only runs the test (and whatever it calls),
+    //         which is inherently subject to the restrictions
+    //         documented for `Builder::raw_fork`.
+    let pid = check(unsafe { libc::fork() })?;
+    if pid == 0 {
+        if let Err(err) = set_up_child(command, stdin, stdout_write, stderr_write, &mut locks) {
+            eprintln!("test-fork: failed to set up forked child: {err}");
+            process::exit(EX_SOFTWARE)
+        }
+        return Ok(None)
+    }
+
+    // Set the child's process group from our side as well, so that it
+    // is in place regardless of which process gets scheduled first.
+    // SAFETY: `setpgid` is always safe to call.
+    let _rc = unsafe { libc::setpgid(pid, pid) };
+    let launched = Launched {
+        process: Box::new(ForkedProcess { pid }),
+        stdin: None,
+        stdout: Some(Box::new(File::from(stdout_read))),
+        stderr: Some(Box::new(File::from(stderr_read))),
+    };
+    let child = ChildWrapper::new(launched, test_name, fork_id);
+    Ok(Some(child))
+}
+
+/// A child process created via a plain `fork`.
+#[derive(Debug)]
+struct ForkedProcess {
+    /// The child's PID.
+    pid: libc::pid_t,
+}
+
+impl ForkedProcess {
+    /// Wait for the child to exit, without blocking unless `block` is
+    /// `true`.
+    fn wait_impl(&self, block: bool) -> io::Result<Option<ExitStatus>> {
+        let flags = if block { 0 } else { libc::WNOHANG };
+        let mut status = 0;
+
+        loop {
+            // SAFETY: `status` is valid for writes.
+            match check(unsafe { libc::waitpid(self.pid, &mut status, flags) }) {
+                Ok(0) => break Ok(None),
+                Ok(_pid) => break Ok(Some(ExitStatus::from_raw(status))),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => break Err(err),
+            }
+        }
+    }
+}
+
+impl ChildProcess for ForkedProcess {
+    fn id(&self) -> u32 {
+        // PIDs of existing processes are always positive.
+        self.pid.unsigned_abs()
+    }
+
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        self.wait_impl(false)
+    }
+
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        loop {
+            if let Some(status) = self.wait_impl(true)? {
+                break Ok(status)
+            }
+        }
+    }
+
+    fn kill(&mut self) -> io::Result<()> {
+        // SAFETY: `kill` is always safe to call.
+        let _rc = check(unsafe { libc::kill(self.pid, libc::SIGKILL) })?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::serial::SerialLock;
+
+
+    /// Check that a forked child runs on a copy of the parent's state.
+    #[test]
+    fn state_isolation() {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let mut command = Command::new("unused");
+        let _command = command.env("TEST_FORK_RAW_TEST", "1");
+        let () = COUNTER.store(42, Ordering::Relaxed);
+
+        match fork(&command, "raw::test::state_isolation", "raw").unwrap() {
+            None => {
+                let code = if COUNTER.swap(0, Ordering::Relaxed) == 42
+                    && env::var_os("TEST_FORK_RAW_TEST").is_some()
+                {
+                    // Output of `println!` may get captured by the test
+                    // harness, so write to stdout directly.
+                    let _result = writeln!(io::stdout(), "pid={}", process::id());
+                    0
+                } else {
+                    1
+                };
+                let _result = io::stdout().flush();
+                process::exit(code)
+            }
+            Some(child) => {
+                let pid = child.id();
+                let output = child.wait_with_output().unwrap();
+                assert!(output.status.success());
+                assert_eq!(
+                    String::from_utf8_lossy(&output.stdout),
+                    format!("pid={pid}\n")
+                );
+                assert_eq!(COUNTER.load(Ordering::Relaxed), 42);
+                assert_eq!(env::var_os("TEST_FORK_RAW_TEST"), None);
+            }
+        }
+    }
+
+    /// Check that a forked child does not keep holding serialization
+    /// locks of its parent.
+    #[test]
+    fn lock_release() {
+        let name = "raw-test-lock-release";
+        let lock = SerialLock::acquire(name).unwrap();
+        let command = Command::new("unused");
+
+        match fork(&command, "raw::test::lock_release", "raw").unwrap() {
+            None => {
+                let () = thread::sleep(Duration::from_secs(5));
+                process::exit(0)
+            }
+            Some(mut child) => {
+                let () = drop(lock);
+                let lock = SerialLock::try_acquire_at(&serial::lock_path(name)).unwrap();
+                let _result = child.kill_group();
+                let _status = child.wait().unwrap();
+                assert!(lock.is_some());
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Helpers for line based records sent from the child to the parent.
+//!
+//! Records are written by the child to its stderr as single lines of
+//! tab separated fields, introduced by a record type specific prefix.
+//! Tabs, newlines, and backslashes in fields are escaped.
+//!
+//! Records persisted for consumption by other tools use JSON instead,
+//! the strings of which are escaped here as well.
+
+use std::fmt::Write as _;
+use std::str;
+
+
+/// Escape a field for inclusion in a record.
+pub(crate) fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverse the escaping performed by [`escape`].
+pub(crate) fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('t') => unescaped.push('\t'),
+                Some('n') => unescaped.push('\n'),
+                Some('r') => unescaped.push('\r'),
+                Some(c) => unescaped.push(c),
+                None => unescaped.push('\\'),
+            }
+        } else {
+            unescaped.push(c)
+        }
+    }
+    unescaped
+}
+
+
+/// Append `s` to `json` as a JSON string literal, including the
+/// surrounding quotes.
+pub(crate) fn write_json_str(json: &mut String, s: &str) {
+    let () = json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _result = write!(json, "\\u{:04x}", u32::from(c));
+            }
+            c => json.push(c),
+        }
+    }
+    let () = json.push('"');
+}
+
+
+/// Extract all records that `parse` recognizes from a child's stderr,
+/// returning them along with the remaining output.
+pub(crate) fn extract<R>(stderr: &[u8], parse: impl Fn(&str) -> Option<R>) -> (Vec<R>, Vec<u8>) {
+    let mut records = Vec::new();
+    let mut remainder = Vec::with_capacity(stderr.len());
+
+    for line in stderr.split_inclusive(|b| *b == b'\n') {
+        let record = str::from_utf8(line)
+            .ok()
+            .and_then(|line| parse(line.trim_end_matches(['\n', '\r'])));
+        match record {
+            Some(record) => records.push(record),
+            None => remainder.extend_from_slice(line),
+        }
+    }
+    (records, remainder)
+}
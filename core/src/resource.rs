@@ -0,0 +1,185 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Allocation of unique resources, such as TCP ports, for children.
+//!
+//! Ports are picked by the parent by binding to an ephemeral port. To
+//! make sure that concurrently running children never get the same
+//! port, even if spawned by different test binaries, the parent holds
+//! a file lock named after each port handed out for as long as the
+//! child is alive. Ports for which the lock is already held are
+//! skipped. Lock files are kept in a directory of their own in the
+//! system's temporary directory. They are never removed, as doing so
+//! while another process is about to lock them would break mutual
+//! exclusion, but there is at most one per port.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::net::Ipv4Addr;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process;
+use std::process::Command;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use crate::serial;
+use crate::serial::SerialLock;
+
+
+/// The environment variable used for conveying the reserved ports to
+/// the child.
+const PORTS_ENV: &str = "TEST_FORK_PORTS";
+/// The environment variable used for conveying the unique token to the
+/// child.
+const TOKEN_ENV: &str = "TEST_FORK_TOKEN";
+/// The maximum number of ephemeral ports to try per reserved port.
+const MAX_ATTEMPTS: usize = 64;
+
+
+/// Generate a token unique among all processes currently running.
+fn generate_token() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    format!(
+        "{}-{}",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+
+/// Retrieve the TCP ports reserved for the current child process.
+///
+/// The ports are only available inside a child process spawned with
+/// ports reserved (see
+/// [`Builder::reserve_ports`][crate::Builder::reserve_ports]), and are
+/// guaranteed to differ from those of all other children running
+/// concurrently, including those of other test binaries. Outside of
+/// such a child, no ports are reported.
+pub fn reserved_ports() -> Vec<u16> {
+    env::var(PORTS_ENV)
+        .ok()
+        .map(|ports| {
+            ports
+                .split(',')
+                .filter_map(|port| port.parse().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Retrieve a token unique to the current child process, e.g., for
+/// naming databases or other external resources.
+///
+/// The token is assigned by the parent and stable for the lifetime of
+/// the child. It is guaranteed to differ from those of all other
+/// children running concurrently. Outside of a child process, every
+/// call returns a new token.
+pub fn unique_token() -> String {
+    env::var(TOKEN_ENV).unwrap_or_else(|_| generate_token())
+}
+
+
+/// Configure the child process spawned by `command` with a unique
+/// token.
+pub(crate) fn configure(command: &mut Command) {
+    command.env(TOKEN_ENV, generate_token());
+}
+
+/// Determine the path of the file backing the reservation lock of
+/// `port`, creating its parent directory as necessary.
+fn port_lock_path(port: u16) -> io::Result<PathBuf> {
+    let dir = env::temp_dir().join("test-fork-ports");
+    let () = fs::create_dir_all(&dir)?;
+    Ok(dir.join(format!("{port}.lock")))
+}
+
+/// Reserve a single TCP port, not reserved by anybody else.
+fn reserve_port() -> io::Result<(TcpListener, SerialLock)> {
+    for _ in 0..MAX_ATTEMPTS {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let port = listener.local_addr()?.port();
+        if let Some(lock) = SerialLock::try_acquire_at(&port_lock_path(port)?)? {
+            return Ok((listener, lock))
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AddrInUse,
+        "failed to find an unreserved port",
+    ))
+}
+
+/// Reserve `count` TCP ports for the child process spawned by
+/// `command`.
+///
+/// The ports remain reserved until the child got dropped.
+pub(crate) fn reserve_ports(command: &mut Command, count: usize) -> io::Result<()> {
+    // Keep all listeners alive until we are done, so that we don't pick
+    // the same port twice.
+    let mut listeners = Vec::with_capacity(count);
+    let mut ports = Vec::with_capacity(count);
+    for _ in 0..count {
+        let (listener, lock) = reserve_port()?;
+        let () = ports.push(listener.local_addr()?.port().to_string());
+        let () = listeners.push(listener);
+        let () = serial::hold(lock);
+    }
+    log!("reserved ports: {ports:?}");
+    command.env(PORTS_ENV, ports.join(","));
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::fork_info;
+    use crate::Builder;
+
+
+    /// Check that reserved ports are conveyed to the child and can be
+    /// bound there.
+    #[test]
+    fn port_reservation() {
+        let () = Builder::new()
+            .reserve_ports(3)
+            .fork(fork_id!(), fork_test_name!(port_reservation), || {
+                let ports = reserved_ports();
+                assert_eq!(ports.len(), 3);
+                let mut unique = ports.clone();
+                let () = unique.sort();
+                let () = unique.dedup();
+                assert_eq!(unique.len(), 3);
+
+                for port in ports {
+                    let path = port_lock_path(port).unwrap();
+                    let lock = SerialLock::try_acquire_at(&path).unwrap();
+                    assert!(lock.is_none());
+                    let _listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).unwrap();
+                }
+            })
+            .unwrap();
+    }
+
+    /// Check that tokens generated outside of a child are unique.
+    #[test]
+    fn token_generation() {
+        assert_ne!(unique_token(), unique_token());
+    }
+
+    /// Check that the child's token is stable and assigned by the
+    /// parent.
+    #[test]
+    fn token_assignment() {
+        let () = Builder::new()
+            .fork(fork_id!(), fork_test_name!(token_assignment), || {
+                let parent_pid = fork_info().unwrap().parent_pid();
+                assert!(unique_token().starts_with(&format!("{parent_pid}-")));
+                assert_eq!(unique_token(), unique_token());
+            })
+            .unwrap();
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Detection of sanitizer reports in the output of child processes.
+//!
+//! Depending on their configuration (e.g., `halt_on_error=0`),
+//! sanitizers may report an error yet let the process exit
+//! successfully. To catch such cases, the child's stderr can be
+//! scanned for the tell-tale markers of a sanitizer report.
+
+use std::env;
+use std::process::Command;
+use std::str;
+
+
+/// Markers identifying the start of a sanitizer report.
+const MARKERS: [&str; 6] = [
+    "ERROR: AddressSanitizer",
+    "ERROR: HWAddressSanitizer",
+    "ERROR: LeakSanitizer",
+    "WARNING: MemorySanitizer",
+    "WARNING: ThreadSanitizer",
+    // UndefinedBehaviorSanitizer does not emit a header, but all its
+    // diagnostics follow this pattern.
+    ": runtime error: ",
+];
+
+/// The environment variables used for configuring the various
+/// sanitizers.
+pub(crate) const OPTIONS_ENVS: [&str; 5] = [
+    "ASAN_OPTIONS",
+    "LSAN_OPTIONS",
+    "MSAN_OPTIONS",
+    "TSAN_OPTIONS",
+    "UBSAN_OPTIONS",
+];
+
+
+/// Find the first sanitizer report in the given output, returning the
+/// line introducing it.
+pub(crate) fn find_report(stderr: &[u8]) -> Option<String> {
+    stderr
+        .split(|b| *b == b'\n')
+        .filter_map(|line| str::from_utf8(line).ok())
+        .find(|line| MARKERS.iter().any(|marker| line.contains(marker)))
+        .map(|line| line.trim().to_string())
+}
+
+/// Configure the sanitizers of the child process spawned by `command`
+/// such that their reports end up on stderr, where we can find them.
+///
+/// Options the user set in the respective environment variables are
+/// preserved, except for `log_path`, which would divert reports into a
+/// file.
+pub(crate) fn configure(command: &mut Command) {
+    for var in OPTIONS_ENVS {
+        let options = env::var(var).unwrap_or_default();
+        // Later options take precedence over earlier ones.
+        let options = if options.is_empty() {
+            "log_path=stderr".to_string()
+        } else {
+            format!("{options}:log_path=stderr")
+        };
+        command.env(var, options);
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that we detect sanitizer reports.
+    #[test]
+    fn report_detection() {
+        let stderr = b"running 1 test\n\
+            =================================================================\n\
+            ==1234==ERROR: AddressSanitizer: heap-use-after-free on address 0x1\n\
+            READ of size 4 at 0x1 thread T0\n";
+        assert_eq!(
+            find_report(stderr).as_deref(),
+            Some("==1234==ERROR: AddressSanitizer: heap-use-after-free on address 0x1")
+        );
+
+        let stderr = b"src/lib.rs:3:5: runtime error: signed integer overflow\n";
+        assert!(find_report(stderr).is_some());
+
+        let stderr = b"running 1 test\ntest foo ... ok\n";
+        assert_eq!(find_report(stderr), None);
+    }
+}
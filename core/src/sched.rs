@@ -0,0 +1,184 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Control over the scheduling of the child: its priority and the CPUs
+//! it may run on.
+
+use std::process::Command;
+
+
+/// The environment variable used for conveying the CPU affinity mask to
+/// the child on Windows, where it is applied by the child itself.
+#[cfg(windows)]
+const AFFINITY_ENV: &str = "TEST_FORK_CPU_AFFINITY";
+
+
+/// Convert a list of CPU indices into an affinity mask, ignoring CPUs
+/// that can't be represented.
+#[cfg(any(windows, test))]
+fn affinity_mask(cpus: &[usize]) -> usize {
+    cpus.iter()
+        .filter_map(|cpu| 1usize.checked_shl(u32::try_from(*cpu).ok()?))
+        .fold(0, |mask, bit| mask | bit)
+}
+
+
+/// Configure the scheduling of the child process spawned by `command`.
+#[cfg(unix)]
+pub(crate) fn configure(command: &mut Command, nice: Option<i32>, cpus: Option<&[usize]>) {
+    use std::io;
+    #[cfg(target_os = "linux")]
+    use std::mem;
+    use std::os::unix::process::CommandExt as _;
+
+    #[cfg(target_os = "linux")]
+    let cpu_set = cpus.map(|cpus| {
+        // SAFETY: `cpu_set_t` is a plain bit set, for which all zeroes
+        //         is a valid (empty) value.
+        let mut cpu_set = unsafe { mem::zeroed::<libc::cpu_set_t>() };
+        for cpu in cpus {
+            // SAFETY: `CPU_SET` ignores CPUs that are out of range.
+            let () = unsafe { libc::CPU_SET(*cpu, &mut cpu_set) };
+        }
+        cpu_set
+    });
+    #[cfg(not(target_os = "linux"))]
+    let _cpus = cpus;
+
+    let set_up = move || {
+        if let Some(nice) = nice {
+            // SAFETY: `setpriority` is always safe to call.
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } == -1 {
+                return Err(io::Error::last_os_error())
+            }
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(cpu_set) = &cpu_set {
+            let size = mem::size_of_val(cpu_set);
+            // SAFETY: `cpu_set` is a valid `cpu_set_t` of `size` bytes.
+            if unsafe { libc::sched_setaffinity(0, size, cpu_set) } == -1 {
+                return Err(io::Error::last_os_error())
+            }
+        }
+        Ok(())
+    };
+
+    if nice.is_some() || (cfg!(target_os = "linux") && cpus.is_some()) {
+        // SAFETY: `set_up` only calls async-signal-safe functions.
+        let _command = unsafe { command.pre_exec(set_up) };
+    }
+}
+
+/// Configure the scheduling of the child process spawned by `command`.
+#[cfg(windows)]
+pub(crate) fn configure(command: &mut Command, nice: Option<i32>, cpus: Option<&[usize]>) {
+    use std::os::windows::process::CommandExt as _;
+
+    const IDLE_PRIORITY_CLASS: u32 = 0x0000_0040;
+    const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+    const ABOVE_NORMAL_PRIORITY_CLASS: u32 = 0x0000_8000;
+    const HIGH_PRIORITY_CLASS: u32 = 0x0000_0080;
+
+    // Map the niceness onto the closest priority class.
+    let class = match nice {
+        None | Some(0) => None,
+        Some(15..) => Some(IDLE_PRIORITY_CLASS),
+        Some(1..) => Some(BELOW_NORMAL_PRIORITY_CLASS),
+        Some(..=-15) => Some(HIGH_PRIORITY_CLASS),
+        Some(_) => Some(ABOVE_NORMAL_PRIORITY_CLASS),
+    };
+    if let Some(class) = class {
+        command.creation_flags(class);
+    }
+
+    if let Some(cpus) = cpus {
+        command.env(AFFINITY_ENV, affinity_mask(cpus).to_string());
+    }
+}
+
+/// Configure the scheduling of the child process spawned by `command`.
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn configure(_command: &mut Command, _nice: Option<i32>, _cpus: Option<&[usize]>) {}
+
+
+/// Apply the CPU affinity requested by the parent to the current
+/// process, if any.
+#[cfg(windows)]
+pub(crate) fn apply() {
+    use std::env;
+    use std::ffi::c_void;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcess() -> *mut c_void;
+        fn SetProcessAffinityMask(process: *mut c_void, mask: usize) -> i32;
+    }
+
+    if let Some(mask) = env::var(AFFINITY_ENV)
+        .ok()
+        .and_then(|mask| mask.parse::<usize>().ok())
+    {
+        // SAFETY: `GetCurrentProcess` is always safe to call.
+        let process = unsafe { GetCurrentProcess() };
+        // SAFETY: `process` is a valid process handle.
+        if unsafe { SetProcessAffinityMask(process, mask) } == 0 {
+            eprintln!("test-fork: failed to set CPU affinity mask {mask:#x}");
+        }
+    }
+}
+
+/// Apply the CPU affinity requested by the parent to the current
+/// process, if any.
+#[cfg(not(windows))]
+pub(crate) fn apply() {}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that we convert CPU indices into an affinity mask
+    /// correctly.
+    #[test]
+    fn affinity_mask_conversion() {
+        assert_eq!(affinity_mask(&[]), 0);
+        assert_eq!(affinity_mask(&[0, 2, 3]), 0b1101);
+        assert_eq!(affinity_mask(&[1, 1000]), 0b10);
+    }
+
+    /// Check that the child runs with the configured niceness and CPU
+    /// affinity.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn niceness_and_affinity() {
+        use std::fs;
+
+        use crate::fork::fork_int;
+
+        fork_int(
+            fork_test_name!(niceness_and_affinity),
+            fork_id!(),
+            |cmd| configure(cmd, Some(7), Some(&[0])),
+            |mut child| assert!(child.wait().unwrap().success()),
+            || {
+                let stat = fs::read_to_string("/proc/self/stat").unwrap();
+                // The niceness is the 19th field; the command name in
+                // the second one is parenthesized and may contain
+                // spaces.
+                let (_, rest) = stat.rsplit_once(')').unwrap();
+                let nice = rest.split_whitespace().nth(16).unwrap();
+                assert_eq!(nice, "7");
+
+                let status = fs::read_to_string("/proc/self/status").unwrap();
+                let cpus = status
+                    .lines()
+                    .find_map(|line| line.strip_prefix("Cpus_allowed_list:"))
+                    .unwrap();
+                assert_eq!(cpus.trim(), "0");
+            },
+        )
+        .unwrap()
+    }
+}
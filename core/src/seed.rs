@@ -0,0 +1,77 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::hash::BuildHasher as _;
+use std::hash::Hasher as _;
+use std::process;
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+
+/// The environment variable used for conveying the seed to child
+/// processes (and for overriding it).
+pub(crate) const SEED_ENV: &str = "TEST_FORK_SEED";
+
+
+/// Generate a new random seed.
+fn generate() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    let () = hasher.write_u32(process::id());
+    if let Ok(time) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        let () = hasher.write_u128(time.as_nanos());
+    }
+    hasher.finish()
+}
+
+
+/// Retrieve the seed for randomized tests.
+///
+/// The seed is stable for the lifetime of the process and propagated
+/// to all child processes, so that a forked test sees the same value
+/// as its parent. It is randomly generated, unless the
+/// `TEST_FORK_SEED` environment variable is set, in which case its
+/// value is used instead. The seed is reported when a child process
+/// fails, so that a randomized test can be reproduced by re-running it
+/// with `TEST_FORK_SEED` set to the reported value.
+///
+/// ## Panics
+///
+/// Panics if `TEST_FORK_SEED` is set but does not contain a valid
+/// `u64`.
+pub fn seed() -> u64 {
+    static SEED: OnceLock<u64> = OnceLock::new();
+
+    *SEED.get_or_init(|| match env::var(SEED_ENV) {
+        Ok(seed) => seed.trim().parse().unwrap_or_else(|err| {
+            panic!("test-fork: failed to parse {SEED_ENV} value `{seed}`: {err}")
+        }),
+        Err(_) => generate(),
+    })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::fork::fork_int;
+
+
+    /// Check that the seed is propagated to the child process.
+    #[test]
+    fn seed_propagation() {
+        let parent_seed = seed();
+        assert_eq!(seed(), parent_seed);
+
+        fork_int(
+            fork_test_name!(seed_propagation),
+            fork_id!(),
+            |_| (),
+            |mut child| assert!(child.wait().unwrap().success()),
+            || assert_eq!(seed(), parent_seed),
+        )
+        .unwrap()
+    }
+}
@@ -0,0 +1,311 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Serialization of children across processes via named file locks.
+//!
+//! Right before spawning a child of a test using a serialization lock,
+//! the parent acquires an exclusive lock on a file named after the
+//! lock, located in the system's temporary directory. The lock is held
+//! by the [`ChildWrapper`][crate::ChildWrapper] of the child and
+//! released once that is dropped, i.e., after the child exited. Because
+//! the lock is a file lock, it serializes children of different test
+//! binaries just as well as children spawned by different threads of
+//! the same one.
+
+use std::cell::RefCell;
+use std::env;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+#[cfg(all(unix, feature = "raw-fork"))]
+use std::os::fd::AsRawFd as _;
+#[cfg(all(unix, feature = "raw-fork"))]
+use std::os::fd::RawFd;
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(all(unix, feature = "raw-fork"))]
+use std::sync::Mutex;
+#[cfg(all(unix, feature = "raw-fork"))]
+use std::sync::MutexGuard;
+#[cfg(all(unix, feature = "raw-fork"))]
+use std::sync::PoisonError;
+
+
+thread_local! {
+    /// The locks acquired for the next child spawned by the current
+    /// thread.
+    static PREPARED: RefCell<Vec<SerialLock>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The file descriptors of all locks held by the current process.
+///
+/// A child created via a raw fork inherits all of them and has to close
+/// them, lest it keeps the locks held.
+#[cfg(all(unix, feature = "raw-fork"))]
+static HELD: Mutex<Vec<RawFd>> = Mutex::new(Vec::new());
+
+
+/// Determine the path of the file backing the lock with the given
+/// name.
+pub(crate) fn lock_path(name: &str) -> PathBuf {
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    env::temp_dir().join(format!("test-fork-serial-{name}.lock"))
+}
+
+/// Acquire an exclusive lock on `file`, blocking until it is available
+/// if `block` is set.
+///
+/// Returns whether the lock got acquired.
+#[cfg(unix)]
+fn lock(file: &File, block: bool) -> io::Result<bool> {
+    use std::os::fd::AsRawFd as _;
+
+    let operation = if block {
+        libc::LOCK_EX
+    } else {
+        libc::LOCK_EX | libc::LOCK_NB
+    };
+    loop {
+        // SAFETY: `file` is a valid open file descriptor.
+        let rc = unsafe { libc::flock(file.as_raw_fd(), operation) };
+        if rc == 0 {
+            break Ok(true)
+        }
+        let err = io::Error::last_os_error();
+        match err.kind() {
+            io::ErrorKind::Interrupted => continue,
+            io::ErrorKind::WouldBlock => break Ok(false),
+            _ => break Err(err),
+        }
+    }
+}
+
+/// Acquire an exclusive lock on `file`, blocking until it is available
+/// if `block` is set.
+///
+/// Returns whether the lock got acquired.
+#[cfg(windows)]
+fn lock(file: &File, block: bool) -> io::Result<bool> {
+    use std::ffi::c_void;
+    use std::mem;
+    use std::os::windows::io::AsRawHandle as _;
+
+    const LOCKFILE_FAIL_IMMEDIATELY: u32 = 0x1;
+    const LOCKFILE_EXCLUSIVE_LOCK: u32 = 0x2;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+
+    #[repr(C)]
+    struct Overlapped {
+        internal: usize,
+        internal_high: usize,
+        offset: u32,
+        offset_high: u32,
+        event: *mut c_void,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn LockFileEx(
+            file: *mut c_void,
+            flags: u32,
+            reserved: u32,
+            bytes_low: u32,
+            bytes_high: u32,
+            overlapped: *mut Overlapped,
+        ) -> i32;
+    }
+
+    // SAFETY: `Overlapped` is plain data, for which all zeroes is a
+    //         valid value.
+    let mut overlapped = unsafe { mem::zeroed::<Overlapped>() };
+    // SAFETY: `file` is a valid open file handle and `overlapped` is
+    //         valid for the duration of the call.
+    let rc = unsafe {
+        LockFileEx(
+            file.as_raw_handle(),
+            if block {
+                LOCKFILE_EXCLUSIVE_LOCK
+            } else {
+                LOCKFILE_EXCLUSIVE_LOCK | LOCKFILE_FAIL_IMMEDIATELY
+            },
+            0,
+            u32::MAX,
+            u32::MAX,
+            &mut overlapped,
+        )
+    };
+    if rc != 0 {
+        return Ok(true)
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(ERROR_LOCK_VIOLATION) {
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
+
+/// Acquire an exclusive lock on `file`, blocking until it is available
+/// if `block` is set.
+///
+/// Returns whether the lock got acquired.
+#[cfg(not(any(unix, windows)))]
+fn lock(_file: &File, _block: bool) -> io::Result<bool> {
+    Ok(true)
+}
+
+
+/// A held serialization lock, released when dropped.
+#[derive(Debug)]
+pub(crate) struct SerialLock {
+    /// The locked file. Closing it releases the lock.
+    ///
+    /// Only `None` while the lock is being dropped.
+    file: Option<File>,
+}
+
+impl SerialLock {
+    /// Acquire the lock backed by the file at `path`, blocking until
+    /// it is available if `block` is set.
+    fn lock(path: &Path, block: bool) -> io::Result<Option<Self>> {
+        let open = || {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)
+        };
+        // Register the file as soon as it is open, so that a concurrent
+        // raw fork can't inherit it unnoticed.
+        #[cfg(all(unix, feature = "raw-fork"))]
+        let file = {
+            let mut held = held();
+            let file = open()?;
+            let () = held.push(file.as_raw_fd());
+            file
+        };
+        #[cfg(not(all(unix, feature = "raw-fork")))]
+        let file = open()?;
+
+        let locked = lock(&file, block);
+        let slf = Self { file: Some(file) };
+        Ok(locked?.then_some(slf))
+    }
+
+    /// Acquire the lock with the given name, blocking until it is
+    /// available.
+    pub fn acquire(name: &str) -> io::Result<Self> {
+        let lock = Self::lock(&lock_path(name), true)?;
+        lock.ok_or_else(|| io::Error::other("failed to acquire lock"))
+    }
+
+    /// Acquire the lock backed by the file at `path`, if it is
+    /// available.
+    pub fn try_acquire_at(path: &Path) -> io::Result<Option<Self>> {
+        Self::lock(path, false)
+    }
+}
+
+impl Drop for SerialLock {
+    fn drop(&mut self) {
+        // Close the file while it is still registered (see above).
+        #[cfg(all(unix, feature = "raw-fork"))]
+        let mut held = held();
+        if let Some(file) = self.file.take() {
+            #[cfg(all(unix, feature = "raw-fork"))]
+            {
+                let fd = file.as_raw_fd();
+                let () = held.retain(|held| *held != fd);
+            }
+            let () = drop(file);
+        }
+    }
+}
+
+
+/// Retrieve the file descriptors of all locks held by the current
+/// process.
+///
+/// No locks are acquired or released while the returned guard is alive.
+#[cfg(all(unix, feature = "raw-fork"))]
+pub(crate) fn held() -> MutexGuard<'static, Vec<RawFd>> {
+    HELD.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+
+/// Make the calling thread hold on to `lock` for the next child it
+/// spawns, to be retrieved via [`take`] afterwards.
+pub(crate) fn hold(lock: SerialLock) {
+    let () = PREPARED.with(|prepared| prepared.borrow_mut().push(lock));
+}
+
+/// Acquire the lock with the given name for the next child spawned by
+/// the calling thread.
+pub(crate) fn prepare(name: &str) {
+    log!("acquiring serialization lock `{name}`");
+    match SerialLock::acquire(name) {
+        Ok(lock) => hold(lock),
+        Err(err) => panic!("test-fork: failed to acquire serialization lock `{name}`: {err}"),
+    }
+}
+
+/// Retrieve the locks previously acquired by the calling thread.
+pub(crate) fn take() -> Vec<SerialLock> {
+    PREPARED.with(RefCell::take)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+
+    /// Check that lock names are mapped to file names correctly.
+    #[test]
+    fn lock_naming() {
+        let path = lock_path("db");
+        assert_eq!(path.file_name().unwrap(), "test-fork-serial-db.lock");
+        let path = lock_path("../a b");
+        assert_eq!(path.file_name().unwrap(), "test-fork-serial-___a_b.lock");
+    }
+
+    /// Check that a lock can only be held once at a time.
+    #[test]
+    fn lock_exclusion() {
+        let name = "serial-test-lock-exclusion";
+        let lock = SerialLock::acquire(name).unwrap();
+        let acquired = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let acquired = Arc::clone(&acquired);
+            thread::spawn(move || {
+                let _lock = SerialLock::acquire(name).unwrap();
+                let () = acquired.store(true, Ordering::SeqCst);
+            })
+        };
+
+        let () = thread::sleep(Duration::from_millis(100));
+        assert!(!acquired.load(Ordering::SeqCst));
+        assert!(SerialLock::try_acquire_at(&lock_path(name))
+            .unwrap()
+            .is_none());
+        drop(lock);
+        let () = thread.join().unwrap();
+        assert!(acquired.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Memory shared between the parent and the child, for exchanging
+//! large amounts of data without copying them over a socket.
+//!
+//! The memory is backed by a file, which is mapped into both processes.
+//! On Linux, the file is created in `/dev/shm`, so that it lives in
+//! memory only. Elsewhere, the system's temporary directory is used.
+
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::os::unix::io::AsRawFd as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::ptr;
+use std::ptr::NonNull;
+use std::slice;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+
+/// Find the directory to create backing files in.
+fn backing_dir() -> PathBuf {
+    let shm = Path::new("/dev/shm");
+    if cfg!(target_os = "linux") && shm.is_dir() {
+        shm.to_path_buf()
+    } else {
+        env::temp_dir()
+    }
+}
+
+/// Map `len` bytes of `file` into memory, shared with all other
+/// processes mapping it.
+fn map(file: &File, len: usize) -> io::Result<NonNull<u8>> {
+    // Zero sized mappings are not supported, but also not needed.
+    if len == 0 {
+        return Ok(NonNull::dangling())
+    }
+
+    // SAFETY: `file` is a valid open file descriptor and we let the
+    //         system pick the address.
+    let addr = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if addr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error())
+    }
+    Ok(NonNull::new(addr.cast()).expect("mmap returned null pointer"))
+}
+
+
+/// A buffer shared with child processes via a memory mapped file.
+///
+/// A `SharedBuffer` is meant to be used with [`Builder::fork_shared`],
+/// which makes its contents available to the child without copying
+/// them. Changes made by the child are directly visible in the
+/// parent's buffer. Contrary to
+/// [`Builder::fork_in_out`][crate::Builder::fork_in_out], the size of
+/// the buffer is fixed on creation.
+///
+/// [`Builder::fork_shared`]: crate::Builder::fork_shared
+#[derive(Debug)]
+pub struct SharedBuffer {
+    /// The start of the mapping.
+    ptr: NonNull<u8>,
+    /// The length of the mapping, in bytes.
+    len: usize,
+    /// The path to the backing file, if it is owned by this buffer.
+    path: Option<PathBuf>,
+}
+
+// SAFETY: The buffer exclusively owns its mapping and access is
+//         governed by Rust's borrowing rules.
+unsafe impl Send for SharedBuffer {}
+// SAFETY: See above.
+unsafe impl Sync for SharedBuffer {}
+
+impl SharedBuffer {
+    /// Create a zero-initialized buffer of `len` bytes.
+    pub fn new(len: usize) -> io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let path = backing_dir().join(format!(
+            "test-fork-shared-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        let result = u64::try_from(len)
+            .map_err(io::Error::other)
+            .and_then(|size| file.set_len(size))
+            .and_then(|()| map(&file, len));
+        match result {
+            Ok(ptr) => Ok(Self {
+                ptr,
+                len,
+                path: Some(path),
+            }),
+            Err(err) => {
+                let _result = fs::remove_file(&path);
+                Err(err)
+            }
+        }
+    }
+
+    /// Create a buffer holding a copy of `data`.
+    pub fn from_slice(data: &[u8]) -> io::Result<Self> {
+        let mut buffer = Self::new(data.len())?;
+        let () = buffer.copy_from_slice(data);
+        Ok(buffer)
+    }
+
+    /// Map the buffer backed by the file at `path`, as created by the
+    /// parent.
+    pub(crate) fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let len = usize::try_from(file.metadata()?.len()).map_err(io::Error::other)?;
+        let ptr = map(&file, len)?;
+        Ok(Self {
+            ptr,
+            len,
+            path: None,
+        })
+    }
+
+    /// Retrieve the path to the file backing the buffer, if it is
+    /// owned by this buffer.
+    pub(crate) fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
+
+impl Deref for SharedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: `ptr` points to a mapping of `len` bytes (or is
+        //         dangling for a length of zero), which lives as long
+        //         as `self`.
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for SharedBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: See `Deref` implementation.
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for SharedBuffer {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            // SAFETY: `ptr` and `len` describe a mapping we created and
+            //         which is no longer referenced.
+            let _result = unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len) };
+        }
+        if let Some(path) = &self.path {
+            let _result = fs::remove_file(path);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that changes made through one mapping of a buffer are
+    /// visible through another.
+    #[test]
+    fn shared_mapping() {
+        let mut buffer = SharedBuffer::from_slice(b"hello").unwrap();
+        let path = buffer.path().unwrap().to_path_buf();
+        assert!(path.exists());
+
+        let mut other = SharedBuffer::open(&path).unwrap();
+        assert_eq!(&*other, b"hello");
+        *other.first_mut().unwrap() = b'j';
+        assert_eq!(&*buffer, b"jello");
+        *buffer.last_mut().unwrap() = b'y';
+        assert_eq!(&*other, b"jelly");
+
+        drop(other);
+        assert!(path.exists());
+        drop(buffer);
+        assert!(!path.exists());
+    }
+
+    /// Check that empty buffers are supported.
+    #[test]
+    fn empty_buffer() {
+        let buffer = SharedBuffer::new(0).unwrap();
+        assert!(buffer.is_empty());
+
+        let other = SharedBuffer::open(buffer.path().unwrap()).unwrap();
+        assert!(other.is_empty());
+    }
+}
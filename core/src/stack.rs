@@ -0,0 +1,177 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Capturing of the stacks of a hung child process.
+//!
+//! Before a hung child gets killed, we try to find out what it was
+//! doing. To that end, an external stack dumping tool is run on it.
+//! The tool can be provided via the `TEST_FORK_STACK_DUMPER`
+//! environment variable, in which case it is invoked with the process
+//! ID of the child as its last argument. Otherwise, a couple of well
+//! known tools are tried in turn. If none is available, we fall back to
+//! the per-thread information the kernel provides on Linux, which at
+//! least tells where each thread is blocked.
+
+use std::env;
+use std::io::Read as _;
+use std::process::Command;
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+
+/// The environment variable providing the command to dump the stacks
+/// of a process with.
+const DUMPER_ENV: &str = "TEST_FORK_STACK_DUMPER";
+/// The time we allow for a stack dumping tool to run.
+const DUMPER_TIMEOUT: Duration = Duration::from_secs(30);
+/// The interval at which to check whether the stack dumping tool
+/// exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+
+/// Run the given command, returning its standard output if it
+/// succeeded within [`DUMPER_TIMEOUT`] and produced any.
+fn run_dumper(mut command: Command) -> Option<String> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    // Read output concurrently, lest the tool blocks on a full pipe.
+    let mut stdout = child.stdout.take()?;
+    let reader = thread::spawn(move || {
+        let mut output = Vec::new();
+        let _result = stdout.read_to_end(&mut output);
+        output
+    });
+
+    let start = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if start.elapsed() < DUMPER_TIMEOUT => thread::sleep(POLL_INTERVAL),
+            Ok(None) | Err(_) => {
+                log!("stack dumper {command:?} did not finish; killing it");
+                let _result = child.kill();
+                let _status = child.wait();
+                break None
+            }
+        }
+    };
+    let output = reader.join().ok()?;
+
+    if !status?.success() {
+        return None
+    }
+    let output = String::from_utf8_lossy(&output).trim_end().to_string();
+    (!output.is_empty()).then_some(output)
+}
+
+/// Assemble a description of the threads of the process with the given
+/// ID from the information provided by the kernel.
+#[cfg(target_os = "linux")]
+fn kernel_stacks(pid: u32) -> Option<String> {
+    use std::fs;
+
+    let mut tids = fs::read_dir(format!("/proc/{pid}/task"))
+        .ok()?
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .collect::<Vec<_>>();
+    let () = tids.sort_unstable();
+
+    let threads = tids
+        .into_iter()
+        .map(|tid| {
+            let read = |file| fs::read_to_string(format!("/proc/{pid}/task/{tid}/{file}"));
+            let comm = read("comm").unwrap_or_default();
+            let mut thread = format!("thread {tid} ({})", comm.trim());
+            if let Ok(wchan) = read("wchan") {
+                if !wchan.is_empty() && wchan != "0" {
+                    thread += &format!(" waiting in {wchan}");
+                }
+            }
+            // The kernel stack is only readable with elevated
+            // privileges.
+            if let Ok(stack) = read("stack") {
+                for frame in stack.lines() {
+                    thread += &format!("\n  {frame}");
+                }
+            }
+            thread
+        })
+        .collect::<Vec<_>>();
+
+    (!threads.is_empty()).then(|| threads.join("\n"))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kernel_stacks(_pid: u32) -> Option<String> {
+    None
+}
+
+/// Capture the stacks of the process with the given ID.
+///
+/// Setting `TEST_FORK_STACK_DUMPER` to an empty value disables the
+/// capturing.
+pub(crate) fn capture(pid: u32) -> Option<String> {
+    let pid_arg = pid.to_string();
+
+    if let Some(dumper) = env::var_os(DUMPER_ENV) {
+        if dumper.is_empty() {
+            return None
+        }
+        let mut command = Command::new(dumper);
+        let _command = command.arg(&pid_arg);
+        return run_dumper(command)
+    }
+
+    let dumpers: [(&str, &[&str]); 2] = [
+        ("eu-stack", &["-p"]),
+        ("gdb", &["--batch", "-ex", "thread apply all bt", "-p"]),
+    ];
+    dumpers
+        .iter()
+        .find_map(|(program, args)| {
+            let mut command = Command::new(program);
+            let _command = command.args(*args).arg(&pid_arg);
+            run_dumper(command)
+        })
+        .or_else(|| kernel_stacks(pid))
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that we can capture the stacks of a blocked process.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn blocked_process_capture() {
+        let mut child = Command::new("sleep").arg("60").spawn().unwrap();
+        let stacks = capture(child.id());
+        let _result = child.kill();
+        let _status = child.wait().unwrap();
+
+        let stacks = stacks.unwrap();
+        assert!(stacks.contains("sleep"), "{stacks}");
+    }
+
+    /// Check that output of a failing stack dumping tool is ignored.
+    #[cfg(unix)]
+    #[test]
+    fn failing_dumper() {
+        let mut command = Command::new("sh");
+        let _command = command.args(["-c", "echo stack; exit 1"]);
+        assert_eq!(run_dumper(command), None);
+
+        let mut command = Command::new("sh");
+        let _command = command.args(["-c", "echo stack"]);
+        assert_eq!(run_dumper(command).as_deref(), Some("stack"));
+    }
+}
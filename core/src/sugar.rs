@@ -25,6 +25,18 @@ use std::hash::Hasher;
 /// This is usually the best thing to pass for the `fork_id` argument of
 /// [`fork`][crate::fork()].
 ///
+/// Optionally, a string literal can be provided, in which case it is
+/// used as the identifier verbatim. Such an identifier is stable across
+/// differently built executables, but it is the caller's responsibility
+/// to ensure its uniqueness. It may only consist of ASCII alphanumeric
+/// characters, `-`, `_`, and `.`, which is checked at compile time.
+///
+/// ```rust
+/// # use test_fork_core::fork_id;
+/// let id = fork_id!("my-id");
+/// assert_eq!(id, ":my-id");
+/// ```
+///
 /// The type of the expression this macro expands to is [`ForkId`].
 #[macro_export]
 macro_rules! fork_id {
@@ -32,25 +44,56 @@ macro_rules! fork_id {
         struct _ForkId;
         &std::string::ToString::to_string(&$crate::ForkId::of(::std::any::TypeId::of::<_ForkId>()))
     }};
+    ($id:literal) => {{
+        const ID: $crate::ForkId = $crate::ForkId::named($id);
+        &std::string::ToString::to_string(&ID)
+    }};
+}
+
+
+#[derive(Clone, Hash, PartialEq, Eq, Debug)]
+enum Repr {
+    Type(TypeId),
+    Named(&'static str),
 }
 
 
 /// The type of the value produced by [`fork_id!`].
 #[derive(Clone, Hash, PartialEq, Eq, Debug)]
-pub struct ForkId(TypeId);
+pub struct ForkId(Repr);
 
 impl ForkId {
     #[doc(hidden)]
     pub fn of(id: TypeId) -> Self {
-        Self(id)
+        Self(Repr::Type(id))
+    }
+
+    #[doc(hidden)]
+    pub const fn named(id: &'static str) -> Self {
+        let mut bytes = id.as_bytes();
+        assert!(!bytes.is_empty(), "fork ID must not be empty");
+
+        while let [b, rest @ ..] = bytes {
+            assert!(
+                b.is_ascii_alphanumeric() || *b == b'-' || *b == b'_' || *b == b'.',
+                "fork ID may only contain ASCII alphanumeric characters, `-`, `_`, and `.`"
+            );
+            bytes = rest;
+        }
+        Self(Repr::Named(id))
     }
 }
 
 impl Display for ForkId {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        let mut hasher = DefaultHasher::default();
-        self.0.hash(&mut hasher);
-        write!(f, ":{:016X}", hasher.finish())
+        match &self.0 {
+            Repr::Type(id) => {
+                let mut hasher = DefaultHasher::default();
+                id.hash(&mut hasher);
+                write!(f, ":{:016X}", hasher.finish())
+            }
+            Repr::Named(id) => write!(f, ":{id}"),
+        }
     }
 }
 
@@ -77,4 +120,16 @@ mod test {
         assert_ne!(id1, id2);
         assert_ne!(id1.to_string(), id2.to_string());
     }
+
+    /// Check that user provided IDs are used verbatim.
+    #[test]
+    fn named_ids() {
+        let id1 = fork_id!("some-id");
+        let id2 = fork_id!("some-id");
+        assert_eq!(id1, id2);
+        assert_eq!(id1, ":some-id");
+
+        let id3 = fork_id!("other_id.1");
+        assert_ne!(id1, id3);
+    }
 }
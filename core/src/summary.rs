@@ -0,0 +1,270 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! An end-of-run summary of the children spawned by a test binary.
+//!
+//! If the `TEST_FORK_SUMMARY` environment variable is set, the parent
+//! keeps statistics about all the children it spawns and prints them to
+//! stderr when it exits: the number of children spawned, how many of
+//! them failed, got killed, or timed out, the total CPU time they
+//! consumed, and the slowest tests run in them. The summary is printed
+//! from an `atexit` handler, which is registered once the first child
+//! got spawned.
+
+use std::env;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::sync::Once;
+use std::sync::OnceLock;
+use std::sync::PoisonError;
+use std::time::Duration;
+
+use crate::failure::ChildFailure;
+use crate::fork_info;
+
+
+/// The environment variable enabling the summary.
+const SUMMARY_ENV: &str = "TEST_FORK_SUMMARY";
+/// The number of slowest tests to report.
+const MAX_SLOWEST: usize = 5;
+
+
+/// The statistics gathered about all children.
+static STATS: Mutex<Stats> = Mutex::new(Stats::new());
+
+
+/// Statistics about the children spawned by the current process.
+#[derive(Debug)]
+struct Stats {
+    /// The number of children spawned.
+    spawned: usize,
+    /// The number of children that failed, for whatever reason.
+    failed: usize,
+    /// The number of children terminated by a signal.
+    killed: usize,
+    /// The number of children that were considered hung.
+    timed_out: usize,
+    /// The slowest tests along with the time their child ran for,
+    /// slowest first.
+    slowest: Vec<(Duration, String)>,
+}
+
+impl Stats {
+    const fn new() -> Self {
+        Self {
+            spawned: 0,
+            failed: 0,
+            killed: 0,
+            timed_out: 0,
+            slowest: Vec::new(),
+        }
+    }
+
+    /// Account for a child running `test_name` having exited after
+    /// `duration`, with the given failure.
+    fn record(&mut self, test_name: &str, duration: Duration, failure: Option<&ChildFailure>) {
+        match failure {
+            None => (),
+            Some(ChildFailure::Signaled { .. }) => {
+                self.failed += 1;
+                self.killed += 1;
+            }
+            Some(ChildFailure::TimedOut { .. }) => {
+                self.failed += 1;
+                self.timed_out += 1;
+            }
+            Some(_) => self.failed += 1,
+        }
+
+        let idx = self
+            .slowest
+            .iter()
+            .position(|(slow, _)| duration > *slow)
+            .unwrap_or(self.slowest.len());
+        if idx < MAX_SLOWEST {
+            let () = self.slowest.insert(idx, (duration, test_name.to_string()));
+            let () = self.slowest.truncate(MAX_SLOWEST);
+        }
+    }
+
+    /// Format the summary, including the total CPU time consumed by
+    /// all children, if known.
+    fn format(&self, cpu_time: Option<Duration>) -> String {
+        let mut summary = format!(
+            "test-fork: spawned {} children: {} failed, {} killed, {} timed out",
+            self.spawned, self.failed, self.killed, self.timed_out
+        );
+        if let Some(cpu_time) = cpu_time {
+            let _result = write!(
+                summary,
+                "; children used {:.3}s of CPU time",
+                cpu_time.as_secs_f64()
+            );
+        }
+        let () = summary.push('\n');
+
+        if !self.slowest.is_empty() {
+            let () = summary.push_str("test-fork: slowest forked tests:\n");
+            for (duration, test_name) in &self.slowest {
+                let _result = writeln!(
+                    summary,
+                    "test-fork:   {:>9.3}s {test_name}",
+                    duration.as_secs_f64()
+                );
+            }
+        }
+        summary
+    }
+}
+
+
+/// Check whether the summary is enabled.
+///
+/// Children never report a summary of their own.
+fn enabled() -> bool {
+    static ENABLED: OnceLock<bool> = OnceLock::new();
+
+    *ENABLED.get_or_init(|| env::var_os(SUMMARY_ENV).is_some() && fork_info().is_none())
+}
+
+/// Retrieve the total CPU time consumed by all children that got waited
+/// for.
+#[cfg(unix)]
+fn children_cpu_time() -> Option<Duration> {
+    use std::mem;
+
+    /// Convert a `timeval` into a `Duration`.
+    fn duration(time: libc::timeval) -> Option<Duration> {
+        let secs = u64::try_from(time.tv_sec).ok()?;
+        let micros = u32::try_from(time.tv_usec).ok()?;
+        Some(Duration::from_secs(secs) + Duration::from_micros(micros.into()))
+    }
+
+    // SAFETY: `rusage` is plain data, for which all zeroes is a valid
+    //         value.
+    let mut usage = unsafe { mem::zeroed::<libc::rusage>() };
+    // SAFETY: `usage` is valid for writes for the duration of the call.
+    let rc = unsafe { libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage) };
+    if rc != 0 {
+        return None
+    }
+    Some(duration(usage.ru_utime)? + duration(usage.ru_stime)?)
+}
+
+/// Retrieve the total CPU time consumed by all children that got waited
+/// for.
+#[cfg(not(unix))]
+fn children_cpu_time() -> Option<Duration> {
+    None
+}
+
+/// Print the summary to stderr.
+extern "C" fn report() {
+    let stats = STATS.lock().unwrap_or_else(PoisonError::into_inner);
+    eprint!("{}", stats.format(children_cpu_time()));
+}
+
+/// Register [`report`] to be run when the process exits.
+fn register() {
+    #[cfg(not(unix))]
+    extern "C" {
+        fn atexit(callback: extern "C" fn()) -> i32;
+    }
+    #[cfg(unix)]
+    use libc::atexit;
+
+    // SAFETY: `report` is a valid function to be invoked on exit.
+    let rc = unsafe { atexit(report) };
+    if rc != 0 {
+        eprintln!("test-fork: failed to register summary reporter; not reporting summary");
+    }
+}
+
+/// Account for a child having been spawned, if the summary is enabled.
+pub(crate) fn spawned() {
+    static REGISTER: Once = Once::new();
+
+    if enabled() {
+        let () = REGISTER.call_once(register);
+        let mut stats = STATS.lock().unwrap_or_else(PoisonError::into_inner);
+        stats.spawned += 1;
+    }
+}
+
+/// Account for a child running `test_name` having exited after
+/// `duration`, with the given failure, if the summary is enabled.
+pub(crate) fn record(test_name: &str, duration: Duration, failure: Option<&ChildFailure>) {
+    if enabled() {
+        let mut stats = STATS.lock().unwrap_or_else(PoisonError::into_inner);
+        let () = stats.record(test_name, duration, failure);
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    /// Check that outcomes of children are accounted for correctly.
+    #[test]
+    fn stats_recording() {
+        let mut stats = Stats::new();
+        stats.spawned = 8;
+        let () = stats.record("a", Duration::from_millis(30), None);
+        let () = stats.record(
+            "b",
+            Duration::from_millis(10),
+            Some(&ChildFailure::Signaled { signal: 9 }),
+        );
+        let () = stats.record(
+            "c",
+            Duration::from_millis(50),
+            Some(&ChildFailure::TimedOut {
+                elapsed: Duration::from_millis(50),
+                stacks: None,
+            }),
+        );
+        let () = stats.record(
+            "d",
+            Duration::from_millis(20),
+            Some(&ChildFailure::ExitCode { code: 1 }),
+        );
+        let () = stats.record("e", Duration::from_millis(40), None);
+        let () = stats.record("f", Duration::from_millis(60), None);
+        let () = stats.record("g", Duration::from_millis(5), None);
+
+        assert_eq!(stats.failed, 3);
+        assert_eq!(stats.killed, 1);
+        assert_eq!(stats.timed_out, 1);
+        let slowest = stats
+            .slowest
+            .iter()
+            .map(|(_, test_name)| test_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(slowest, ["f", "c", "e", "a", "d"]);
+
+        let summary = stats.format(Some(Duration::from_millis(1500)));
+        let expected = "\
+test-fork: spawned 8 children: 3 failed, 1 killed, 1 timed out; children used 1.500s of CPU time
+test-fork: slowest forked tests:
+test-fork:       0.060s f
+test-fork:       0.050s c
+test-fork:       0.040s e
+test-fork:       0.030s a
+test-fork:       0.020s d
+";
+        assert_eq!(summary, expected);
+    }
+
+    /// Check that an empty summary is formatted correctly.
+    #[test]
+    fn empty_summary() {
+        let stats = Stats::new();
+        let summary = stats.format(None);
+        assert_eq!(
+            summary,
+            "test-fork: spawned 0 children: 0 failed, 0 killed, 0 timed out\n"
+        );
+    }
+}
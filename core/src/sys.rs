@@ -0,0 +1,20 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Helpers for calling into the operating system via `libc`.
+
+use std::ffi::c_int;
+use std::io;
+
+
+/// Convert the return value of a libc function reporting errors via
+/// `-1` and `errno` into an [`io::Result`].
+///
+/// This function is async-signal-safe.
+pub(crate) fn check(rc: c_int) -> io::Result<c_int> {
+    if rc == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(rc)
+    }
+}
@@ -0,0 +1,285 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Forwarding of `tracing` events from child processes to the parent.
+//!
+//! Output relayed from the child is plain text, losing levels,
+//! targets, and spans of any `tracing` events emitted by it. To
+//! preserve those, install a [`ChildSubscriber`] in the child (e.g.,
+//! via [`init`]). It serializes each event into a record on the
+//! child's stderr, which the parent picks up and re-emits into its own
+//! `tracing` subscriber.
+//!
+//! Re-emitted events use the `test_fork::child` target. The original
+//! target, the names of the spans the event was emitted in, and the
+//! ID of the child process are attached as the `child_target`,
+//! `child_spans`, and `child_pid` fields, respectively.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fmt::Write as _;
+use std::io;
+use std::io::Write as _;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use tracing::field::Field;
+use tracing::field::Visit;
+use tracing::span::Attributes;
+use tracing::span::Id;
+use tracing::span::Record;
+use tracing::subscriber;
+use tracing::subscriber::SetGlobalDefaultError;
+use tracing::Event;
+use tracing::Level;
+use tracing::Metadata;
+use tracing::Subscriber;
+
+use crate::record;
+use crate::record::escape;
+use crate::record::unescape;
+
+
+/// The prefix identifying a trace record line.
+const RECORD_PREFIX: &str = "test-fork-trace:";
+
+
+thread_local! {
+    /// The spans entered on the current thread, innermost last.
+    static STACK: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+
+/// A visitor formatting the fields of an event or span.
+#[derive(Debug, Default)]
+struct FieldFormatter {
+    /// The event's message, if any.
+    message: String,
+    /// All other fields, formatted as ` <name>=<value>` pairs.
+    fields: String,
+}
+
+impl Visit for FieldFormatter {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            let _result = write!(self.message, "{value:?}");
+        } else {
+            let _result = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            let () = self.message.push_str(value);
+        } else {
+            let _result = write!(self.fields, " {}={value}", field.name());
+        }
+    }
+}
+
+
+/// A `tracing` subscriber forwarding all events to the parent process.
+///
+/// Events are written to stderr in a format that the parent detects
+/// and re-emits into its own subscriber. Outside of a child process
+/// spawned by `test-fork`, these records are not interpreted and end
+/// up on stderr verbatim.
+#[derive(Debug, Default)]
+pub struct ChildSubscriber {
+    /// The ID to assign to the next span.
+    next_id: AtomicU64,
+    /// The names of all spans currently alive, keyed by ID.
+    spans: Mutex<HashMap<u64, String>>,
+}
+
+impl ChildSubscriber {
+    /// Create a new [`ChildSubscriber`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Retrieve the names of the spans entered on the current thread,
+    /// separated by `:`.
+    fn current_spans(&self) -> String {
+        let spans = self.spans.lock().unwrap_or_else(PoisonError::into_inner);
+        STACK.with_borrow(|stack| {
+            stack
+                .iter()
+                .filter_map(|id| spans.get(id).map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(":")
+        })
+    }
+}
+
+impl Subscriber for ChildSubscriber {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        // Span IDs must not be zero.
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut formatter = FieldFormatter::default();
+        let () = span.record(&mut formatter);
+        let name = format!("{}{{{}}}", span.metadata().name(), formatter.fields.trim());
+
+        let mut spans = self.spans.lock().unwrap_or_else(PoisonError::into_inner);
+        let _prev = spans.insert(id, name);
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut formatter = FieldFormatter::default();
+        let () = event.record(&mut formatter);
+        let metadata = event.metadata();
+        let message = format!("{}{}", formatter.message, formatter.fields);
+
+        let line = format!(
+            "{RECORD_PREFIX}\t{}\t{}\t{}\t{}\n",
+            metadata.level(),
+            escape(metadata.target()),
+            escape(&self.current_spans()),
+            escape(&message),
+        );
+        // Write the record in one go, directly to stderr, bypassing any
+        // output capturing.
+        let _result = io::stderr().lock().write_all(line.as_bytes());
+    }
+
+    fn enter(&self, span: &Id) {
+        STACK.with_borrow_mut(|stack| stack.push(span.into_u64()))
+    }
+
+    fn exit(&self, span: &Id) {
+        STACK.with_borrow_mut(|stack| {
+            if let Some(idx) = stack.iter().rposition(|id| *id == span.into_u64()) {
+                let _id = stack.remove(idx);
+            }
+        })
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let mut spans = self.spans.lock().unwrap_or_else(PoisonError::into_inner);
+        let _name = spans.remove(&span.into_u64());
+        true
+    }
+}
+
+
+/// Install a [`ChildSubscriber`] as the global default subscriber.
+///
+/// This function is meant to be called from within a test running in a
+/// child process.
+pub fn init() -> Result<(), SetGlobalDefaultError> {
+    subscriber::set_global_default(ChildSubscriber::new())
+}
+
+
+/// An event forwarded by a child process.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct TraceRecord {
+    level: Level,
+    target: String,
+    spans: String,
+    message: String,
+}
+
+impl TraceRecord {
+    /// Parse a single line (without trailing newline) into a record.
+    fn from_line(line: &str) -> Option<Self> {
+        let rest = line.strip_prefix(RECORD_PREFIX)?.strip_prefix('\t')?;
+        let mut fields = rest.splitn(4, '\t');
+        let record = Self {
+            level: fields.next()?.parse().ok()?,
+            target: unescape(fields.next()?),
+            spans: unescape(fields.next()?),
+            message: unescape(fields.next()?),
+        };
+        Some(record)
+    }
+
+    /// Emit the record into the current subscriber.
+    fn emit(&self, pid: u32) {
+        macro_rules! emit {
+            ($level:expr) => {
+                tracing::event!(
+                    target: "test_fork::child",
+                    $level,
+                    child_pid = pid,
+                    child_target = %self.target,
+                    child_spans = %self.spans,
+                    "{}",
+                    self.message
+                )
+            };
+        }
+
+        match self.level {
+            Level::ERROR => emit!(Level::ERROR),
+            Level::WARN => emit!(Level::WARN),
+            Level::INFO => emit!(Level::INFO),
+            Level::DEBUG => emit!(Level::DEBUG),
+            Level::TRACE => emit!(Level::TRACE),
+        }
+    }
+}
+
+
+/// Extract all trace records from the stderr output of the child with
+/// the given PID and re-emit them, returning the remaining output.
+pub(crate) fn forward(pid: u32, stderr: &[u8]) -> Vec<u8> {
+    let (records, remainder) = record::extract(stderr, TraceRecord::from_line);
+    let () = records.iter().for_each(|record| record.emit(pid));
+    remainder
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use tracing::info_span;
+    use tracing::warn;
+
+    use crate::fork::fork_int;
+
+
+    /// Check that events emitted in the child are forwarded correctly.
+    #[test]
+    fn event_forwarding() {
+        let stderr = fork_int(
+            fork_test_name!(event_forwarding),
+            fork_id!(),
+            |_| (),
+            |child| {
+                let output = child.wait_with_output().unwrap();
+                assert!(output.status.success());
+                output.stderr
+            },
+            || {
+                let _guard = subscriber::set_default(ChildSubscriber::new());
+                let span = info_span!("outer", id = 7);
+                let _entered = span.enter();
+                warn!(answer = 42, "hello\tworld");
+            },
+        )
+        .unwrap();
+
+        let (records, _remainder) = record::extract(&stderr, TraceRecord::from_line);
+        let expected = TraceRecord {
+            level: Level::WARN,
+            target: "test_fork_core::trace::test".to_string(),
+            spans: "outer{id=7}".to_string(),
+            message: "hello\tworld answer=42".to_string(),
+        };
+        assert_eq!(records, vec![expected]);
+    }
+}
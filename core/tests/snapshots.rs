@@ -5,10 +5,13 @@
 
 use proc_macro2::TokenStream;
 
+use quote::quote;
+
 use syn::parse2;
 use syn::parse_quote;
 use syn::AttrStyle;
 use syn::ItemFn;
+use syn::ItemMod;
 use syn::Meta;
 use syn::Result;
 
@@ -48,10 +51,16 @@ fn expand(mut input: ItemFn) -> String {
         [_, kind] if kind == "test" => test_fork_core::try_test,
         [_, kind] if kind == "bench" => test_fork_core::try_bench,
         [_, kind] if kind == "fork" => try_fork,
+        [_, kind] if kind == "main" => test_fork_core::try_main,
         [..] => panic!("encountered unsupported attribute"),
     };
 
     let tokens = (try_fn)(attr_args, input).unwrap();
+    unparse(tokens)
+}
+
+/// Format the given tokens as Rust code.
+fn unparse(tokens: TokenStream) -> String {
     let file = parse2(tokens).unwrap();
     let snapshot = prettyplease::unparse(&file);
 
@@ -85,6 +94,182 @@ fn snapshot_test_result() {
     assert_snapshot!(output);
 }
 
+/// Check expansion of a `#[test_fork::test]` test with arguments.
+#[test]
+fn snapshot_test_args() {
+    let output = expand(parse_quote! {
+        #[test_fork::test(
+            heartbeat_ms = 1000,
+            output_limit = 4096,
+            prefix_output,
+            persist_output,
+            artifacts = "on_failure",
+            color = "never",
+            sanitizer_check,
+            leak_check,
+            raw_fork,
+            debug,
+            nice = -5,
+            cpu_affinity = [0, 2],
+            serial = "db",
+            reserve_ports = 2,
+            clean_env(allow = ["PATH", "HOME"]),
+            expected_output = "tests/golden/it_works.txt",
+            stdin = "42\n",
+            before = start_server,
+            after = self::stop_server,
+        )]
+        fn it_works() {
+            assert_eq!(2 + 2, 4);
+        }
+    });
+    assert_snapshot!(output);
+}
+
+/// Check expansion of a `#[test_fork::test]` test forking only on
+/// some targets.
+#[test]
+fn snapshot_test_fork_if() {
+    let output = expand(parse_quote! {
+        #[test_fork::test(fork_if(any(target_os = "linux", target_os = "macos")))]
+        fn it_works() {
+            assert_eq!(2 + 2, 4);
+        }
+    });
+    assert_snapshot!(output);
+}
+
+/// Check expansion of a `#[test_fork::test]` test forking only if an
+/// environment variable is set.
+#[test]
+fn snapshot_test_opt_in_env() {
+    let output = expand(parse_quote! {
+        #[test_fork::test(opt_in_env = "TEST_FORK")]
+        fn it_works() {
+            assert_eq!(2 + 2, 4);
+        }
+    });
+    assert_snapshot!(output);
+}
+
+/// Check expansion of a `#[test_fork::test]` test expected to panic.
+#[test]
+fn snapshot_test_should_panic() {
+    let output = expand(parse_quote! {
+        #[test_fork::test]
+        #[should_panic(expected = "boom")]
+        fn it_works() {
+            panic!("boom");
+        }
+    });
+    assert_snapshot!(output);
+
+    let output = expand(parse_quote! {
+        #[test_fork::test]
+        #[should_panic]
+        fn it_works() -> Result<(), &'static str> {
+            Err("boom")
+        }
+    });
+    assert_snapshot!(output);
+}
+
+/// Check expansion of a `#[test_fork::test]` test run for a matrix of
+/// configurations.
+#[test]
+fn snapshot_test_matrix() {
+    let output = expand(parse_quote! {
+        #[test_fork::test(matrix(env("MODE" = "a"), env("MODE" = "b", "LEVEL" = "2")))]
+        fn it_works() {
+            assert!(std::env::var_os("MODE").is_some());
+        }
+    });
+    assert_snapshot!(output);
+}
+
+/// Check expansion of a `#[test_fork::test]` test run in multiple
+/// concurrent instances.
+#[test]
+fn snapshot_test_instances() {
+    let output = expand(parse_quote! {
+        #[test_fork::test(instances = 8)]
+        fn it_works() {
+            assert!(std::env::var_os("TEST_FORK_INSTANCE").is_some());
+        }
+    });
+    assert_snapshot!(output);
+}
+
+/// Check expansion of a `#[test_fork::test]` test using a fixture.
+#[test]
+fn snapshot_test_fixture() {
+    let output = expand(parse_quote! {
+        #[test_fork::test(fixture = start_server)]
+        fn it_works(port: u16) {
+            assert_ne!(port, 0);
+        }
+    });
+    assert_snapshot!(output);
+}
+
+/// Check expansion of an async `#[test_fork::test]` test running on a
+/// Tokio runtime.
+#[test]
+fn snapshot_test_tokio() {
+    let output = expand(parse_quote! {
+        #[test_fork::test(tokio(flavor = "multi_thread", worker_threads = 2))]
+        async fn it_works() {
+            assert_eq!(2 + 2, 4);
+        }
+    });
+    assert_snapshot!(output);
+}
+
+/// Check expansion of an async `#[test_fork::test]` test running on a
+/// custom runtime.
+#[test]
+fn snapshot_test_runtime() {
+    let output = expand(parse_quote! {
+        #[test_fork::test(runtime = smol)]
+        async fn it_works() {
+            assert_eq!(2 + 2, 4);
+        }
+    });
+    assert_snapshot!(output);
+
+    let output = expand(parse_quote! {
+        #[test_fork::test(runtime = my_runtime::block_on)]
+        async fn it_works() {
+            assert_eq!(2 + 2, 4);
+        }
+    });
+    assert_snapshot!(output);
+}
+
+/// Check that `#[test_fork::test]` defers to an inner third-party test
+/// attribute.
+#[test]
+fn snapshot_test_third_party() {
+    let output = expand(parse_quote! {
+        #[test_fork::test(serial = "db")]
+        #[tokio::test(flavor = "multi_thread")]
+        async fn it_works() {
+            assert_eq!(2 + 2, 4);
+        }
+    });
+    assert_snapshot!(output);
+
+    let output = expand(parse_quote! {
+        #[test_fork::fork]
+        #[rstest]
+        #[case(2)]
+        fn it_works(#[case] value: u32) {
+            assert_eq!(value + 2, 4);
+        }
+    });
+    assert_snapshot!(output);
+}
+
 /// Check expansion of a plain `#[test_fork::fork]` test.
 #[test]
 fn snapshot_fork_attr() {
@@ -109,3 +294,64 @@ fn snapshot_bench_attr() {
     });
     assert_snapshot!(output);
 }
+
+/// Check expansion of a `#[test_fork::bench]` test on a stable
+/// toolchain.
+#[test]
+fn snapshot_bench_stable() {
+    let input = parse_quote! {
+        fn bench_it(b: &mut Bencher) {
+            let () = b.iter(|| 2 + 2);
+        }
+    };
+    let tokens = test_fork_core::try_bench_stable(TokenStream::new(), input).unwrap();
+    assert_snapshot!(unparse(tokens));
+}
+
+/// Check expansion of a `#[test_fork::bench]` test configuring the
+/// measurement loop.
+#[test]
+fn snapshot_bench_args() {
+    let output = expand(parse_quote! {
+        #[test_fork::bench(warmup_iters = 10, min_iters = 1000, measure = "cpu")]
+        fn bench_it(b: &mut Bencher) {
+            let () = b.iter(|| 2 + 2);
+        }
+    });
+    assert_snapshot!(output);
+}
+
+/// Check expansion of a `#[test_fork::main]` function.
+#[test]
+fn snapshot_main() {
+    let output = expand(parse_quote! {
+        #[test_fork::main(bodies(crash, tests::hang))]
+        fn main() {
+            println!("hello");
+        }
+    });
+    assert_snapshot!(output);
+}
+
+/// Check expansion of a `#[test_fork::config]` module.
+#[test]
+fn snapshot_config() {
+    let input: ItemMod = parse_quote! {
+        mod tests {
+            #[test_fork::test]
+            fn inherits() {}
+
+            #[test_fork::test(heartbeat_ms = 60000, tokio)]
+            async fn overrides() {}
+
+            #[test_fork::config(nice = 5)]
+            mod nested {
+                #[test_fork::test]
+                fn nested() {}
+            }
+        }
+    };
+    let attr = quote! { heartbeat_ms = 10000, runtime = smol, nice = 10 };
+    let tokens = test_fork_core::try_config(attr, input).unwrap();
+    assert_snapshot!(unparse(tokens));
+}
@@ -10,10 +10,13 @@ use proc_macro::TokenStream;
 
 use syn::parse_macro_input;
 use syn::ItemFn;
+use syn::ItemMod;
 
-#[cfg(all(feature = "unstable", feature = "unsound"))]
 use test_fork_core::try_bench;
+use test_fork_core::try_bench_stable;
+use test_fork_core::try_config;
 use test_fork_core::try_fork;
+use test_fork_core::try_main;
 use test_fork_core::try_test;
 
 
@@ -38,6 +41,109 @@ use test_fork_core::try_test;
 ///   assert_eq!(2 + 3, 5);
 /// }
 /// ```
+///
+/// # Arguments
+///
+/// The attribute optionally accepts a comma separated list of
+/// arguments configuring how the child process is run:
+/// - `heartbeat_ms = <ms>`: enable heartbeat based hang detection,
+///   considering the child hung if the test did not report progress
+///   via `test_fork::heartbeat` for the given number of milliseconds
+///   (see
+///   [`Builder::heartbeat`][test_fork_core::Builder::heartbeat])
+/// - `output_limit = <bytes>`: drop the child's output beyond the
+///   given number of bytes per stream, emitting a truncation marker in
+///   its place (see
+///   [`Builder::output_limit`][test_fork_core::Builder::output_limit])
+/// - `prefix_output`: prefix each line of the child's forwarded output
+///   with the name of the test (see
+///   [`Builder::prefix_output`][test_fork_core::Builder::prefix_output])
+/// - `persist_output`: write the child's output to files below Cargo's
+///   target directory, keeping them if the child fails (see
+///   [`Builder::persist_output`][test_fork_core::Builder::persist_output])
+/// - `artifacts = "<treatment>"`: provide the child with a directory for
+///   storing artifacts, available via
+///   [`artifact_dir`][test_fork_core::artifact_dir], and either `keep`
+///   it or keep it only if the child fails (`on_failure`) (see
+///   [`Builder::artifacts`][test_fork_core::Builder::artifacts])
+/// - `color = "<mode>"`: either leave the use of colors to the child
+///   (`auto`, the default), ask the child to use colors even though
+///   its output is piped (`always`), or strip colors from the forwarded
+///   output (`never`) (see
+///   [`Builder::color`][test_fork_core::Builder::color])
+/// - `sanitizer_check`: fail the test if the child emits a sanitizer
+///   report (see
+///   [`Builder::sanitizer_check`][test_fork_core::Builder::sanitizer_check])
+/// - `leak_check`: fail the test if the child leaves behind any
+///   processes it spawned (see
+///   [`Builder::leak_check`][test_fork_core::Builder::leak_check])
+/// - `raw_fork`: create the child process via a plain `fork`, without
+///   `exec`; requires the `raw-fork` feature and comes with severe
+///   caveats (see
+///   [`Builder::raw_fork`][test_fork_core::Builder::raw_fork])
+/// - `fork_if(<predicate>)`: run the test in a child process only if
+///   the given `cfg` predicate holds (e.g.,
+///   `fork_if(target_os = "linux")`) and in-process otherwise (see
+///   [`Builder::in_process`][test_fork_core::Builder::in_process])
+/// - `opt_in_env = "<var>"`: run the test in a child process only if
+///   the given environment variable is set to a non-empty value other
+///   than `0` and in-process otherwise (see
+///   [`Builder::opt_in_env`][test_fork_core::Builder::opt_in_env])
+/// - `debug`: make the child wait for a debugger to attach before
+///   running the test (see
+///   [`Builder::debug`][test_fork_core::Builder::debug])
+/// - `nice = <n>`: set the niceness of the child process (see
+///   [`Builder::nice`][test_fork_core::Builder::nice])
+/// - `cpu_affinity = [<cpu>, ...]`: restrict the child process to
+///   running on the CPUs with the given indices (see
+///   [`Builder::cpu_affinity`][test_fork_core::Builder::cpu_affinity])
+/// - `clean_env` or `clean_env(allow = ["<var>", ...])`: start the
+///   child from an environment containing only the listed variables
+///   (see
+///   [`Builder::env_clear_except`][test_fork_core::Builder::env_clear_except])
+/// - `expected_output = "<path>"`: fail the test if its output differs
+///   from the contents of the given file, relative to the crate's root
+///   directory (see
+///   [`Builder::expected_output`][test_fork_core::Builder::expected_output])
+/// - `stdin = "<data>"` or `stdin_file = "<path>"`: feed the given
+///   data or the contents of the given file, relative to the crate's
+///   root directory, to the child's standard input (see
+///   [`Builder::stdin`][test_fork_core::Builder::stdin] and
+///   [`Builder::stdin_file`][test_fork_core::Builder::stdin_file])
+/// - `before = <fn>` and `after = <fn>`: run the given functions in the
+///   parent process before the child is spawned and after it exited,
+///   respectively (see
+///   [`Builder::before`][test_fork_core::Builder::before] and
+///   [`Builder::after`][test_fork_core::Builder::after])
+/// - `fixture = <fn>`: run the given function in the parent process and
+///   pass the value it produces to the test, which has to accept it as
+///   its single argument; requires the `serde` feature (see
+///   [`Builder::fork_with_fixture`][test_fork_core::Builder::fork_with_fixture])
+/// - `matrix(env("<var>" = "<value>", ...), ...)`: run the test in one
+///   child process per listed configuration, each with the given
+///   environment variables set, and fail if any of them fails,
+///   reporting the offending configurations (see
+///   [`Builder::fork_matrix`][test_fork_core::Builder::fork_matrix])
+/// - `instances = <n>`: run the given number of instances of the child
+///   process concurrently and fail if any of them fails, reporting the
+///   offending instances (see
+///   [`Builder::fork_instances`][test_fork_core::Builder::fork_instances])
+/// - `tokio` or `tokio(flavor = "<flavor>", worker_threads = <n>)`: run
+///   an async test on a Tokio runtime, which is created only in the
+///   child process; `flavor` is either `current_thread` (the default)
+///   or `multi_thread` and `worker_threads` sets the number of worker
+///   threads of the latter
+/// - `runtime = <runtime>`: run an async test on the given runtime in
+///   the child process, which is either one of `async_std`, `smol`, and
+///   `tokio`, or the path of a function running a future to completion
+///   akin to `block_on` (e.g., `runtime = my_runtime::block_on`)
+///
+/// ```rust,ignore
+/// #[test_fork::test(heartbeat_ms = 5000)]
+/// fn test3() {
+///   assert_eq!(2 + 4, 6);
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
     let input_fn = parse_macro_input!(item as ItemFn);
@@ -68,15 +174,57 @@ pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
 /// fn bench2(b: &mut Bencher) {
 ///   b.iter(|| sleep(Duration::from_millis(1)));
 /// }
-#[cfg(all(feature = "unstable", feature = "unsound"))]
-#[cfg_attr(docsrs, doc(cfg(all(feature = "unstable", feature = "unsound"))))]
+/// ```
+///
+/// The attribute accepts the same arguments as #[[macro@test]]. In
+/// addition, the following arguments configure the measurement loop
+/// run in the child process:
+/// - `warmup_iters = <n>`: run the benchmark's function this many
+///   times before starting to measure (see
+///   [`Builder::warmup_iters`][test_fork_core::Builder::warmup_iters])
+/// - `min_iters = <n>`: measure at least this many iterations (see
+///   [`Builder::min_iters`][test_fork_core::Builder::min_iters])
+/// - `measure = "<clock>"`: measure either `wall` clock time (the
+///   default) or the `cpu` time of the benchmarking thread (see
+///   [`Builder::measure`][test_fork_core::Builder::measure])
+///
+/// If any of these is provided, the benchmark's function is invoked
+/// with a [`test_fork_core::Bencher`] instead of the harness' `Bencher`
+/// and the measurements are reported on standard error (see
+/// [`Builder::fork_bench`][test_fork_core::Builder::fork_bench]).
+///
+/// ```rust,ignore
+/// #[test_fork::bench(warmup_iters = 10, measure = "cpu")]
+/// fn bench3(b: &mut Bencher) {
+///   b.iter(|| (0..1000).sum::<u64>());
+/// }
+/// ```
+///
+/// Unless both the `unstable` and `unsound` features are enabled, the
+/// harness' `Bencher` is not available and the benchmark is turned into
+/// a regular test, which runs the benchmark's function with a
+/// [`test_fork_core::Bencher`] (re-exported as `test_fork::Bencher`)
+/// in the child process. That works on stable toolchains, without
+/// `#![feature(test)]`.
+///
+/// ```rust,ignore
+/// use test_fork::Bencher;
+///
+/// #[test_fork::bench]
+/// fn bench4(b: &mut Bencher) {
+///   b.iter(|| (0..1000).sum::<u64>());
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn bench(attr: TokenStream, item: TokenStream) -> TokenStream {
     let input_fn = parse_macro_input!(item as ItemFn);
 
-    try_bench(attr.into(), input_fn)
-        .unwrap_or_else(syn::Error::into_compile_error)
-        .into()
+    let result = if cfg!(all(feature = "unstable", feature = "unsound")) {
+        try_bench(attr.into(), input_fn)
+    } else {
+        try_bench_stable(attr.into(), input_fn)
+    };
+    result.unwrap_or_else(syn::Error::into_compile_error).into()
 }
 
 
@@ -111,6 +259,8 @@ pub fn bench(attr: TokenStream, item: TokenStream) -> TokenStream {
 ///   b.iter(|| sleep(Duration::from_millis(1)));
 /// }
 /// ```
+///
+/// The attribute accepts the same arguments as #[[macro@test]].
 #[proc_macro_attribute]
 pub fn fork(attr: TokenStream, item: TokenStream) -> TokenStream {
     let supports_bench = cfg!(all(feature = "unstable", feature = "unsound"));
@@ -120,3 +270,85 @@ pub fn fork(attr: TokenStream, item: TokenStream) -> TokenStream {
         .unwrap_or_else(syn::Error::into_compile_error)
         .into()
 }
+
+
+/// A procedural macro for forking from a custom `main` function.
+///
+/// Binaries not run by the standard test harness, such as examples,
+/// `harness = false` test targets, or fuzz drivers, have to annotate
+/// their `main` function with this attribute in order to fork. The
+/// command line of such a binary is passed on to child processes
+/// unchanged.
+///
+/// Each child process starts out running `main` again. Functions listed
+/// via the `bodies(<fn>, ...)` argument are dispatched to directly in a
+/// child spawned for the test of the same name, without running `main`
+/// itself. A child spawned for any other test runs `main` up to the
+/// fork point it was spawned for, which hence has to be reached the
+/// same way as in the parent.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use test_fork::test_fork_core::fork_id;
+/// use test_fork::test_fork_core::Builder;
+///
+/// fn crash() {
+///   std::process::abort()
+/// }
+///
+/// #[test_fork::main(bodies(crash))]
+/// fn main() {
+///   let failure = Builder::new()
+///     .try_fork(fork_id!(), "crash", crash)
+///     .unwrap_err();
+///   println!("child failed: {failure}");
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn main(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let input_fn = parse_macro_input!(item as ItemFn);
+
+    try_main(attr.into(), input_fn)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+
+/// A procedural macro for configuring all tests of a module.
+///
+/// The attribute accepts the same arguments as #[[macro@test]] and
+/// applies them to every #[[macro@test]], #[[macro@fork]], and
+/// #[[macro@bench]] attribute inside the (inline) module it is put on,
+/// including those in nested modules. Arguments provided to a test
+/// directly take precedence over those configured for its module, as do
+/// those of a nested `#[test_fork::config]`. A runtime (`tokio` or
+/// `runtime = <runtime>`) is only applied to `async` functions.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// #[test_fork::config(heartbeat_ms = 10000, clean_env(allow = ["PATH"]))]
+/// mod tests {
+///   #[test_fork::test]
+///   fn test1() {
+///     assert_eq!(2 + 2, 4);
+///   }
+///
+///   #[test_fork::test(heartbeat_ms = 60000)]
+///   fn test2() {
+///     assert_eq!(2 + 3, 5);
+///   }
+/// }
+/// ```
+///
+/// Note that only attributes spelled out as `#[test_fork::<name>]` are
+/// recognized.
+#[proc_macro_attribute]
+pub fn config(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let input_mod = parse_macro_input!(item as ItemMod);
+
+    try_config(attr.into(), input_mod)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
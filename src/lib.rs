@@ -7,8 +7,17 @@
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
 pub use test_fork_core;
-#[cfg(all(feature = "unstable", feature = "unsound"))]
-#[cfg_attr(docsrs, doc(cfg(all(feature = "unstable", feature = "unsound"))))]
 pub use test_fork_macros::bench;
+pub use test_fork_macros::config;
 pub use test_fork_macros::fork;
+pub use test_fork_macros::main;
 pub use test_fork_macros::test;
+
+pub use test_fork_core::artifact_dir;
+pub use test_fork_core::fork_info;
+pub use test_fork_core::heartbeat;
+pub use test_fork_core::reserved_ports;
+pub use test_fork_core::seed;
+pub use test_fork_core::unique_token;
+pub use test_fork_core::Bencher;
+pub use test_fork_core::ForkInfo;
@@ -0,0 +1,48 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+//! Tests for [`test-fork`] in a binary not run by the standard test
+//! harness.
+
+use test_fork::fork_info;
+use test_fork::test_fork_core::fork_id;
+use test_fork::test_fork_core::Builder;
+use test_fork::test_fork_core::ChildFailure;
+
+
+fn registered() {
+    assert_eq!(fork_info().unwrap().test_name(), "registered");
+}
+
+fn failing() {
+    panic!("failing as requested")
+}
+
+#[test_fork::main(bodies(registered, failing))]
+fn main() {
+    // Children spawned for registered bodies never run `main`.
+    let info = fork_info();
+    assert!(info
+        .as_ref()
+        .is_none_or(|info| info.test_name() == "unregistered"));
+
+    // A child for a body that is not registered runs `main` up to the
+    // fork point, so it has to come first.
+    let () = Builder::new()
+        .fork(fork_id!(), "unregistered", || {
+            assert_eq!(fork_info().unwrap().test_name(), "unregistered");
+        })
+        .unwrap();
+
+    let () = Builder::new()
+        .fork(fork_id!(), "registered", registered)
+        .unwrap();
+
+    let failure = Builder::new()
+        .try_fork(fork_id!(), "failing", failing)
+        .unwrap_err();
+    assert!(
+        matches!(&failure, ChildFailure::Panicked { message, .. } if message == "failing as requested"),
+        "{failure:?}"
+    );
+}
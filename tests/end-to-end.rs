@@ -3,7 +3,24 @@
 
 //! End-to-end tests for [`test-fork`].
 
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::future::Future;
+use std::io;
+use std::io::Read as _;
+use std::net::TcpListener;
+use std::path::Path;
+use std::path::PathBuf;
 use std::process;
+use std::thread;
+use std::time::Duration;
+
+use test_fork::test_fork_core::Builder;
+use test_fork::test_fork_core::ChildFailure;
+use test_fork::test_fork_core::ForkOutcome;
+use tokio::runtime::Builder as RuntimeBuilder;
+use tokio::task;
 
 
 #[test_fork::test]
@@ -35,16 +52,207 @@ fn panicking_child() {
     panic!("just testing a panic, nothing to see here")
 }
 
-#[test_fork::test]
-#[should_panic]
+/// Check that a crashing child does not count as the panic a test
+/// expects.
+#[test]
 fn aborting_child() {
-    process::abort()
+    let () = Builder::new()
+        .should_panic(true)
+        .fork(
+            test_fork::test_fork_core::fork_id!(),
+            test_fork::test_fork_core::fork_test_name!(aborting_child),
+            || process::abort(),
+        )
+        .unwrap();
 }
 
 #[test_fork::fork]
 #[test]
 fn fork_attr() {}
 
+#[test_fork::test(heartbeat_ms = 5000)]
+fn heartbeat() {}
+
+#[test_fork::test(clean_env(allow = ["PATH"]))]
+fn clean_env() {
+    assert_eq!(env::var_os("CARGO_MANIFEST_DIR"), None);
+}
+
+#[test_fork::config(clean_env(allow = ["PATH"]))]
+mod configured {
+    use super::*;
+
+    #[test_fork::test]
+    fn inherited() {
+        assert_eq!(env::var_os("CARGO_MANIFEST_DIR"), None);
+    }
+
+    #[test_fork::test(clean_env(allow = ["PATH", "CARGO_MANIFEST_DIR"]))]
+    fn overridden() {
+        assert_ne!(env::var_os("CARGO_MANIFEST_DIR"), None);
+    }
+
+    #[test_fork::config(clean_env(allow = ["CARGO_MANIFEST_DIR"]))]
+    mod nested {
+        use super::*;
+
+        #[test_fork::test]
+        fn inherited_nested() {
+            assert_ne!(env::var_os("CARGO_MANIFEST_DIR"), None);
+            assert_eq!(env::var_os("PATH"), None);
+        }
+    }
+}
+
+#[test_fork::test(matrix(env("TEST_FORK_MODE" = "a"), env("TEST_FORK_MODE" = "b")))]
+fn matrix() {
+    let mode = env::var("TEST_FORK_MODE").unwrap();
+    assert!(mode == "a" || mode == "b", "{mode}");
+}
+
+#[test_fork::test(instances = 4)]
+fn instances() {
+    let instance = env::var("TEST_FORK_INSTANCE").unwrap();
+    assert!(instance.parse::<usize>().unwrap() < 4, "{instance}");
+}
+
+#[test_fork::test(fork_if(all()))]
+fn fork_if_forked() {
+    assert!(env::var_os("TEST_FORK_OCCURS").is_some());
+}
+
+#[test_fork::test(fork_if(any()))]
+fn fork_if_in_process() {
+    assert_eq!(env::var_os("TEST_FORK_OCCURS"), None);
+}
+
+#[test_fork::test(opt_in_env = "CARGO_MANIFEST_DIR")]
+fn opt_in_env_forked() {
+    assert!(env::var_os("TEST_FORK_OCCURS").is_some());
+}
+
+#[test_fork::test(opt_in_env = "TEST_FORK_END_TO_END_UNSET")]
+fn opt_in_env_in_process() {
+    assert_eq!(env::var_os("TEST_FORK_OCCURS"), None);
+}
+
+#[test_fork::test(nice = 3, cpu_affinity = [0])]
+fn scheduling() {}
+
+/// A marker of a running child, removed when dropped.
+struct Marker(PathBuf);
+
+impl Drop for Marker {
+    fn drop(&mut self) {
+        let _result = fs::remove_file(&self.0);
+    }
+}
+
+/// Check that children of tests using the same serialization lock
+/// never run concurrently.
+fn serialized() {
+    // Each child leaves a marker in a directory specific to the current
+    // test run, so that markers of aborted earlier runs can't interfere.
+    let parent_pid = test_fork::fork_info().unwrap().parent_pid();
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("serial-{parent_pid}"));
+    let () = fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(process::id().to_string());
+    let _file = File::create_new(&path).unwrap();
+    let _marker = Marker(path);
+
+    let running = || fs::read_dir(&dir).unwrap().count();
+    assert_eq!(running(), 1, "serialized children overlapped");
+    let () = thread::sleep(Duration::from_millis(100));
+    assert_eq!(running(), 1, "serialized children overlapped");
+}
+
+#[test_fork::test(serial = "end-to-end")]
+fn serial1() {
+    serialized()
+}
+
+#[test_fork::test(serial = "end-to-end")]
+fn serial2() {
+    serialized()
+}
+
+#[test_fork::test(reserve_ports = 1)]
+fn reserved_port() {
+    let port = *test_fork::reserved_ports().first().unwrap();
+    let _listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+}
+
+#[test_fork::test(expected_output = "tests/golden/expected_output.txt")]
+fn expected_output() {
+    println!("Hello, world!");
+    println!("The answer is {}", 42);
+}
+
+#[test_fork::test(stdin = "Hello, world!\n")]
+fn stdin() {
+    let mut line = String::new();
+    let _count = io::stdin().read_line(&mut line).unwrap();
+    assert_eq!(line, "Hello, world!\n");
+}
+
+#[test_fork::test(stdin_file = "tests/golden/expected_output.txt")]
+fn stdin_file() {
+    let mut input = String::new();
+    let _count = io::stdin().read_to_string(&mut input).unwrap();
+    assert_eq!(input, "Hello, world!\nThe answer is 42\n");
+}
+
+fn set_up(builder: &mut Builder) {
+    let _builder = builder.env("TEST_FORK_END_TO_END", "hooked");
+}
+
+fn tear_down(result: &Result<ForkOutcome, ChildFailure>) {
+    assert!(result.is_ok());
+}
+
+#[test_fork::test(before = set_up, after = tear_down)]
+fn hooks() {
+    assert_eq!(env::var("TEST_FORK_END_TO_END").unwrap(), "hooked");
+}
+
+#[cfg(feature = "serde")]
+fn fixture() -> (u32, String) {
+    (process::id(), "fixture".to_string())
+}
+
+#[cfg(feature = "serde")]
+#[test_fork::test(fixture = fixture)]
+fn with_fixture((pid, name): (u32, String)) {
+    assert_ne!(pid, process::id());
+    assert_eq!(name, "fixture");
+}
+
+#[test_fork::test(tokio)]
+async fn tokio_runtime() {
+    let () = task::yield_now().await;
+}
+
+#[test_fork::test(tokio(flavor = "multi_thread", worker_threads = 2))]
+async fn tokio_runtime_multi_thread() {
+    let value = task::spawn(async { 42 }).await.unwrap();
+    assert_eq!(value, 42);
+}
+
+fn block_on<F>(future: F) -> F::Output
+where
+    F: Future,
+{
+    RuntimeBuilder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+#[test_fork::test(runtime = block_on)]
+async fn custom_runtime() {
+    let () = task::yield_now().await;
+}
+
 #[tokio::test]
 #[test_fork::test]
 async fn async_test() {}
@@ -59,3 +267,24 @@ async fn async_test_fork_attr() {}
 async fn async_test_panicking() {
     panic!("panic makes the world go 'round")
 }
+
+#[test_fork::test]
+#[tokio::test]
+async fn async_test_inner_tokio() {
+    let () = task::yield_now().await;
+}
+
+#[test_fork::fork]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn async_test_fork_attr_inner_tokio() {
+    let value = task::spawn(async { 42 }).await.unwrap();
+    assert_eq!(value, 42);
+}
+
+/// Benchmark a computation in a different process, without relying on
+/// the unstable `libtest` benchmark infrastructure.
+#[cfg(not(all(feature = "unstable", feature = "unsound")))]
+#[test_fork::bench(min_iters = 10)]
+fn stable_benchmark(b: &mut test_fork::Bencher) {
+    b.iter(|| (0..1000u64).sum::<u64>())
+}
@@ -0,0 +1,12 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+use test_fork_core::fork_id;
+
+
+/// Check that user provided fork IDs are validated at compile time.
+fn invalid_id() {
+    let _id = fork_id!("invalid:id");
+}
+
+fn main() {}
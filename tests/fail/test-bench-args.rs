@@ -0,0 +1,8 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+/// Benchmark measurement options on a regular test.
+#[test_fork::test(warmup_iters = 10)]
+fn bench_args() {}
+
+fn main() {}
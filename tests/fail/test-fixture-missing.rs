@@ -0,0 +1,10 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+/// Test function with argument but without fixture.
+#[test_fork::test]
+fn fixture_missing(port: u16) {
+    assert_ne!(port, 0);
+}
+
+fn main() {}
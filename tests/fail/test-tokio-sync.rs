@@ -0,0 +1,8 @@
+// Copyright (C) 2026 Daniel Mueller <deso@posteo.net>
+// SPDX-License-Identifier: (Apache-2.0 OR MIT)
+
+/// Tokio runtime requested for non-async test.
+#[test_fork::test(tokio)]
+fn tokio_sync() {}
+
+fn main() {}
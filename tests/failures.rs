@@ -14,6 +14,10 @@ fn failures() {
     let () = t.compile_fail("tests/fail/test-invalid-args.rs");
     let () = t.compile_fail("tests/fail/fork-env-mut-capture.rs");
     let () = t.compile_fail("tests/fail/fork-no-inner-test.rs");
+    let () = t.compile_fail("tests/fail/fork-id-invalid.rs");
+    let () = t.compile_fail("tests/fail/test-fixture-missing.rs");
+    let () = t.compile_fail("tests/fail/test-tokio-sync.rs");
+    let () = t.compile_fail("tests/fail/test-bench-args.rs");
 
     if cfg!(all(feature = "unstable", feature = "unsound")) {
         let () = t.compile_fail("tests/fail/fork-no-inner-bench.rs");
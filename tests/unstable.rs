@@ -40,3 +40,11 @@ fn benchmark(b: &mut Bencher) {
 fn benchmark_fork_attr(b: &mut Bencher) {
     b.iter(action)
 }
+
+
+/// Benchmark an "action" in a different process, with a custom
+/// measurement loop.
+#[test_fork::bench(warmup_iters = 2, min_iters = 100, measure = "wall")]
+fn benchmark_measure(b: &mut Bencher) {
+    b.iter(action)
+}